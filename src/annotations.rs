@@ -0,0 +1,40 @@
+//! Well-known annotation keys. wadm stamps these on every actor and provider it manages, so they
+//! are kept here in one place rather than repeated as string literals by each consumer
+
+use crate::AnnotationMap;
+
+/// The name of the application (wadm manifest) a resource belongs to
+pub const APP_SPEC: &str = "wasmcloud.dev/appspec";
+/// The tool that manages a resource
+pub const MANAGED_BY: &str = "wasmcloud.dev/managed-by";
+/// The value of [`MANAGED_BY`] for resources managed by wadm
+pub const MANAGED_BY_WADM: &str = "wadm";
+/// The scaler within an application that created a resource
+pub const SCALER: &str = "wasmcloud.dev/scaler";
+/// The spread within a spread scaler that placed a resource
+pub const SPREAD_NAME: &str = "wasmcloud.dev/spread_name";
+
+/// Returns the annotations that mark a resource as belonging to the named wadm application
+pub fn app(name: &str) -> AnnotationMap {
+    AnnotationMap::from([
+        (APP_SPEC.to_string(), name.to_string()),
+        (MANAGED_BY.to_string(), MANAGED_BY_WADM.to_string()),
+    ])
+}
+
+/// Returns true if `actual` carries every one of the `wanted` annotations with the same value.
+/// Annotations that aren't wanted are ignored, so nothing wanted matches anything
+pub(crate) fn contains_all(actual: Option<&AnnotationMap>, wanted: &AnnotationMap) -> bool {
+    wanted.iter().all(|(key, value)| {
+        actual
+            .and_then(|actual| actual.get(key))
+            .is_some_and(|actual| actual == value)
+    })
+}
+
+/// Returns true if the annotations mark a resource as belonging to the named application
+pub fn is_app(annotations: Option<&AnnotationMap>, name: &str) -> bool {
+    annotations
+        .and_then(|annotations| annotations.get(APP_SPEC))
+        .is_some_and(|app| app == name)
+}
@@ -11,6 +11,11 @@ fn prefix(topic_prefix: &Option<String>, lattice_prefix: &str) -> String {
     )
 }
 
+/// A reply inbox under a custom prefix, made unique by `token`
+pub fn inbox(inbox_prefix: &str, token: &str) -> String {
+    format!("{inbox_prefix}.{token}")
+}
+
 pub fn control_event(lattice_prefix: &str) -> String {
     format!("{}.{}", EVT_TOPIC_PREFIX, lattice_prefix)
 }
@@ -35,6 +40,19 @@ pub fn publish_registries(topic_prefix: &Option<String>, lattice_prefix: &str) -
     format!("{}.registries.put", prefix(topic_prefix, lattice_prefix))
 }
 
+/// Registry credentials for a single host, e.g. one that just joined the lattice
+pub fn publish_registries_to_host(
+    topic_prefix: &Option<String>,
+    lattice_prefix: &str,
+    host: &str,
+) -> String {
+    format!(
+        "{}.registries.put.{}",
+        prefix(topic_prefix, lattice_prefix),
+        host
+    )
+}
+
 pub mod commands {
     use super::prefix;
 
@@ -83,6 +101,30 @@ pub mod commands {
     pub fn stop_host(topic_prefix: &Option<String>, lattice_prefix: &str, host: &str) -> String {
         format!("{}.cmd.{}.stop", prefix(topic_prefix, lattice_prefix), host)
     }
+
+    pub fn put_label(topic_prefix: &Option<String>, lattice_prefix: &str, host: &str) -> String {
+        format!(
+            "{}.labels.{}.put",
+            prefix(topic_prefix, lattice_prefix),
+            host
+        )
+    }
+
+    pub fn delete_label(topic_prefix: &Option<String>, lattice_prefix: &str, host: &str) -> String {
+        format!(
+            "{}.labels.{}.del",
+            prefix(topic_prefix, lattice_prefix),
+            host
+        )
+    }
+
+    pub fn batch(topic_prefix: &Option<String>, lattice_prefix: &str, host: &str) -> String {
+        format!(
+            "{}.cmd.{}.batch",
+            prefix(topic_prefix, lattice_prefix),
+            host
+        )
+    }
 }
 
 pub mod queries {
@@ -96,6 +138,10 @@ pub mod queries {
         format!("{}.get.claims", prefix(topic_prefix, lattice_prefix))
     }
 
+    pub fn registries(topic_prefix: &Option<String>, lattice_prefix: &str) -> String {
+        format!("{}.get.registries", prefix(topic_prefix, lattice_prefix))
+    }
+
     pub fn host_inventory(
         topic_prefix: &Option<String>,
         lattice_prefix: &str,
@@ -0,0 +1,194 @@
+//! Encoding of control interface payloads, negotiated per request with a content type header
+
+use async_nats::header::HeaderMap;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::otel::OtelHeaderInjector;
+use crate::{Error, Result};
+
+/// The header that names the encoding of a request or reply payload
+pub const CONTENT_TYPE_HEADER: &str = "Content-Type";
+
+/// How a client encodes the payloads of its requests, set with [`crate::ClientBuilder::codec`].
+/// Every request carries a content type header naming its encoding. Replies are decoded according
+/// to their own content type header, and as JSON if they don't have one, so a client that sends
+/// MessagePack still understands hosts that always answer in JSON
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Codec {
+    /// JSON, which every host understands
+    #[default]
+    Json,
+    /// MessagePack, which is smaller and faster to parse for large payloads such as inventories
+    /// and link definition lists. Only use this with hosts that accept it
+    MsgPack,
+}
+
+impl Codec {
+    /// The content type that identifies this encoding
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Codec::Json => "application/json",
+            Codec::MsgPack => "application/msgpack",
+        }
+    }
+
+    /// Returns the encoding a content type names, if it's one this crate supports
+    pub fn from_content_type(content_type: &str) -> Option<Codec> {
+        match content_type.trim() {
+            "application/json" => Some(Codec::Json),
+            "application/msgpack" | "application/x-msgpack" => Some(Codec::MsgPack),
+            _ => None,
+        }
+    }
+
+    /// The encoding of a message with the given headers, which is JSON unless its content type
+    /// header says otherwise
+    pub(crate) fn of_message(headers: Option<&HeaderMap>) -> Codec {
+        headers
+            .and_then(|headers| headers.get(CONTENT_TYPE_HEADER))
+            .and_then(|content_type| Codec::from_content_type(content_type.as_str()))
+            .unwrap_or_default()
+    }
+
+    /// The headers to send with a request in this encoding, including the tracing context
+    pub(crate) fn headers(&self) -> HeaderMap {
+        let mut headers: HeaderMap = OtelHeaderInjector::default_with_span().into();
+        headers.insert(CONTENT_TYPE_HEADER, self.content_type());
+        headers
+    }
+
+    /// Serializes `item` in this encoding
+    pub(crate) fn serialize<T: Serialize>(&self, item: T) -> Result<Vec<u8>> {
+        match self {
+            Codec::Json => serde_json::to_vec(&item).map_err(Error::Serialization),
+            // Named fields keep the encoding self-describing, which the optional and defaulted
+            // fields throughout the wire types rely on
+            Codec::MsgPack => rmp_serde::to_vec_named(&item).map_err(|e| Error::MsgPack(e.into())),
+        }
+    }
+
+    /// Deserializes a payload in this encoding
+    pub(crate) fn deserialize<T: DeserializeOwned>(&self, buf: &[u8]) -> Result<T> {
+        match self {
+            Codec::Json => serde_json::from_slice(buf).map_err(Error::Serialization),
+            Codec::MsgPack => rmp_serde::from_slice(buf).map_err(|e| Error::MsgPack(e.into())),
+        }
+    }
+}
+
+/// A reply whose payload can be decoded according to its content type
+pub(crate) trait Reply {
+    fn codec(&self) -> Codec;
+
+    fn payload(&self) -> &[u8];
+}
+
+impl Reply for async_nats::Message {
+    fn codec(&self) -> Codec {
+        Codec::of_message(self.headers.as_ref())
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+/// Bare payloads, as used in tests, are always JSON
+impl Reply for Vec<u8> {
+    fn codec(&self) -> Codec {
+        Codec::Json
+    }
+
+    fn payload(&self) -> &[u8] {
+        self
+    }
+}
+
+/// Deserializes a reply according to its content type
+pub(crate) fn decode<T: DeserializeOwned>(reply: &impl Reply) -> Result<T> {
+    reply.codec().deserialize(reply.payload())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+
+    use super::*;
+    use crate::compat::{fixture, Variant};
+    use crate::*;
+
+    /// Asserts that the fully populated value of a wire type survives a round trip through both
+    /// encodings
+    fn assert_round_trips<T>(name: &str)
+    where
+        T: Serialize + DeserializeOwned + PartialEq + Debug,
+    {
+        let fixture = fixture(name, Variant::Full).expect("fixture should exist");
+        let value: T = serde_json::from_str(fixture.json).unwrap();
+        for codec in [Codec::Json, Codec::MsgPack] {
+            let bytes = codec.serialize(&value).unwrap();
+            let decoded: T = codec
+                .deserialize(&bytes)
+                .unwrap_or_else(|e| panic!("{name} does not round trip as {codec:?}: {e}"));
+            assert_eq!(decoded, value, "{name} as {codec:?}");
+        }
+    }
+
+    #[test]
+    fn responses_round_trip() {
+        assert_round_trips::<HostInventory>("host_inventory");
+        assert_round_trips::<LinkDefinitionList>("link_definition_list");
+        assert_round_trips::<Host>("host");
+        assert_round_trips::<CtlOperationAck>("ctl_operation_ack");
+        assert_round_trips::<CommandBatchResponse>("command_batch_response");
+        assert_round_trips::<GetClaimsResponse>("get_claims_response");
+        assert_round_trips::<ActorAuctionAck>("actor_auction_ack");
+        assert_round_trips::<ProviderAuctionAck>("provider_auction_ack");
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        assert_round_trips::<ScaleActorCommand>("scale_actor_command");
+        assert_round_trips::<UpdateActorCommand>("update_actor_command");
+        assert_round_trips::<StartProviderCommand>("start_provider_command");
+        assert_round_trips::<StopProviderCommand>("stop_provider_command");
+        assert_round_trips::<StopActorCommand>("stop_actor_command");
+        assert_round_trips::<StopHostCommand>("stop_host_command");
+        assert_round_trips::<CommandBatch>("command_batch");
+        assert_round_trips::<ActorAuctionRequest>("actor_auction_request");
+        assert_round_trips::<ProviderAuctionRequest>("provider_auction_request");
+        assert_round_trips::<LinkDefinition>("link_definition");
+        assert_round_trips::<HostLabel>("host_label");
+    }
+
+    #[test]
+    fn replies_are_decoded_by_their_content_type() {
+        let ack = CtlOperationAck {
+            accepted: true,
+            error: String::new(),
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE_HEADER, Codec::MsgPack.content_type());
+        assert_eq!(Codec::of_message(Some(&headers)), Codec::MsgPack);
+        assert_eq!(Codec::of_message(None), Codec::Json);
+        assert_eq!(Codec::of_message(Some(&HeaderMap::new())), Codec::Json);
+
+        let msgpack = Codec::MsgPack.serialize(&ack).unwrap();
+        assert!(decode::<CtlOperationAck>(&msgpack).is_err());
+        let json = Codec::Json.serialize(&ack).unwrap();
+        assert_eq!(decode::<CtlOperationAck>(&json).unwrap(), ack);
+    }
+
+    #[test]
+    fn content_types_are_recognized() {
+        for codec in [Codec::Json, Codec::MsgPack] {
+            assert_eq!(Codec::from_content_type(codec.content_type()), Some(codec));
+        }
+        assert_eq!(
+            Codec::from_content_type("application/x-msgpack"),
+            Some(Codec::MsgPack)
+        );
+        assert_eq!(Codec::from_content_type("text/plain"), None);
+        assert_eq!(Codec::default(), Codec::Json);
+    }
+}
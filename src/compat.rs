@@ -0,0 +1,694 @@
+//! Wire-format compatibility fixtures for the control interface protocol.
+//!
+//! The types in this crate are the de-facto definition of the control interface wire format, so
+//! an accidental serde change breaks hosts and tools alike. Every command, response, and event
+//! payload type has a committed golden JSON file for both a minimal (default) and a fully
+//! populated value, and this crate's tests assert that serialization matches those files byte for
+//! byte. Host implementations can enable the `testing` feature and run their own decoders over
+//! [`fixtures`] to check that they agree with this crate.
+//!
+//! When the wire format is changed on purpose, regenerate the golden files by running the tests
+//! with `UPDATE_COMPAT_FIXTURES=1` set and commit the resulting diff alongside the change
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Which value of a type a [`Fixture`] holds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Variant {
+    /// The type's default value, with every optional field omitted
+    Minimal,
+    /// A value with every field populated
+    Full,
+}
+
+impl Variant {
+    fn extension(&self) -> &'static str {
+        match self {
+            Variant::Minimal => "min",
+            Variant::Full => "full",
+        }
+    }
+}
+
+/// A golden JSON document for one variant of one wire type
+#[derive(Clone, Copy, Debug)]
+pub struct Fixture {
+    /// The snake_case name of the type, e.g. `host_inventory`
+    pub name: &'static str,
+    pub variant: Variant,
+    /// The exact JSON this crate produces for the value
+    pub json: &'static str,
+}
+
+impl Fixture {
+    /// The path of the golden file, relative to the crate root
+    pub fn path(&self) -> String {
+        format!(
+            "fixtures/compat/{}.{}.json",
+            self.name,
+            self.variant.extension()
+        )
+    }
+
+    /// Checks that the fixture deserializes into `T` and serializes back to exactly the same bytes
+    pub fn check_roundtrip<T: Serialize + DeserializeOwned>(&self) -> Result<(), String> {
+        let value: T = serde_json::from_str(self.json)
+            .map_err(|e| format!("{} does not deserialize: {e}", self.path()))?;
+        let json = to_fixture_json(&value)?;
+        if json == self.json {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} does not serialize back to the same bytes:\n{json}",
+                self.path()
+            ))
+        }
+    }
+}
+
+/// Serializes a value the same way the golden files are written
+pub fn to_fixture_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string_pretty(value)
+        .map(|json| json + "\n")
+        .map_err(|e| format!("serialization failed: {e}"))
+}
+
+macro_rules! fixtures {
+    ($($name:literal),* $(,)?) => {
+        &[$(
+            Fixture {
+                name: $name,
+                variant: Variant::Minimal,
+                json: include_str!(concat!("../fixtures/compat/", $name, ".min.json")),
+            },
+            Fixture {
+                name: $name,
+                variant: Variant::Full,
+                json: include_str!(concat!("../fixtures/compat/", $name, ".full.json")),
+            },
+        )*]
+    };
+}
+
+const FIXTURES: &[Fixture] = fixtures![
+    "actor_auction_ack",
+    "actor_auction_request",
+    "actor_description",
+    "actor_instance",
+    "actor_start_failed",
+    "actor_started",
+    "actor_stopped",
+    "actor_update_failed",
+    "actor_updated",
+    "batch_report",
+    "command_batch",
+    "command_batch_response",
+    "ctl_operation_ack",
+    "generation_changed",
+    "get_claims_response",
+    "host",
+    "host_heartbeat",
+    "host_inventory",
+    "host_label",
+    "host_started",
+    "host_stopped",
+    "instance_stop",
+    "lattice_snapshot",
+    "link_definition",
+    "link_definition_list",
+    "named_config",
+    "progress_event",
+    "provider_auction_ack",
+    "provider_auction_request",
+    "provider_description",
+    "provider_start_failed",
+    "provider_started",
+    "provider_stopped",
+    "purge_plan",
+    "registry_credential",
+    "remove_link_definition_request",
+    "scale_actor_command",
+    "scale_report",
+    "start_actor_command",
+    "start_provider_command",
+    "stop_actor_command",
+    "stop_host_command",
+    "stop_provider_command",
+    "update_actor_command",
+];
+
+/// Returns every golden fixture
+pub fn fixtures() -> &'static [Fixture] {
+    FIXTURES
+}
+
+/// Returns the golden fixture for the given type name and variant
+pub fn fixture(name: &str, variant: Variant) -> Option<&'static Fixture> {
+    FIXTURES
+        .iter()
+        .find(|fixture| fixture.name == name && fixture.variant == variant)
+}
+
+#[cfg(test)]
+#[allow(deprecated)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+
+    use super::*;
+    use crate::*;
+
+    /// Asserts that `value` serializes to the golden file for `name`/`variant` and that the golden
+    /// file deserializes to `value`. With `UPDATE_COMPAT_FIXTURES` set, rewrites the golden file
+    /// instead
+    fn assert_wire_format<T>(name: &str, variant: Variant, value: &T)
+    where
+        T: Serialize + DeserializeOwned + PartialEq + Debug,
+    {
+        let fixture = fixture(name, variant).unwrap_or_else(|| panic!("no fixture for {name}"));
+        let json = to_fixture_json(value).unwrap();
+        if std::env::var_os("UPDATE_COMPAT_FIXTURES").is_some() {
+            let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(fixture.path());
+            std::fs::write(path, json).unwrap();
+            return;
+        }
+        assert_eq!(
+            json,
+            fixture.json,
+            "wire format of {} changed; if this is deliberate, rerun with UPDATE_COMPAT_FIXTURES=1",
+            fixture.path()
+        );
+        let parsed: T = serde_json::from_str(fixture.json).unwrap();
+        assert_eq!(&parsed, value);
+    }
+
+    fn assert_both<T>(name: &str, full: T)
+    where
+        T: Serialize + DeserializeOwned + PartialEq + Debug + Default,
+    {
+        assert_wire_format(name, Variant::Minimal, &T::default());
+        assert_wire_format(name, Variant::Full, &full);
+    }
+
+    /// Maps in fixtures hold a single entry so that their serialization order is stable
+    fn map(key: &str, value: &str) -> HashMap<String, String> {
+        HashMap::from([(key.to_string(), value.to_string())])
+    }
+
+    fn actor_instance() -> ActorInstance {
+        ActorInstance {
+            annotations: Some(map(annotations::APP_SPEC, "echo")),
+            image_ref: Some("wasmcloud.azurecr.io/echo:0.3.8".to_string()),
+            instance_id: "2e2b0a2c-7a3e-4b32-9bb9-c5b4f1f3e6a1".to_string(),
+            revision: 2,
+            max_concurrent: 10,
+        }
+    }
+
+    fn actor_description() -> ActorDescription {
+        ActorDescription {
+            id: "MBCFOPM6JW2APJLXJD3Z5O4CN7CPYJ2B4FTKLJUR5YR5MITIU7HD3WD5".to_string(),
+            image_ref: Some("wasmcloud.azurecr.io/echo:0.3.8".to_string()),
+            instances: vec![actor_instance()],
+            name: Some("Echo".to_string()),
+        }
+    }
+
+    fn provider_description() -> ProviderDescription {
+        ProviderDescription {
+            annotations: Some(map(annotations::APP_SPEC, "echo")),
+            id: "VAG3QITQQ2ODAOWB5TTQSDJ53XK3SHBEIFNK4AYJ5RKAX2UNSCAPHA5M".to_string(),
+            image_ref: Some("wasmcloud.azurecr.io/httpserver:0.17.0".to_string()),
+            contract_id: "wasmcloud:httpserver".to_string(),
+            link_name: "default".to_string(),
+            name: Some("HTTP Server".to_string()),
+            revision: 1,
+        }
+    }
+
+    fn link_definition() -> LinkDefinition {
+        LinkDefinition {
+            actor_id: "MBCFOPM6JW2APJLXJD3Z5O4CN7CPYJ2B4FTKLJUR5YR5MITIU7HD3WD5".to_string(),
+            provider_id: "VAG3QITQQ2ODAOWB5TTQSDJ53XK3SHBEIFNK4AYJ5RKAX2UNSCAPHA5M".to_string(),
+            link_name: "default".to_string(),
+            contract_id: "wasmcloud:httpserver".to_string(),
+            values: map("address", "0.0.0.0:8080"),
+        }
+    }
+
+    fn ack() -> CtlOperationAck {
+        CtlOperationAck {
+            accepted: false,
+            error: "actor not found".to_string(),
+        }
+    }
+
+    fn command_id() -> CommandId {
+        CommandId::from("5f0c7a2e-9b1d-4c3e-8a6f-2d4b6c8e0a1f")
+    }
+
+    fn scale_actor_command() -> ScaleActorCommand {
+        ScaleActorCommand {
+            actor_ref: "wasmcloud.azurecr.io/echo:0.3.8".to_string(),
+            annotations: Some(map(annotations::APP_SPEC, "echo")),
+            command_id: Some(command_id()),
+            max_concurrent: Some(5),
+            host_id: "NCPGH5CVAP4XGOBOCXNUMXAXSFXL2DFZG2KXDGD2RLNJBMVGGVD4VQ5P".to_string(),
+        }
+    }
+
+    #[test]
+    fn fixtures_come_in_pairs() {
+        for fixture in fixtures() {
+            assert!(super::fixture(fixture.name, Variant::Minimal).is_some());
+            assert!(super::fixture(fixture.name, Variant::Full).is_some());
+        }
+        assert_eq!(fixtures().len() % 2, 0);
+    }
+
+    #[test]
+    fn actor_types() {
+        assert_both(
+            "actor_auction_ack",
+            ActorAuctionAck {
+                actor_ref: "wasmcloud.azurecr.io/echo:0.3.8".to_string(),
+                host_id: "NCPGH5CVAP4XGOBOCXNUMXAXSFXL2DFZG2KXDGD2RLNJBMVGGVD4VQ5P".to_string(),
+                constraints: map("hostcore.os", "linux"),
+            },
+        );
+        assert_both(
+            "actor_auction_request",
+            ActorAuctionRequest {
+                actor_ref: "wasmcloud.azurecr.io/echo:0.3.8".to_string(),
+                constraints: map("hostcore.os", "linux"),
+            },
+        );
+        assert_both("actor_description", actor_description());
+        assert_both("actor_instance", actor_instance());
+    }
+
+    #[test]
+    fn event_types() {
+        let actor_id = "MBCFOPM6JW2APJLXJD3Z5O4CN7CPYJ2B4FTKLJUR5YR5MITIU7HD3WD5".to_string();
+        let provider_id = "VAG3QITQQ2ODAOWB5TTQSDJ53XK3SHBEIFNK4AYJ5RKAX2UNSCAPHA5M".to_string();
+        let instance_id = "2e2b0a2c-7a3e-4b32-9bb9-c5b4f1f3e6a1".to_string();
+        assert_both(
+            "actor_started",
+            ActorStarted {
+                public_key: actor_id.clone(),
+                image_ref: Some("wasmcloud.azurecr.io/echo:0.3.8".to_string()),
+                instance_id: instance_id.clone(),
+                annotations: Some(map(annotations::APP_SPEC, "echo")),
+            },
+        );
+        assert_both(
+            "actor_stopped",
+            ActorStopped {
+                public_key: actor_id.clone(),
+                instance_id: instance_id.clone(),
+                annotations: Some(map(annotations::APP_SPEC, "echo")),
+            },
+        );
+        assert_both(
+            "actor_start_failed",
+            ActorStartFailed {
+                actor_ref: "wasmcloud.azurecr.io/echo:0.3.8".to_string(),
+                error: "failed to fetch actor".to_string(),
+            },
+        );
+        assert_both(
+            "actor_updated",
+            ActorUpdated {
+                public_key: actor_id.clone(),
+                revision: 3,
+                instance_id: instance_id.clone(),
+            },
+        );
+        assert_both(
+            "actor_update_failed",
+            ActorUpdateFailed {
+                public_key: actor_id.clone(),
+                error: "new revision is not newer".to_string(),
+            },
+        );
+        assert_both(
+            "host_heartbeat",
+            HostHeartbeat {
+                actors: HashMap::from([(actor_id, 3)]),
+                providers: vec![HostHeartbeatProvider {
+                    public_key: provider_id.clone(),
+                    link_name: "default".to_string(),
+                    contract_id: "wasmcloud:httpserver".to_string(),
+                }],
+                friendly_name: "silent-bush-1234".to_string(),
+                labels: map("hostcore.os", "linux"),
+                uptime_seconds: 3600,
+                version: Some("0.78.0".to_string()),
+            },
+        );
+        assert_both(
+            "host_started",
+            HostStarted {
+                friendly_name: "silent-bush-1234".to_string(),
+                labels: map("hostcore.os", "linux"),
+            },
+        );
+        assert_both(
+            "host_stopped",
+            HostStopped {
+                labels: map("hostcore.os", "linux"),
+            },
+        );
+        assert_both(
+            "provider_started",
+            ProviderStarted {
+                public_key: provider_id.clone(),
+                image_ref: Some("wasmcloud.azurecr.io/httpserver:0.17.0".to_string()),
+                link_name: "default".to_string(),
+                contract_id: "wasmcloud:httpserver".to_string(),
+                instance_id: instance_id.clone(),
+                annotations: Some(map(annotations::APP_SPEC, "echo")),
+            },
+        );
+        assert_both(
+            "provider_start_failed",
+            ProviderStartFailed {
+                provider_ref: "wasmcloud.azurecr.io/httpserver:0.17.0".to_string(),
+                link_name: "default".to_string(),
+                error: "failed to fetch provider".to_string(),
+            },
+        );
+        assert_both(
+            "provider_stopped",
+            ProviderStopped {
+                public_key: provider_id,
+                link_name: "default".to_string(),
+                contract_id: "wasmcloud:httpserver".to_string(),
+                instance_id,
+                reason: "host shutdown".to_string(),
+            },
+        );
+    }
+
+    #[test]
+    fn batch_types() {
+        let batch = CommandBatch {
+            commands: vec![
+                BatchCommand::ScaleActor(scale_actor_command()),
+                BatchCommand::StopActor(StopActorCommand {
+                    actor_ref: "wasmcloud.azurecr.io/kvcounter:0.4.0".to_string(),
+                    annotations: None,
+                    command_id: None,
+                    host_id: "NCPGH5CVAP4XGOBOCXNUMXAXSFXL2DFZG2KXDGD2RLNJBMVGGVD4VQ5P".to_string(),
+                }),
+            ],
+            stop_on_failure: true,
+        };
+        assert_both(
+            "batch_report",
+            BatchReport {
+                acks: vec![ack()],
+                not_attempted: batch.commands.clone(),
+                deadline_exceeded: true,
+            },
+        );
+        assert_both("command_batch", batch);
+        assert_both(
+            "instance_stop",
+            InstanceStop {
+                annotations: map(annotations::APP_SPEC, "echo"),
+                instance_count: 2,
+                ack: ack(),
+            },
+        );
+        assert_both(
+            "purge_plan",
+            PurgePlan {
+                host_id: "NCPGH5CVAP4XGOBOCXNUMXAXSFXL2DFZG2KXDGD2RLNJBMVGGVD4VQ5P".to_string(),
+                claims: vec!["VAG3QITQQ2ODAOWB5TTQSDJ53XK3SHBEIFNK4AYJ5RKAX2UNSCAPHA5M".to_string()],
+                links: vec![link_definition()],
+            },
+        );
+        assert_both(
+            "scale_report",
+            ScaleReport {
+                previous_count: 1,
+                desired_count: 3,
+                changes: vec![HostScaleChange {
+                    host_id: "NCPGH5CVAP4XGOBOCXNUMXAXSFXL2DFZG2KXDGD2RLNJBMVGGVD4VQ5P".to_string(),
+                    from: 1,
+                    to: 3,
+                    ack: ack(),
+                }],
+                unreachable_hosts: vec![
+                    "NBZ7F4MMQFMWDKVH2ZKLYDBPNDDUSIYMYQQEGR5J4JCQ5JSY4SSSAT2V".to_string()
+                ],
+            },
+        );
+        assert_both(
+            "command_batch_response",
+            CommandBatchResponse { acks: vec![ack()] },
+        );
+    }
+
+    #[test]
+    fn response_types() {
+        assert_both("ctl_operation_ack", ack());
+        assert_both(
+            "generation_changed",
+            GenerationChanged {
+                previous: Some("17a2b3c4d5e6f7".to_string()),
+                current: Some("17a2b3c4d5e6f8".to_string()),
+            },
+        );
+        assert_both(
+            "get_claims_response",
+            GetClaimsResponse {
+                claims: vec![map(
+                    "sub",
+                    "MBCFOPM6JW2APJLXJD3Z5O4CN7CPYJ2B4FTKLJUR5YR5MITIU7HD3WD5",
+                )],
+            },
+        );
+        assert_both(
+            "link_definition_list",
+            LinkDefinitionList {
+                links: vec![link_definition()],
+            },
+        );
+        let host_id = "NCPGH5CVAP4XGOBOCXNUMXAXSFXL2DFZG2KXDGD2RLNJBMVGGVD4VQ5P".to_string();
+        let stale_host_id = "NBZ7F4MMQFMWDKVH2ZKLYDBPNDDUSIYMYQQEGR5J4JCQ5JSY4SSSAT2V".to_string();
+        assert_both(
+            "lattice_snapshot",
+            LatticeSnapshot {
+                captured_at_ms: 1_682_942_400_000,
+                hosts: Some(vec![
+                    Host {
+                        id: host_id.clone(),
+                        ..Default::default()
+                    },
+                    Host {
+                        id: stale_host_id.clone(),
+                        ..Default::default()
+                    },
+                ]),
+                hosts_error: None,
+                inventories: vec![
+                    InventorySnapshot {
+                        host_id: host_id.clone(),
+                        inventory: Some(HostInventory {
+                            actors: vec![actor_description()],
+                            host_id,
+                            ..Default::default()
+                        }),
+                        error: None,
+                    },
+                    InventorySnapshot {
+                        host_id: stale_host_id,
+                        inventory: None,
+                        error: Some("timed out after 2s".to_string()),
+                    },
+                ],
+                links: Some(LinkDefinitionList {
+                    links: vec![link_definition()],
+                }),
+                links_error: None,
+                claims: None,
+                claims_error: Some("no responders on wasmbus.ctl.default.get.claims".to_string()),
+            },
+        );
+    }
+
+    #[test]
+    fn progress_event() {
+        // Has no default, so the minimal fixture is the failure without the optional host
+        assert_wire_format(
+            "progress_event",
+            Variant::Minimal,
+            &ProgressEvent::PhaseFailed {
+                phase: "auction".to_string(),
+                host: None,
+                error: "no bids".to_string(),
+            },
+        );
+        assert_wire_format(
+            "progress_event",
+            Variant::Full,
+            &ProgressEvent::PhaseFailed {
+                phase: "scale_actor".to_string(),
+                host: Some("NCPGH5CVAP4XGOBOCXNUMXAXSFXL2DFZG2KXDGD2RLNJBMVGGVD4VQ5P".to_string()),
+                error: "not enough capacity".to_string(),
+            },
+        );
+    }
+
+    #[test]
+    fn host_types() {
+        assert_both(
+            "host",
+            Host {
+                cluster_issuers: Some(
+                    "CDKF6OKPOBQKAX57UOXO7SCHURTOZWKWIVPC2HFJTGFXY5VJX44ECEHH".to_string(),
+                ),
+                ctl_host: Some("127.0.0.1:4222".to_string()),
+                friendly_name: "silent-bush-1234".to_string(),
+                id: "NCPGH5CVAP4XGOBOCXNUMXAXSFXL2DFZG2KXDGD2RLNJBMVGGVD4VQ5P".to_string(),
+                js_domain: Some("core".to_string()),
+                labels: Some(map("hostcore.os", "linux")),
+                lattice_prefix: Some("default".to_string()),
+                rpc_host: Some("127.0.0.1:4222".to_string()),
+                uptime_human: Some("1 hour".to_string()),
+                uptime_seconds: 3600,
+                version: Some("0.78.0".to_string()),
+            },
+        );
+        assert_both(
+            "host_inventory",
+            HostInventory {
+                actors: vec![actor_description()],
+                host_id: "NCPGH5CVAP4XGOBOCXNUMXAXSFXL2DFZG2KXDGD2RLNJBMVGGVD4VQ5P".to_string(),
+                issuer: "CDKF6OKPOBQKAX57UOXO7SCHURTOZWKWIVPC2HFJTGFXY5VJX44ECEHH".to_string(),
+                friendly_name: "silent-bush-1234".to_string(),
+                labels: map("hostcore.os", "linux"),
+                providers: vec![provider_description()],
+            },
+        );
+    }
+
+    #[test]
+    fn link_and_provider_types() {
+        assert_both("link_definition", link_definition());
+        assert_both(
+            "named_config",
+            NamedConfig {
+                name: "http-defaults".to_string(),
+                values: map("port", "8080"),
+            },
+        );
+        assert_both(
+            "provider_auction_ack",
+            ProviderAuctionAck {
+                host_id: "NCPGH5CVAP4XGOBOCXNUMXAXSFXL2DFZG2KXDGD2RLNJBMVGGVD4VQ5P".to_string(),
+                link_name: "default".to_string(),
+                provider_ref: "wasmcloud.azurecr.io/httpserver:0.17.0".to_string(),
+                constraints: map("hostcore.os", "linux"),
+            },
+        );
+        assert_both(
+            "provider_auction_request",
+            ProviderAuctionRequest {
+                constraints: map("hostcore.os", "linux"),
+                link_name: "default".to_string(),
+                provider_ref: "wasmcloud.azurecr.io/httpserver:0.17.0".to_string(),
+            },
+        );
+        assert_both("provider_description", provider_description());
+        assert_both(
+            "registry_credential",
+            RegistryCredential {
+                password: Some("hunter2".to_string()),
+                token: Some("token".to_string()),
+                username: Some("admin".to_string()),
+                registry_type: "oci".to_string(),
+            },
+        );
+        assert_both(
+            "remove_link_definition_request",
+            RemoveLinkDefinitionRequest {
+                actor_id: "MBCFOPM6JW2APJLXJD3Z5O4CN7CPYJ2B4FTKLJUR5YR5MITIU7HD3WD5".to_string(),
+                contract_id: "wasmcloud:httpserver".to_string(),
+                link_name: "default".to_string(),
+            },
+        );
+    }
+
+    #[test]
+    fn command_types() {
+        let host_id = "NCPGH5CVAP4XGOBOCXNUMXAXSFXL2DFZG2KXDGD2RLNJBMVGGVD4VQ5P".to_string();
+        assert_both("scale_actor_command", scale_actor_command());
+        assert_both(
+            "start_actor_command",
+            StartActorCommand {
+                actor_ref: "wasmcloud.azurecr.io/echo:0.3.8".to_string(),
+                annotations: Some(map(annotations::APP_SPEC, "echo")),
+                command_id: Some(command_id()),
+                count: 3,
+                host_id: host_id.clone(),
+            },
+        );
+        assert_both(
+            "start_provider_command",
+            StartProviderCommand {
+                annotations: Some(map(annotations::APP_SPEC, "echo")),
+                command_id: Some(command_id()),
+                configuration: Some("eyJwb3J0Ijo4MDgwfQ==".to_string()),
+                host_id: host_id.clone(),
+                link_name: "default".to_string(),
+                provider_ref: "wasmcloud.azurecr.io/httpserver:0.17.0".to_string(),
+            },
+        );
+        assert_both(
+            "stop_actor_command",
+            StopActorCommand {
+                actor_ref: "wasmcloud.azurecr.io/echo:0.3.8".to_string(),
+                annotations: Some(map(annotations::APP_SPEC, "echo")),
+                command_id: Some(command_id()),
+                host_id: host_id.clone(),
+            },
+        );
+        assert_both(
+            "host_label",
+            HostLabel {
+                key: "zone".to_string(),
+                value: "us-east-1".to_string(),
+            },
+        );
+        assert_both(
+            "stop_host_command",
+            StopHostCommand {
+                host_id: host_id.clone(),
+                timeout: Some(30),
+            },
+        );
+        assert_both(
+            "stop_provider_command",
+            StopProviderCommand {
+                annotations: Some(map(annotations::APP_SPEC, "echo")),
+                contract_id: "wasmcloud:httpserver".to_string(),
+                host_id: host_id.clone(),
+                link_name: "default".to_string(),
+                provider_ref: "VAG3QITQQ2ODAOWB5TTQSDJ53XK3SHBEIFNK4AYJ5RKAX2UNSCAPHA5M"
+                    .to_string(),
+            },
+        );
+        assert_both(
+            "update_actor_command",
+            UpdateActorCommand {
+                actor_id: "MBCFOPM6JW2APJLXJD3Z5O4CN7CPYJ2B4FTKLJUR5YR5MITIU7HD3WD5".to_string(),
+                annotations: Some(map(annotations::APP_SPEC, "echo")),
+                host_id,
+                new_actor_ref: "wasmcloud.azurecr.io/echo:0.3.9".to_string(),
+            },
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! An overall time budget for composite operations that issue several requests in sequence
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Tracks the time remaining for a composite operation. Each sub-operation gets the smaller of
+/// its own configured timeout and whatever is left of the budget, so the whole operation never
+/// runs (much) longer than the budget
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Deadline {
+    expires_at: Option<Instant>,
+}
+
+impl Deadline {
+    /// A deadline that expires once `budget` has elapsed from now
+    pub fn after(budget: Duration) -> Deadline {
+        Deadline {
+            expires_at: Some(Instant::now() + budget),
+        }
+    }
+
+    /// A deadline that never expires
+    pub fn unbounded() -> Deadline {
+        Deadline { expires_at: None }
+    }
+
+    /// The timeout to use for the next sub-operation, or `None` if the budget has been used up and
+    /// no further sub-operations should be started
+    pub fn timeout(&self, configured: Duration) -> Option<Duration> {
+        match self.expires_at {
+            None => Some(configured),
+            Some(expires_at) => {
+                let remaining = expires_at.saturating_duration_since(Instant::now());
+                (!remaining.is_zero()).then(|| remaining.min(configured))
+            }
+        }
+    }
+
+    /// Whether the budget has been used up. Always false for an unbounded deadline
+    pub fn expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_is_capped_by_remaining_budget() {
+        let deadline = Deadline::after(Duration::from_secs(5));
+        assert_eq!(
+            deadline.timeout(Duration::from_secs(2)),
+            Some(Duration::from_secs(2))
+        );
+
+        tokio::time::advance(Duration::from_secs(4)).await;
+        assert_eq!(
+            deadline.timeout(Duration::from_secs(2)),
+            Some(Duration::from_secs(1))
+        );
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(deadline.timeout(Duration::from_secs(2)), None);
+        assert!(deadline.expired());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unbounded_deadline_uses_configured_timeout() {
+        let deadline = Deadline::unbounded();
+        tokio::time::advance(Duration::from_secs(3600)).await;
+        assert_eq!(
+            deadline.timeout(Duration::from_secs(2)),
+            Some(Duration::from_secs(2))
+        );
+        assert!(!deadline.expired());
+    }
+}
@@ -0,0 +1,326 @@
+//! The error type returned by every fallible client operation
+
+use std::time::Duration;
+
+/// The result type returned by client operations
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// The result type client operations returned before [`Error`] was introduced. Any [`Error`] can
+/// be converted into the boxed error with `?`
+#[deprecated(
+    since = "0.32.0",
+    note = "client operations now return `Result`, which uses the `Error` enum"
+)]
+pub type BoxedResult<T> = ::std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Everything that can go wrong when talking to a lattice
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// No response was received on `subject` within `duration`
+    Timeout { subject: String, duration: Duration },
+    /// Nothing was listening on `subject`, e.g. the target host doesn't exist
+    NoResponders { subject: String },
+    /// The NATS connection failed to publish, subscribe, or make a request
+    Nats(async_nats::Error),
+    /// A payload couldn't be serialized, or a response couldn't be deserialized
+    Serialization(serde_json::Error),
+    /// Like [`Error::Serialization`], for a payload encoded as MessagePack
+    MsgPack(Box<dyn std::error::Error + Send + Sync>),
+    /// Reading from or writing to the lattice metadata bucket failed
+    KvStore(async_nats::Error),
+    /// A host or the lattice declined the request. On a client built with
+    /// [`ClientBuilder::error_on_nack`](crate::ClientBuilder::error_on_nack), this describes the
+    /// refused command too
+    NotAccepted(AckError),
+    /// A host accepted a command but then reported that carrying it out failed
+    CommandFailed { host_id: String, reason: String },
+    /// Claims were rejected before being written to the lattice metadata bucket
+    InvalidClaims { reason: String },
+    /// An identifier passed to the client, such as a label key, was rejected before anything was
+    /// published
+    InvalidArgument {
+        argument: &'static str,
+        reason: String,
+    },
+    /// Fewer hosts bid in an auction than the operation needed
+    NotEnoughBids {
+        reference: String,
+        required: usize,
+        received: usize,
+    },
+    /// A reference matched more than one actor, so it can't be told which was meant. The public
+    /// keys of the actors it matched are listed in `candidates`
+    AmbiguousReference {
+        reference: String,
+        candidates: Vec<String>,
+    },
+    /// An operation needed a JetStream stream that doesn't exist
+    StreamNotFound { stream: String },
+    /// A mutating operation was attempted on a read-only client
+    ReadOnlyViolation(ReadOnlyViolation),
+    /// An operation needed the lattice metadata bucket, but it doesn't exist
+    RequiresKv(RequiresKv),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Timeout { subject, duration } => {
+                write!(
+                    f,
+                    "timed out after {duration:?} waiting for a response on {subject}"
+                )
+            }
+            Error::NoResponders { subject } => write!(f, "no responders on {subject}"),
+            Error::Nats(e) => write!(f, "NATS error: {e}"),
+            Error::Serialization(e) => write!(f, "JSON serialization failure: {e}"),
+            Error::MsgPack(e) => write!(f, "MessagePack serialization failure: {e}"),
+            Error::KvStore(e) => write!(f, "lattice metadata bucket error: {e}"),
+            Error::NotAccepted(e) => e.fmt(f),
+            Error::CommandFailed { host_id, reason } => {
+                write!(
+                    f,
+                    "host {host_id} failed to carry out the command: {reason}"
+                )
+            }
+            Error::InvalidClaims { reason } => write!(f, "invalid claims: {reason}"),
+            Error::InvalidArgument { argument, reason } => {
+                write!(f, "invalid {argument}: {reason}")
+            }
+            Error::NotEnoughBids {
+                reference,
+                required,
+                received,
+            } => write!(
+                f,
+                "only {received} of {required} required hosts bid for {reference}"
+            ),
+            Error::AmbiguousReference {
+                reference,
+                candidates,
+            } => write!(
+                f,
+                "{reference} could refer to any of the actors {}",
+                candidates.join(", ")
+            ),
+            Error::StreamNotFound { stream } => {
+                write!(f, "JetStream stream {stream} does not exist")
+            }
+            Error::ReadOnlyViolation(e) => e.fmt(f),
+            Error::RequiresKv(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Nats(e) | Error::KvStore(e) | Error::MsgPack(e) => Some(e.as_ref()),
+            Error::Serialization(e) => Some(e),
+            Error::ReadOnlyViolation(e) => Some(e),
+            Error::RequiresKv(e) => Some(e),
+            Error::NotAccepted(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Serialization(e)
+    }
+}
+
+impl From<ReadOnlyViolation> for Error {
+    fn from(e: ReadOnlyViolation) -> Error {
+        Error::ReadOnlyViolation(e)
+    }
+}
+
+impl From<AckError> for Error {
+    fn from(e: AckError) -> Error {
+        Error::NotAccepted(e)
+    }
+}
+
+impl From<RequiresKv> for Error {
+    fn from(e: RequiresKv) -> Error {
+        Error::RequiresKv(e)
+    }
+}
+
+impl Error {
+    /// Wraps any error raised by the NATS client
+    pub(crate) fn nats(e: impl Into<async_nats::Error>) -> Error {
+        Error::Nats(e.into())
+    }
+
+    /// Wraps any error raised while using the lattice metadata bucket
+    pub(crate) fn kv(e: impl Into<async_nats::Error>) -> Error {
+        Error::KvStore(e.into())
+    }
+
+    /// Maps a failed NATS request on `subject` that was allowed to take `timeout`
+    pub(crate) fn request(
+        subject: String,
+        timeout: Duration,
+        e: async_nats::RequestError,
+    ) -> Error {
+        match e.kind() {
+            async_nats::RequestErrorKind::TimedOut => Error::Timeout {
+                subject,
+                duration: timeout,
+            },
+            async_nats::RequestErrorKind::NoResponders => Error::NoResponders { subject },
+            _ => Error::nats(e),
+        }
+    }
+}
+
+/// The error returned when a mutating operation is attempted on a client built with
+/// [`ClientBuilder::read_only`](crate::ClientBuilder::read_only)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReadOnlyViolation {
+    /// The name of the client method that was refused
+    pub operation: &'static str,
+}
+
+impl std::fmt::Display for ReadOnlyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is not permitted because the client is read-only",
+            self.operation
+        )
+    }
+}
+
+impl std::error::Error for ReadOnlyViolation {}
+
+/// The error returned when an operation needs the lattice metadata bucket but it doesn't exist,
+/// i.e. the client is operating in legacy topic-only mode
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RequiresKv {
+    /// The name of the client method that was refused
+    pub operation: &'static str,
+    /// The name of the bucket that was looked for
+    pub bucket: String,
+    /// The JetStream domain the bucket was looked for in, or `None` for the connection's default
+    pub js_domain: Option<String>,
+}
+
+impl std::fmt::Display for RequiresKv {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} requires the lattice metadata bucket {}, which was not found in ",
+            self.operation, self.bucket
+        )?;
+        match &self.js_domain {
+            Some(domain) => write!(f, "JetStream domain {domain}"),
+            None => f.write_str("the default JetStream domain"),
+        }
+    }
+}
+
+impl std::error::Error for RequiresKv {}
+
+/// A negative acknowledgement, from [`CtlOperationAck::into_result`](crate::CtlOperationAck::into_result)
+/// or from a command sent by a client built with
+/// [`ClientBuilder::error_on_nack`](crate::ClientBuilder::error_on_nack)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AckError {
+    /// The reason the host gave for refusing the command
+    pub error: String,
+    /// The name of the client method that sent the command, if known
+    pub operation: Option<&'static str>,
+    /// The host that refused the command. `None` for commands addressed to the whole lattice, or
+    /// if it isn't known
+    pub host_id: Option<String>,
+    /// The command that was refused, as JSON whatever codec it was sent with. Never set for
+    /// commands carrying registry credentials, and link definitions are included without their
+    /// values
+    pub command: Option<serde_json::Value>,
+}
+
+impl std::fmt::Display for AckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.operation, &self.host_id) {
+            (Some(operation), Some(host_id)) => {
+                write!(f, "{operation} was rejected by host {host_id}")?
+            }
+            (Some(operation), None) => write!(f, "{operation} was rejected")?,
+            (None, _) => f.write_str("request was not accepted")?,
+        }
+        write!(f, ": {}", self.error)
+    }
+}
+
+impl std::error::Error for AckError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{json_deserialize, Host};
+
+    #[test]
+    fn bad_json_maps_to_serialization() {
+        let err = json_deserialize::<Host>(b"{\"id\": 42").unwrap_err();
+        assert!(matches!(err, Error::Serialization(_)));
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn rejections_describe_the_command() {
+        let rejected = AckError {
+            error: "actor not running".to_string(),
+            operation: Some("stop_actor"),
+            host_id: Some("NHOST".to_string()),
+            command: None,
+        };
+        assert_eq!(
+            Error::from(rejected.clone()).to_string(),
+            "stop_actor was rejected by host NHOST: actor not running"
+        );
+        let lattice_wide = AckError {
+            host_id: None,
+            operation: Some("remove_link"),
+            ..rejected
+        };
+        assert_eq!(
+            lattice_wide.to_string(),
+            "remove_link was rejected: actor not running"
+        );
+    }
+
+    #[test]
+    fn ambiguous_references_list_the_candidates() {
+        let err = Error::AmbiguousReference {
+            reference: "echo".to_string(),
+            candidates: vec!["MECHO".to_string(), "MOTHER".to_string()],
+        };
+        assert_eq!(
+            err.to_string(),
+            "echo could refer to any of the actors MECHO, MOTHER"
+        );
+    }
+
+    #[test]
+    fn errors_convert_into_boxed_errors() {
+        fn boxed() -> ::std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            Err(Error::from(ReadOnlyViolation {
+                operation: "scale_actor",
+            }))?;
+            Ok(())
+        }
+        let err = boxed().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ReadOnlyViolation(ReadOnlyViolation {
+                operation: "scale_actor"
+            }))
+        ));
+    }
+}
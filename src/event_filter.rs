@@ -0,0 +1,94 @@
+//! Filtering of the lattice control event stream, applied before events are buffered
+
+use cloudevents::{AttributesReader, Event};
+
+/// Selects which events [`crate::Client::events_receiver_filtered`] delivers. An event must match
+/// at least one of the event types and at least one of the hosts; an empty list of either matches
+/// everything, so [`EventFilter::new`] on its own lets every event through
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EventFilter {
+    event_types: Vec<String>,
+    hosts: Vec<String>,
+}
+
+impl EventFilter {
+    /// Creates a filter that matches every event
+    pub fn new() -> EventFilter {
+        EventFilter::default()
+    }
+
+    /// Matches events with the given CloudEvent type. A pattern ending in `*` matches every type
+    /// starting with the rest of the pattern, e.g. `com.wasmcloud.lattice.actor_*`
+    pub fn event_type(mut self, pattern: impl Into<String>) -> EventFilter {
+        self.event_types.push(pattern.into());
+        self
+    }
+
+    /// Matches events published by the host with the given ID, i.e. whose CloudEvent source is
+    /// the host ID
+    pub fn host(mut self, host_id: impl Into<String>) -> EventFilter {
+        self.hosts.push(host_id.into());
+        self
+    }
+
+    /// Returns whether the event passes the filter
+    pub fn matches(&self, event: &Event) -> bool {
+        let ty = event.ty();
+        let type_matches = self.event_types.is_empty()
+            || self
+                .event_types
+                .iter()
+                .any(|pattern| match pattern.strip_suffix('*') {
+                    Some(prefix) => ty.starts_with(prefix),
+                    None => ty == pattern,
+                });
+        let source = event.source().as_str();
+        type_matches && (self.hosts.is_empty() || self.hosts.iter().any(|host| host == source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cloudevents::{EventBuilder as _, EventBuilderV10};
+
+    use super::*;
+
+    fn event(ty: &str, source: &str) -> Event {
+        EventBuilderV10::new()
+            .id("1")
+            .ty(ty)
+            .source(source)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = EventFilter::new();
+        assert!(filter.matches(&event("com.wasmcloud.lattice.host_heartbeat", "N1")));
+        assert!(filter.matches(&event("anything", "N2")));
+    }
+
+    #[test]
+    fn event_types_match_exactly_or_by_prefix() {
+        let filter = EventFilter::new()
+            .event_type("com.wasmcloud.lattice.actor_*")
+            .event_type("com.wasmcloud.lattice.linkdef_set");
+        assert!(filter.matches(&event("com.wasmcloud.lattice.actor_started", "N1")));
+        assert!(filter.matches(&event("com.wasmcloud.lattice.actor_stopped", "N1")));
+        assert!(filter.matches(&event("com.wasmcloud.lattice.linkdef_set", "N1")));
+        assert!(!filter.matches(&event("com.wasmcloud.lattice.linkdef_set_v2", "N1")));
+        assert!(!filter.matches(&event("com.wasmcloud.lattice.host_heartbeat", "N1")));
+    }
+
+    #[test]
+    fn hosts_and_types_must_both_match() {
+        let filter = EventFilter::new()
+            .event_type("com.wasmcloud.lattice.actor_*")
+            .host("N1")
+            .host("N2");
+        assert!(filter.matches(&event("com.wasmcloud.lattice.actor_started", "N2")));
+        assert!(!filter.matches(&event("com.wasmcloud.lattice.actor_started", "N3")));
+        assert!(!filter.matches(&event("com.wasmcloud.lattice.host_started", "N1")));
+    }
+}
@@ -0,0 +1,253 @@
+//! A bounded buffer between the lattice event subscription and its consumer, with a choice of what
+//! happens when the consumer falls behind
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use cloudevents::event::Event;
+use tokio::sync::Notify;
+
+/// What [`Client::events_receiver_with_policy`](crate::Client::events_receiver_with_policy) does
+/// with an event that arrives while the buffer is full
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    /// Wait for the consumer to make room, as [`crate::Client::events_receiver`] does. Nothing is
+    /// dropped, but while waiting the subscription falls behind, and a consumer that stalls for
+    /// long enough can get the NATS connection disconnected as a slow consumer
+    #[default]
+    Block,
+    /// Make room by discarding the oldest buffered event, so that the consumer always sees the
+    /// most recent events
+    DropOldest,
+    /// Discard the event that just arrived, so that the consumer sees events in an unbroken run
+    /// up to the point it fell behind
+    DropNewest,
+}
+
+struct Shared {
+    queue: Mutex<VecDeque<Event>>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    dropped: AtomicU64,
+    /// Set when the receiver is closed or dropped
+    closed: AtomicBool,
+    /// Set when the subscription has ended
+    finished: AtomicBool,
+    pushed: Notify,
+    popped: Notify,
+}
+
+impl Shared {
+    fn queue(&self) -> std::sync::MutexGuard<'_, VecDeque<Event>> {
+        self.queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Creates a buffer holding up to `capacity` events, which must not be zero
+pub(crate) fn channel(capacity: usize, policy: OverflowPolicy) -> (EventSender, EventReceiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(VecDeque::with_capacity(capacity)),
+        capacity,
+        policy,
+        dropped: AtomicU64::new(0),
+        closed: AtomicBool::new(false),
+        finished: AtomicBool::new(false),
+        pushed: Notify::new(),
+        popped: Notify::new(),
+    });
+    (
+        EventSender {
+            shared: shared.clone(),
+        },
+        EventReceiver { shared },
+    )
+}
+
+/// The subscription's end of the buffer
+pub(crate) struct EventSender {
+    shared: Arc<Shared>,
+}
+
+impl EventSender {
+    /// Buffers an event, applying the overflow policy if the buffer is full. Returns false once
+    /// the receiver has been closed or dropped, after which the subscription should end
+    pub async fn send(&self, event: Event) -> bool {
+        let shared = &self.shared;
+        loop {
+            if shared.closed.load(Ordering::Acquire) {
+                return false;
+            }
+            {
+                let mut queue = shared.queue();
+                if queue.len() < shared.capacity {
+                    queue.push_back(event);
+                    drop(queue);
+                    shared.pushed.notify_one();
+                    return true;
+                }
+                match shared.policy {
+                    OverflowPolicy::Block => {}
+                    OverflowPolicy::DropOldest => {
+                        queue.pop_front();
+                        queue.push_back(event);
+                        shared.dropped.fetch_add(1, Ordering::Relaxed);
+                        return true;
+                    }
+                    OverflowPolicy::DropNewest => {
+                        shared.dropped.fetch_add(1, Ordering::Relaxed);
+                        return true;
+                    }
+                }
+            }
+            // A pop between releasing the lock and waiting leaves a permit, so it isn't missed
+            shared.popped.notified().await;
+        }
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        self.shared.finished.store(true, Ordering::Release);
+        self.shared.pushed.notify_one();
+    }
+}
+
+/// Receives lattice events from
+/// [`Client::events_receiver_with_policy`](crate::Client::events_receiver_with_policy), keeping
+/// count of the events its [`OverflowPolicy`] dropped. Dropping the receiver unsubscribes, as
+/// [`EventReceiver::close`] does
+pub struct EventReceiver {
+    shared: Arc<Shared>,
+}
+
+impl EventReceiver {
+    /// Receives the next event, waiting for one to arrive. Returns `None` once the receiver has
+    /// been closed and every buffered event received, or if the subscription ended
+    pub async fn recv(&mut self) -> Option<Event> {
+        let shared = &self.shared;
+        loop {
+            if let Some(event) = shared.queue().pop_front() {
+                shared.popped.notify_one();
+                return Some(event);
+            }
+            if shared.closed.load(Ordering::Acquire) || shared.finished.load(Ordering::Acquire) {
+                return None;
+            }
+            // A push between the check and waiting leaves a permit, so it isn't missed
+            shared.pushed.notified().await;
+        }
+    }
+
+    /// The number of events dropped so far because the buffer was full. Always zero with
+    /// [`OverflowPolicy::Block`]
+    pub fn dropped_count(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Stops receiving new events. Events that were already buffered can still be received, after
+    /// which [`EventReceiver::recv`] returns `None`. The subscription itself ends when the next
+    /// event arrives
+    pub fn close(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        // Wake the subscription if it is waiting for room
+        self.shared.popped.notify_one();
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl std::fmt::Debug for EventReceiver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventReceiver")
+            .field("capacity", &self.shared.capacity)
+            .field("policy", &self.shared.policy)
+            .field("buffered", &self.shared.queue().len())
+            .field("dropped", &self.dropped_count())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use cloudevents::{AttributesReader, EventBuilder, EventBuilderV10};
+
+    use super::*;
+
+    fn event(id: usize) -> Event {
+        EventBuilderV10::new()
+            .id(id.to_string())
+            .source("NHOST")
+            .ty("com.wasmcloud.lattice.host_heartbeat")
+            .build()
+            .unwrap()
+    }
+
+    /// Sends events 0 to 4 into a buffer of 2 without consuming any, then reads what was kept
+    async fn overflow(policy: OverflowPolicy) -> (Vec<String>, u64) {
+        let (sender, mut receiver) = channel(2, policy);
+        for id in 0..5 {
+            assert!(sender.send(event(id)).await);
+        }
+        drop(sender);
+        let mut ids = Vec::new();
+        while let Some(event) = receiver.recv().await {
+            ids.push(event.id().to_string());
+        }
+        (ids, receiver.dropped_count())
+    }
+
+    #[tokio::test]
+    async fn drop_policies_count_what_they_discard() {
+        assert_eq!(
+            overflow(OverflowPolicy::DropOldest).await,
+            (vec!["3".to_string(), "4".to_string()], 3)
+        );
+        assert_eq!(
+            overflow(OverflowPolicy::DropNewest).await,
+            (vec!["0".to_string(), "1".to_string()], 3)
+        );
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_a_slow_consumer() {
+        let (sender, mut receiver) = channel(2, OverflowPolicy::Block);
+        let producer = tokio::spawn(async move {
+            for id in 0..5 {
+                assert!(sender.send(event(id)).await);
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!producer.is_finished(), "the producer should wait for room");
+
+        let mut ids = Vec::new();
+        while let Some(event) = receiver.recv().await {
+            ids.push(event.id().to_string());
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert_eq!(ids, ["0", "1", "2", "3", "4"]);
+        assert_eq!(receiver.dropped_count(), 0);
+        producer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn closing_releases_a_blocked_sender() {
+        let (sender, mut receiver) = channel(1, OverflowPolicy::Block);
+        assert!(sender.send(event(0)).await);
+        let blocked = tokio::spawn(async move { sender.send(event(1)).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        receiver.close();
+        assert!(!blocked.await.unwrap());
+        assert_eq!(receiver.recv().await.unwrap().id(), "0");
+        assert!(receiver.recv().await.is_none());
+    }
+}
@@ -0,0 +1,238 @@
+//! A minimal stand-in for a wasmCloud host that acknowledges control interface commands, used to
+//! exercise client behavior that depends on how a host responds. Tests using it need a NATS server
+//! listening on 127.0.0.1:4222 and are therefore marked as ignored
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::StreamExt;
+use tokio::task::JoinHandle;
+
+use crate::codec::{Codec, CONTENT_TYPE_HEADER};
+use crate::{
+    broker, ActorAuctionAck, ActorAuctionRequest, ActorDescription, BatchCommand, CommandBatch,
+    CommandBatchResponse, CtlOperationAck, GetClaimsResponse, Host, HostInventory,
+};
+
+/// Controls how a [`FakeHost`] responds
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FakeHostConfig {
+    /// Whether the host listens on the batch subject
+    pub supports_batch: bool,
+    /// Command operations (the last subject token, e.g. `scale` or `sp`) that will be rejected
+    pub reject: Vec<&'static str>,
+    /// How long to wait before acknowledging each command
+    pub delay: Duration,
+    /// Operations that take longer than `delay` to acknowledge, and by how much
+    pub op_delays: Vec<(&'static str, Duration)>,
+    /// Whether messages are handled concurrently, as a real host does, rather than one at a time
+    /// in order of arrival
+    pub concurrent: bool,
+    /// Operations that are recorded but never answered, e.g. `inv` for a host that has stopped
+    /// responding to inventory queries
+    pub ignore: Vec<&'static str>,
+    /// The actors listed in the host's inventory
+    pub actors: Vec<ActorDescription>,
+    /// The claims the host answers a claims query with
+    pub claims: Vec<HashMap<String, String>>,
+}
+
+pub(crate) struct FakeHost {
+    received: Arc<Mutex<Vec<String>>>,
+    handle: JoinHandle<()>,
+}
+
+impl FakeHost {
+    /// Starts answering commands addressed to `host_id` in the given lattice
+    pub async fn start(
+        nc: async_nats::Client,
+        lattice_prefix: &str,
+        host_id: &str,
+        config: FakeHostConfig,
+    ) -> FakeHost {
+        let topic_prefix = None;
+        let mut subjects = if config.supports_batch {
+            vec![format!("wasmbus.ctl.{lattice_prefix}.cmd.{host_id}.>")]
+        } else {
+            vec![
+                broker::commands::scale_actor(&topic_prefix, lattice_prefix, host_id),
+                broker::commands::stop_actor(&topic_prefix, lattice_prefix, host_id),
+                broker::commands::update_actor(&topic_prefix, lattice_prefix, host_id),
+                broker::commands::start_provider(&topic_prefix, lattice_prefix, host_id),
+                broker::commands::stop_provider(&topic_prefix, lattice_prefix, host_id),
+                broker::commands::stop_host(&topic_prefix, lattice_prefix, host_id),
+            ]
+        };
+        subjects.push(broker::actor_auction_subject(&topic_prefix, lattice_prefix));
+        subjects.push(broker::queries::hosts(&topic_prefix, lattice_prefix));
+        subjects.push(broker::queries::claims(&topic_prefix, lattice_prefix));
+        subjects.push(broker::queries::host_inventory(
+            &topic_prefix,
+            lattice_prefix,
+            host_id,
+        ));
+        let mut subs = Vec::with_capacity(subjects.len());
+        for subject in subjects {
+            subs.push(
+                nc.subscribe(subject)
+                    .await
+                    .expect("fake host should subscribe"),
+            );
+        }
+        nc.flush()
+            .await
+            .expect("fake host should flush subscriptions");
+
+        let responder = Responder {
+            nc,
+            host_id: host_id.to_string(),
+            config: Arc::new(config),
+            received: Arc::new(Mutex::new(Vec::new())),
+        };
+        let received = responder.received.clone();
+        let handle = tokio::spawn(async move {
+            let mut messages = futures::stream::select_all(subs);
+            while let Some(msg) = messages.next().await {
+                if responder.config.concurrent {
+                    let responder = responder.clone();
+                    tokio::spawn(async move { responder.answer(msg).await });
+                } else {
+                    responder.answer(msg).await;
+                }
+            }
+        });
+        FakeHost { received, handle }
+    }
+
+    /// The operations handled so far, in the order they were handled. A batch is recorded as `batch`
+    /// followed by the operations it contained, a bid in an auction as `auction`, a reply to a
+    /// hosts query as `ping`, an inventory query as `inv`, and a claims query as `claims`
+    pub fn received(&self) -> Vec<String> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+impl Drop for FakeHost {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Answers the messages a [`FakeHost`] receives
+#[derive(Clone)]
+struct Responder {
+    nc: async_nats::Client,
+    host_id: String,
+    config: Arc<FakeHostConfig>,
+    received: Arc<Mutex<Vec<String>>>,
+}
+
+impl Responder {
+    async fn answer(&self, msg: async_nats::Message) {
+        let config = self.config.as_ref();
+        let Some(reply) = msg.reply.clone() else {
+            return;
+        };
+        let subject = msg.subject.to_string();
+        let op = subject.rsplit('.').next().unwrap_or_default().to_string();
+        let extra = config
+            .op_delays
+            .iter()
+            .find(|(slow, _)| *slow == op)
+            .map(|(_, delay)| *delay)
+            .unwrap_or_default();
+        tokio::time::sleep(config.delay + extra).await;
+        // Answer in whatever encoding the request was sent in
+        let codec = Codec::of_message(msg.headers.as_ref());
+        if config.ignore.contains(&op.as_str()) {
+            self.received.lock().unwrap().push(op);
+            return;
+        }
+        let payload = if subject.ends_with(".ping.hosts") {
+            self.received.lock().unwrap().push("ping".to_string());
+            codec
+                .serialize(&Host {
+                    id: self.host_id.clone(),
+                    ..Default::default()
+                })
+                .unwrap()
+        } else if op == "inv" {
+            self.received.lock().unwrap().push(op);
+            codec
+                .serialize(&HostInventory {
+                    host_id: self.host_id.clone(),
+                    actors: config.actors.clone(),
+                    ..Default::default()
+                })
+                .unwrap()
+        } else if subject.ends_with(".get.claims") {
+            self.received.lock().unwrap().push("claims".to_string());
+            codec
+                .serialize(&GetClaimsResponse {
+                    claims: config.claims.clone(),
+                })
+                .unwrap()
+        } else if subject.contains(".auction.") {
+            // Every fake host bids in every actor auction
+            self.received.lock().unwrap().push("auction".to_string());
+            let request: ActorAuctionRequest = codec.deserialize(&msg.payload).unwrap();
+            codec
+                .serialize(&ActorAuctionAck {
+                    actor_ref: request.actor_ref,
+                    host_id: self.host_id.clone(),
+                    constraints: request.constraints,
+                })
+                .unwrap()
+        } else if op == "batch" {
+            self.received.lock().unwrap().push(op);
+            let batch: CommandBatch = codec.deserialize(&msg.payload).unwrap();
+            let mut acks = Vec::new();
+            for command in batch.commands {
+                let op = batch_op(&command);
+                self.received.lock().unwrap().push(op.to_string());
+                let ack = ack_for(config, op);
+                let accepted = ack.accepted;
+                acks.push(ack);
+                if !accepted && batch.stop_on_failure {
+                    break;
+                }
+            }
+            codec.serialize(&CommandBatchResponse { acks }).unwrap()
+        } else {
+            let ack = ack_for(config, &op);
+            self.received.lock().unwrap().push(op);
+            codec.serialize(&ack).unwrap()
+        };
+        let mut headers = async_nats::header::HeaderMap::new();
+        headers.insert(CONTENT_TYPE_HEADER, codec.content_type());
+        let _ = self
+            .nc
+            .publish_with_headers(reply, headers, payload.into())
+            .await;
+    }
+}
+
+fn batch_op(command: &BatchCommand) -> &'static str {
+    match command {
+        BatchCommand::ScaleActor(_) => "scale",
+        BatchCommand::StopActor(_) => "sa",
+        BatchCommand::UpdateActor(_) => "upd",
+        BatchCommand::StartProvider(_) => "lp",
+        BatchCommand::StopProvider(_) => "sp",
+    }
+}
+
+fn ack_for(config: &FakeHostConfig, op: &str) -> CtlOperationAck {
+    if config.reject.contains(&op) {
+        CtlOperationAck {
+            accepted: false,
+            error: format!("{op} rejected by fake host"),
+        }
+    } else {
+        CtlOperationAck {
+            accepted: true,
+            error: String::new(),
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Optional per-host serialization of control commands. NATS makes no ordering guarantee across
+//! separate requests, so a `stop_actor` followed immediately by a `start_actor` on the same host
+//! can be processed in the opposite order. When enabled, commands to the same host are sent one at
+//! a time, each waiting for its acknowledgement, while commands to different hosts still run
+//! concurrently
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::OwnedMutexGuard;
+use tracing::trace;
+
+use crate::interceptor::Interceptor;
+
+type Queues = Arc<Mutex<HashMap<String, Arc<HostQueue>>>>;
+
+/// The queue of every host with commands waiting or in flight. A host's queue is removed once its
+/// last command finishes, so hosts that come and go don't accumulate
+#[derive(Default)]
+pub(crate) struct HostQueues {
+    queues: Queues,
+    /// Told about every change in queue depth
+    interceptor: Interceptor,
+}
+
+#[derive(Debug, Default)]
+struct HostQueue {
+    /// Tokio's mutex is fair, so waiters acquire it in the order they started waiting
+    lock: Arc<tokio::sync::Mutex<()>>,
+    /// Commands waiting for or holding the lock. Only changed while the queues are locked, so a
+    /// queue is never removed while a command is joining it
+    depth: AtomicUsize,
+}
+
+/// Held while a command is being sent to a host. Dropping it lets the next queued command proceed
+pub(crate) struct HostQueueGuard {
+    _lock: OwnedMutexGuard<()>,
+    _depth: DepthGuard,
+}
+
+/// Keeps the queue depth accurate even if a command is cancelled while waiting for its turn, and
+/// removes the queue when its last command leaves
+struct DepthGuard {
+    queues: Queues,
+    host_id: String,
+    queue: Arc<HostQueue>,
+    interceptor: Interceptor,
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        let depth = {
+            let mut queues = self.queues.lock().unwrap();
+            let depth = self.queue.depth.fetch_sub(1, Ordering::SeqCst) - 1;
+            if depth == 0 {
+                queues.remove(&self.host_id);
+            }
+            depth
+        };
+        self.interceptor.host_queue_depth(&self.host_id, depth);
+    }
+}
+
+impl HostQueues {
+    pub fn new(interceptor: Interceptor) -> HostQueues {
+        HostQueues {
+            queues: Queues::default(),
+            interceptor,
+        }
+    }
+
+    /// Waits until every earlier command to `host_id` has finished
+    pub async fn enter(&self, host_id: &str) -> HostQueueGuard {
+        let (queue, depth) = {
+            let mut queues = self.queues.lock().unwrap();
+            let queue = queues.entry(host_id.to_string()).or_default().clone();
+            let depth = queue.depth.fetch_add(1, Ordering::SeqCst) + 1;
+            (queue, depth)
+        };
+        trace!(%host_id, depth, "queued host command");
+        self.interceptor.host_queue_depth(host_id, depth);
+        let depth = DepthGuard {
+            queues: self.queues.clone(),
+            host_id: host_id.to_string(),
+            queue: queue.clone(),
+            interceptor: self.interceptor.clone(),
+        };
+        HostQueueGuard {
+            _lock: queue.lock.clone().lock_owned().await,
+            _depth: depth,
+        }
+    }
+
+    /// The number of commands to `host_id` that are queued or in flight
+    pub fn depth(&self, host_id: &str) -> usize {
+        self.queues
+            .lock()
+            .unwrap()
+            .get(host_id)
+            .map(|queue| queue.depth.load(Ordering::SeqCst))
+            .unwrap_or_default()
+    }
+
+    /// The number of commands queued or in flight for every host with at least one
+    pub fn depths(&self) -> HashMap<String, usize> {
+        self.queues
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(host_id, queue)| (host_id.clone(), queue.depth.load(Ordering::SeqCst)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Runs a fake command against `host` that takes `millis` to complete, recording when it
+    /// started and finished
+    async fn command(
+        queues: Arc<HostQueues>,
+        log: Arc<Mutex<Vec<String>>>,
+        host: &'static str,
+        name: &'static str,
+        millis: u64,
+    ) {
+        let _guard = queues.enter(host).await;
+        log.lock().unwrap().push(format!("{name} start"));
+        tokio::time::sleep(Duration::from_millis(millis)).await;
+        log.lock().unwrap().push(format!("{name} end"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn commands_to_one_host_run_in_order() {
+        let queues = Arc::new(HostQueues::default());
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let first = tokio::spawn(command(queues.clone(), log.clone(), "N1", "stop", 100));
+        tokio::task::yield_now().await;
+        let second = tokio::spawn(command(queues.clone(), log.clone(), "N1", "start", 10));
+        tokio::task::yield_now().await;
+        assert_eq!(queues.depth("N1"), 2);
+
+        first.await.unwrap();
+        second.await.unwrap();
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["stop start", "stop end", "start start", "start end"]
+        );
+        assert_eq!(queues.depth("N1"), 0);
+        assert!(queues.depths().is_empty());
+        assert!(queues.queues.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn commands_to_different_hosts_overlap() {
+        let queues = Arc::new(HostQueues::default());
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let first = tokio::spawn(command(queues.clone(), log.clone(), "N1", "one", 100));
+        tokio::task::yield_now().await;
+        let second = tokio::spawn(command(queues.clone(), log.clone(), "N2", "two", 10));
+        tokio::task::yield_now().await;
+        assert_eq!(
+            queues.depths(),
+            HashMap::from([("N1".to_string(), 1), ("N2".to_string(), 1)])
+        );
+
+        first.await.unwrap();
+        second.await.unwrap();
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["one start", "two start", "two end", "one end"]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled_waiter_leaves_queue() {
+        let queues = Arc::new(HostQueues::default());
+        let held = queues.enter("N1").await;
+        let waiting = tokio::spawn({
+            let queues = queues.clone();
+            async move {
+                let _guard = queues.enter("N1").await;
+            }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(queues.depth("N1"), 2);
+
+        waiting.abort();
+        let _ = waiting.await;
+        assert_eq!(queues.depth("N1"), 1);
+        drop(held);
+        assert_eq!(queues.depth("N1"), 0);
+        assert!(queues.queues.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn depth_changes_are_reported() {
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<(String, usize)>>);
+        impl crate::CtlInterceptor for Recorder {
+            fn on_host_queue_depth(&self, host_id: &str, depth: usize) {
+                self.0.lock().unwrap().push((host_id.to_string(), depth));
+            }
+        }
+
+        let recorder = Arc::new(Recorder::default());
+        let queues = HostQueues::new(Interceptor::new(Some(recorder.clone())));
+        let first = queues.enter("N1").await;
+        let second = queues.enter("N2").await;
+        drop(first);
+        drop(second);
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                ("N1".to_string(), 1),
+                ("N2".to_string(), 1),
+                ("N1".to_string(), 0),
+                ("N2".to_string(), 0)
+            ]
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! Tracking which hosts are alive from the lattice events they publish, for
+//! [`crate::Client::host_status_receiver`]
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use cloudevents::Event;
+use futures::{Stream, StreamExt};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::{json_deserialize, Host, HostHeartbeat, HostStatusUpdate, LatticeEvent, TypedEvent};
+
+/// Describes the host that published a heartbeat
+pub(crate) fn heartbeat_host(heartbeat: TypedEvent<HostHeartbeat>) -> Host {
+    Host {
+        id: heartbeat.host_id,
+        friendly_name: heartbeat.data.friendly_name,
+        labels: Some(heartbeat.data.labels),
+        uptime_seconds: heartbeat.data.uptime_seconds,
+        version: heartbeat.data.version,
+        ..Default::default()
+    }
+}
+
+struct Seen {
+    last: Instant,
+    stale: bool,
+}
+
+/// The hosts that have been heard from, and when
+pub(crate) struct Liveness {
+    stale_after: Duration,
+    hosts: HashMap<String, Seen>,
+}
+
+impl Liveness {
+    pub fn new(stale_after: Duration) -> Liveness {
+        Liveness {
+            stale_after,
+            hosts: HashMap::new(),
+        }
+    }
+
+    /// Records an event, returning the update it causes, if any. A host is reported up the first
+    /// time it is heard from and again whenever it is heard from after going stale
+    pub fn observe(&mut self, event: LatticeEvent, now: Instant) -> Option<HostStatusUpdate> {
+        match event {
+            LatticeEvent::HostHeartbeat(heartbeat) => self.seen(heartbeat_host(heartbeat), now),
+            LatticeEvent::HostStarted(started) => self.seen(
+                Host {
+                    id: started.host_id,
+                    friendly_name: started.data.friendly_name,
+                    labels: Some(started.data.labels),
+                    ..Default::default()
+                },
+                now,
+            ),
+            LatticeEvent::HostStopped(stopped) => {
+                self.hosts.remove(&stopped.host_id);
+                Some(HostStatusUpdate::Down(stopped.host_id))
+            }
+            _ => None,
+        }
+    }
+
+    fn seen(&mut self, host: Host, now: Instant) -> Option<HostStatusUpdate> {
+        let previous = self.hosts.insert(
+            host.id.clone(),
+            Seen {
+                last: now,
+                stale: false,
+            },
+        );
+        match previous {
+            Some(Seen { stale: false, .. }) => None,
+            _ => Some(HostStatusUpdate::Up(host)),
+        }
+    }
+
+    /// When the next live host goes stale if it isn't heard from, if any host is live
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.hosts
+            .values()
+            .filter(|seen| !seen.stale)
+            .map(|seen| seen.last + self.stale_after)
+            .min()
+    }
+
+    /// Marks every live host that hasn't been heard from within the window as stale, returning an
+    /// update for each, in host ID order
+    pub fn expire(&mut self, now: Instant) -> Vec<HostStatusUpdate> {
+        let mut expired: Vec<_> = self
+            .hosts
+            .iter_mut()
+            .filter(|(_, seen)| !seen.stale && seen.last + self.stale_after <= now)
+            .map(|(host_id, seen)| {
+                seen.stale = true;
+                host_id.clone()
+            })
+            .collect();
+        expired.sort();
+        expired.into_iter().map(HostStatusUpdate::Stale).collect()
+    }
+}
+
+/// Tracks hosts from the event payloads, sending every update to `sender`, until the payloads end
+/// or the receiver is dropped
+pub(crate) async fn track<S>(
+    mut payloads: S,
+    stale_after: Duration,
+    sender: mpsc::Sender<HostStatusUpdate>,
+) where
+    S: Stream + Unpin,
+    S::Item: AsRef<[u8]>,
+{
+    let mut liveness = Liveness::new(stale_after);
+    loop {
+        let deadline = liveness.next_deadline();
+        let expired = async {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+        let updates = tokio::select! {
+            payload = payloads.next() => {
+                let Some(payload) = payload else { break };
+                let Ok(event) = json_deserialize::<Event>(payload.as_ref()) else {
+                    continue;
+                };
+                liveness
+                    .observe(LatticeEvent::from(event), Instant::now())
+                    .into_iter()
+                    .collect()
+            }
+            _ = expired => liveness.expire(Instant::now()),
+            _ = sender.closed() => break,
+        };
+        for update in updates {
+            if sender.send(update).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cloudevents::{EventBuilder as _, EventBuilderV10};
+
+    use super::*;
+    use crate::{HostStarted, HostStopped};
+
+    fn typed<T>(host_id: &str, data: T) -> TypedEvent<T> {
+        TypedEvent {
+            id: "1".to_string(),
+            host_id: host_id.to_string(),
+            timestamp: None,
+            data,
+        }
+    }
+
+    fn heartbeat(host_id: &str) -> LatticeEvent {
+        LatticeEvent::HostHeartbeat(typed(
+            host_id,
+            HostHeartbeat {
+                friendly_name: "silent-bush-1234".to_string(),
+                ..Default::default()
+            },
+        ))
+    }
+
+    #[test]
+    fn hosts_are_up_once_until_they_go_stale() {
+        let mut liveness = Liveness::new(Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(matches!(
+            liveness.observe(heartbeat("N1"), start),
+            Some(HostStatusUpdate::Up(host)) if host.id == "N1" && host.friendly_name == "silent-bush-1234"
+        ));
+        assert_eq!(liveness.observe(heartbeat("N1"), start), None);
+        assert!(matches!(
+            liveness.observe(
+                LatticeEvent::HostStarted(typed("N2", HostStarted::default())),
+                start + Duration::from_secs(30)
+            ),
+            Some(HostStatusUpdate::Up(_))
+        ));
+        assert_eq!(
+            liveness.next_deadline(),
+            Some(start + Duration::from_secs(60))
+        );
+
+        assert_eq!(
+            liveness.expire(start + Duration::from_secs(60)),
+            [HostStatusUpdate::Stale("N1".to_string())]
+        );
+        assert_eq!(
+            liveness.next_deadline(),
+            Some(start + Duration::from_secs(90))
+        );
+        assert!(liveness.expire(start + Duration::from_secs(61)).is_empty());
+        assert!(matches!(
+            liveness.observe(heartbeat("N1"), start + Duration::from_secs(70)),
+            Some(HostStatusUpdate::Up(_))
+        ));
+    }
+
+    #[test]
+    fn stopped_hosts_are_down_and_forgotten() {
+        let mut liveness = Liveness::new(Duration::from_secs(60));
+        let now = Instant::now();
+        liveness.observe(heartbeat("N1"), now);
+        assert_eq!(
+            liveness.observe(
+                LatticeEvent::HostStopped(typed("N1", HostStopped::default())),
+                now
+            ),
+            Some(HostStatusUpdate::Down("N1".to_string()))
+        );
+        assert_eq!(liveness.next_deadline(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn silent_hosts_go_stale_and_dropping_the_receiver_stops_tracking() {
+        let (payloads, events) = futures::channel::mpsc::unbounded::<Vec<u8>>();
+        let (sender, mut receiver) = mpsc::channel(8);
+        let tracker = tokio::spawn(track(events, Duration::from_secs(60), sender));
+        let event = EventBuilderV10::new()
+            .id("1")
+            .source("N1")
+            .ty(LatticeEvent::HOST_HEARTBEAT)
+            .data("application/json", serde_json::json!({}))
+            .build()
+            .unwrap();
+        payloads
+            .unbounded_send(serde_json::to_vec(&event).unwrap())
+            .unwrap();
+
+        assert!(matches!(
+            receiver.recv().await,
+            Some(HostStatusUpdate::Up(_))
+        ));
+        let start = Instant::now();
+        assert_eq!(
+            receiver.recv().await,
+            Some(HostStatusUpdate::Stale("N1".to_string()))
+        );
+        assert_eq!(start.elapsed(), Duration::from_secs(60));
+
+        drop(receiver);
+        tracker.await.unwrap();
+        drop(payloads);
+    }
+}
@@ -0,0 +1,142 @@
+//! Observation of the raw control interface traffic a client sends and receives, for auditing
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{debug, error};
+
+use crate::Error;
+
+/// Observes every control interface request a client makes, set with
+/// [`crate::ClientBuilder::with_interceptor`]. Each method has a default that does nothing, so an
+/// implementation only needs the ones it cares about.
+///
+/// The methods are called inline on the request path, so they should be quick; anything slow
+/// belongs on a channel or background task. A method that panics is logged and otherwise ignored,
+/// so it can't fail the client call that invoked it
+pub trait CtlInterceptor: Send + Sync {
+    /// Called before a request or auction is published on `subject`. Retries are reported as
+    /// separate requests
+    fn on_request(&self, _subject: &str, _payload: &[u8]) {}
+
+    /// Called for every response to a request on `subject`, `elapsed` after it was published.
+    /// Auctions and other scatter/gather requests report one response per host that answered
+    fn on_response(&self, _subject: &str, _payload: &[u8], _elapsed: Duration) {}
+
+    /// Called when a request on `subject` fails, including when it times out
+    fn on_error(&self, _subject: &str, _err: &Error) {}
+
+    /// Called on a client that serializes host commands whenever a command to `host_id` joins or
+    /// leaves its queue, with the number of commands now queued or in flight. Useful for exporting
+    /// queue depth as a metric
+    fn on_host_queue_depth(&self, _host_id: &str, _depth: usize) {}
+}
+
+/// A [`CtlInterceptor`] that logs every request, response and error with `tracing` at debug level,
+/// payloads included
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingInterceptor;
+
+impl CtlInterceptor for TracingInterceptor {
+    fn on_request(&self, subject: &str, payload: &[u8]) {
+        debug!(%subject, payload = %String::from_utf8_lossy(payload), "control request");
+    }
+
+    fn on_response(&self, subject: &str, payload: &[u8], elapsed: Duration) {
+        debug!(%subject, ?elapsed, payload = %String::from_utf8_lossy(payload), "control response");
+    }
+
+    fn on_error(&self, subject: &str, err: &Error) {
+        debug!(%subject, %err, "control request failed");
+    }
+}
+
+/// The interceptor a client was built with, if any
+#[derive(Clone, Default)]
+pub(crate) struct Interceptor(Option<Arc<dyn CtlInterceptor>>);
+
+impl Interceptor {
+    pub fn new(interceptor: Option<Arc<dyn CtlInterceptor>>) -> Interceptor {
+        Interceptor(interceptor)
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn request(&self, subject: &str, payload: &[u8]) {
+        self.call("on_request", |interceptor| {
+            interceptor.on_request(subject, payload)
+        });
+    }
+
+    pub fn response(&self, subject: &str, payload: &[u8], elapsed: Duration) {
+        self.call("on_response", |interceptor| {
+            interceptor.on_response(subject, payload, elapsed)
+        });
+    }
+
+    pub fn error(&self, subject: &str, err: &Error) {
+        self.call("on_error", |interceptor| interceptor.on_error(subject, err));
+    }
+
+    pub fn host_queue_depth(&self, host_id: &str, depth: usize) {
+        self.call("on_host_queue_depth", |interceptor| {
+            interceptor.on_host_queue_depth(host_id, depth)
+        });
+    }
+
+    /// Calls the interceptor if there is one, containing any panic
+    fn call(&self, method: &str, f: impl FnOnce(&dyn CtlInterceptor)) {
+        if let Some(interceptor) = &self.0 {
+            if catch_unwind(AssertUnwindSafe(|| f(interceptor.as_ref()))).is_err() {
+                error!(%method, "control interface interceptor panicked");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl CtlInterceptor for Recorder {
+        fn on_request(&self, subject: &str, _payload: &[u8]) {
+            self.0.lock().unwrap().push(format!("request {subject}"));
+        }
+
+        fn on_error(&self, subject: &str, _err: &Error) {
+            self.0.lock().unwrap().push(format!("error {subject}"));
+        }
+    }
+
+    struct Panicker;
+
+    impl CtlInterceptor for Panicker {
+        fn on_request(&self, _subject: &str, _payload: &[u8]) {
+            panic!("interceptor bug");
+        }
+    }
+
+    #[test]
+    fn calls_only_the_methods_an_interceptor_implements() {
+        let recorder = Arc::new(Recorder::default());
+        let interceptor = Interceptor::new(Some(recorder.clone()));
+        interceptor.request("a", b"{}");
+        interceptor.response("a", b"{}", Duration::from_millis(1));
+        interceptor.error("b", &Error::nats("down"));
+        assert_eq!(*recorder.0.lock().unwrap(), ["request a", "error b"]);
+    }
+
+    #[test]
+    fn panics_are_contained() {
+        Interceptor::new(Some(Arc::new(Panicker))).request("a", b"{}");
+        Interceptor::default().request("a", b"{}");
+    }
+}
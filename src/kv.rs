@@ -0,0 +1,759 @@
+//! Helpers for working with the lattice metadata key-value bucket (`LATTICEDATA_{prefix}`). Hosts
+//! keep link definitions, claims, and other shared lattice state in this bucket when it exists
+
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_nats::jetstream::kv::{Operation, Store};
+use async_nats::jetstream::stream::StorageType;
+use data_encoding::{BASE64URL_NOPAD, HEXUPPER};
+use futures::StreamExt;
+use serde::Deserialize;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+
+use crate::{
+    json_deserialize, json_serialize, ClaimsChange, ConfigValues, Error, GenerationChanged,
+    LinkChange, LinkDefinition, LinkFilter, NamedConfig, RegistryCredentialMap, Result,
+};
+
+pub(crate) const LATTICE_METADATA_PREFIX: &str = "LATTICEDATA_";
+/// Key holding a value that is unique to each incarnation of the bucket. If the bucket is deleted
+/// and recreated, the marker changes, which lets long-lived clients detect that any state they
+/// derived from the old bucket is stale
+pub(crate) const GENERATION_KEY: &str = "LATTICE_GENERATION";
+pub(crate) const CLAIMS_PREFIX: &str = "CLAIMS_";
+pub(crate) const LINKDEF_PREFIX: &str = "LINKDEF_";
+pub(crate) const REGISTRIES_KEY: &str = "REGISTRIES";
+pub(crate) const CONFIG_PREFIX: &str = "CONFIG_";
+
+/// How many changes can be waiting to be read before a bucket watch pauses
+const WATCH_BUFFER: usize = 256;
+
+pub(crate) const CLAIM_SUBJECT: &str = "sub";
+const CLAIM_ISSUER: &str = "iss";
+const CLAIM_JWT: &str = "jwt";
+
+/// Returns the name of the metadata bucket for the given lattice
+pub(crate) fn bucket_name(lattice_prefix: &str) -> String {
+    format!("{LATTICE_METADATA_PREFIX}{lattice_prefix}")
+}
+
+/// Looks up the metadata bucket for the given lattice, returning `None` if it doesn't exist (or
+/// JetStream isn't available) in which case the client operates in legacy topic-only mode
+pub(crate) async fn get_kv_store(
+    nc: async_nats::Client,
+    lattice_prefix: &str,
+    js_domain: Option<String>,
+) -> Option<Store> {
+    let js_context = if let Some(domain) = js_domain {
+        async_nats::jetstream::with_domain(nc, domain)
+    } else {
+        async_nats::jetstream::new(nc)
+    };
+    let bucket = bucket_name(lattice_prefix);
+    match js_context.get_key_value(&bucket).await {
+        Ok(store) => Some(store),
+        Err(error) => {
+            debug!(%error, %bucket, "lattice metadata bucket not found");
+            None
+        }
+    }
+}
+
+/// How [`crate::Client::ensure_lattice_metadata_bucket`] creates the lattice metadata bucket if it
+/// doesn't exist. An existing bucket is used as it is
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BucketConfig {
+    history: i64,
+    storage: StorageType,
+    replicas: usize,
+}
+
+impl Default for BucketConfig {
+    fn default() -> BucketConfig {
+        BucketConfig {
+            history: 1,
+            storage: StorageType::File,
+            replicas: 1,
+        }
+    }
+}
+
+impl BucketConfig {
+    /// Creates the configuration wasmCloud hosts use for the bucket: file storage, one replica,
+    /// and only the latest value of each key
+    pub fn new() -> BucketConfig {
+        BucketConfig::default()
+    }
+
+    /// Sets how many values of each key are kept. Defaults to 1
+    pub fn history(self, history: i64) -> BucketConfig {
+        BucketConfig { history, ..self }
+    }
+
+    /// Sets where the bucket is stored. Defaults to [`StorageType::File`]
+    pub fn storage(self, storage: StorageType) -> BucketConfig {
+        BucketConfig { storage, ..self }
+    }
+
+    /// Sets how many replicas of the bucket a clustered JetStream keeps. Defaults to 1
+    pub fn replicas(self, replicas: usize) -> BucketConfig {
+        BucketConfig { replicas, ..self }
+    }
+}
+
+/// Returns the metadata bucket for the given lattice, creating it first if it doesn't exist.
+/// Returns true along with the bucket if this call created it
+pub(crate) async fn create_kv_store(
+    nc: async_nats::Client,
+    lattice_prefix: &str,
+    js_domain: Option<String>,
+    config: &BucketConfig,
+) -> Result<(Store, bool)> {
+    if let Some(store) = get_kv_store(nc.clone(), lattice_prefix, js_domain.clone()).await {
+        return Ok((store, false));
+    }
+    let js_context = match js_domain.clone() {
+        Some(domain) => async_nats::jetstream::with_domain(nc.clone(), domain),
+        None => async_nats::jetstream::new(nc.clone()),
+    };
+    let bucket = bucket_name(lattice_prefix);
+    let created = js_context
+        .create_key_value(async_nats::jetstream::kv::Config {
+            bucket: bucket.clone(),
+            description: format!("Lattice metadata for {lattice_prefix}"),
+            history: config.history,
+            storage: config.storage,
+            num_replicas: config.replicas,
+            ..Default::default()
+        })
+        .await;
+    match created {
+        Ok(store) => Ok((store, true)),
+        Err(error) => {
+            // Another client may have created the bucket since we looked
+            debug!(%error, %bucket, "creating lattice metadata bucket failed, looking it up again");
+            match get_kv_store(nc, lattice_prefix, js_domain).await {
+                Some(store) => Ok((store, false)),
+                None => Err(Error::kv(error)),
+            }
+        }
+    }
+}
+
+/// Reads the generation marker from the bucket, if one has been written
+pub(crate) async fn get_generation(store: &Store) -> Result<Option<String>> {
+    let value = store.get(GENERATION_KEY).await.map_err(Error::kv)?;
+    Ok(value.map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+/// Returns the generation marker, writing a new one first if the bucket doesn't have one yet.
+/// Should be called before the first write a client makes to a bucket
+pub(crate) async fn ensure_generation(store: &Store) -> Result<String> {
+    if let Some(generation) = get_generation(store).await? {
+        return Ok(generation);
+    }
+    let generation = new_generation();
+    // A revision of 0 only succeeds if the key doesn't exist, so concurrent writers agree on a
+    // single marker
+    if store
+        .update(GENERATION_KEY, generation.clone().into(), 0)
+        .await
+        .is_ok()
+    {
+        return Ok(generation);
+    }
+    get_generation(store)
+        .await?
+        .ok_or_else(|| Error::kv("lattice generation was neither written nor found"))
+}
+
+fn new_generation() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!("{nanos:x}")
+}
+
+/// Compares two observations of the bucket generation, where `None` means there was no bucket or
+/// it had no marker yet. The first marker to appear after `None` is the baseline rather than a
+/// change: it is either written to a bucket that was already being observed, or it follows the
+/// disappearance of the previous marker, which was reported already
+pub(crate) fn generation_change(
+    previous: Option<String>,
+    current: &Option<String>,
+) -> Option<GenerationChanged> {
+    match previous {
+        Some(previous) if current.as_ref() != Some(&previous) => Some(GenerationChanged {
+            previous: Some(previous),
+            current: current.clone(),
+        }),
+        _ => None,
+    }
+}
+
+/// Reads every claims entry from the bucket
+pub(crate) async fn get_claims(store: &Store) -> Result<Vec<HashMap<String, String>>> {
+    let mut keys = store.keys().await.map_err(Error::kv)?;
+    let mut claims = Vec::new();
+    while let Some(key) = keys.next().await {
+        let key = key.map_err(Error::kv)?;
+        if !key.starts_with(CLAIMS_PREFIX) {
+            continue;
+        }
+        if let Some(bytes) = store.get(key.as_str()).await.map_err(Error::kv)? {
+            claims.push(json_deserialize(&bytes)?);
+        }
+    }
+    Ok(claims)
+}
+
+/// Reads the claims entry for the given subject, if there is one
+pub(crate) async fn get_claims_for(
+    store: &Store,
+    subject: &str,
+) -> Result<Option<HashMap<String, String>>> {
+    match store.get(claims_key(subject)).await.map_err(Error::kv)? {
+        Some(bytes) => Ok(Some(json_deserialize(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Reads the registry credentials stored in the bucket, if any have been stored
+pub(crate) async fn get_registries(store: &Store) -> Result<Option<RegistryCredentialMap>> {
+    match store.get(REGISTRIES_KEY).await.map_err(Error::kv)? {
+        Some(bytes) => Ok(Some(json_deserialize(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Reads the named config, if it exists
+pub(crate) async fn get_config(store: &Store, name: &str) -> Result<Option<ConfigValues>> {
+    match store.get(config_key(name)).await.map_err(Error::kv)? {
+        Some(bytes) => Ok(Some(json_deserialize(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Writes the named config, replacing any existing values
+pub(crate) async fn put_config(store: &Store, name: &str, values: &ConfigValues) -> Result<()> {
+    let bytes = json_serialize(values)?;
+    store
+        .put(config_key(name), bytes.into())
+        .await
+        .map_err(Error::kv)?;
+    Ok(())
+}
+
+/// Deletes the named config. Deleting config that doesn't exist is not an error
+pub(crate) async fn delete_config(store: &Store, name: &str) -> Result<()> {
+    store.delete(config_key(name)).await.map_err(Error::kv)
+}
+
+/// Lists the names of every config in the bucket
+pub(crate) async fn list_config_names(store: &Store) -> Result<Vec<String>> {
+    let mut keys = store.keys().await.map_err(Error::kv)?;
+    let mut names = Vec::new();
+    while let Some(key) = keys.next().await {
+        if let Some(name) = key.map_err(Error::kv)?.strip_prefix(CONFIG_PREFIX) {
+            names.push(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+/// Reads every config in the bucket, sorted by name. Config deleted while this runs is left out
+pub(crate) async fn list_configs(store: &Store) -> Result<Vec<NamedConfig>> {
+    let mut names = list_config_names(store).await?;
+    names.sort();
+    let mut configs = Vec::with_capacity(names.len());
+    for name in names {
+        if let Some(values) = get_config(store, &name).await? {
+            configs.push(NamedConfig { name, values });
+        }
+    }
+    Ok(configs)
+}
+
+fn config_key(name: &str) -> String {
+    format!("{CONFIG_PREFIX}{name}")
+}
+
+/// Writes a claims entry, keyed by its subject
+pub(crate) async fn put_claims(store: &Store, claims: &HashMap<String, String>) -> Result<()> {
+    let subject = claims
+        .get(CLAIM_SUBJECT)
+        .ok_or_else(|| invalid_claims("Claims are missing a subject"))?;
+    let bytes = json_serialize(claims)?;
+    store
+        .put(claims_key(subject), bytes.into())
+        .await
+        .map_err(Error::kv)?;
+    Ok(())
+}
+
+/// Deletes the claims entry for the given subject. Deleting claims that don't exist is not an error
+pub(crate) async fn delete_claims(store: &Store, subject: &str) -> Result<()> {
+    store.delete(claims_key(subject)).await.map_err(Error::kv)
+}
+
+pub(crate) fn claims_key(subject: &str) -> String {
+    format!("{CLAIMS_PREFIX}{subject}")
+}
+
+/// Reads the link definitions that match `filter`. A filter naming the actor, contract and link
+/// name identifies a single key, which is read directly; otherwise every link key is scanned
+pub(crate) async fn get_links(store: &Store, filter: &LinkFilter) -> Result<Vec<LinkDefinition>> {
+    if let (Some(actor_id), Some(contract_id), Some(link_name)) =
+        (&filter.actor_id, &filter.contract_id, &filter.link_name)
+    {
+        let key = link_key(actor_id, contract_id, link_name);
+        return match store.get(key).await.map_err(Error::kv)? {
+            Some(bytes) => {
+                let link: LinkDefinition = json_deserialize(&bytes)?;
+                Ok(filter.matches(&link).then_some(link).into_iter().collect())
+            }
+            None => Ok(Vec::new()),
+        };
+    }
+    let mut keys = store.keys().await.map_err(Error::kv)?;
+    let mut links = Vec::new();
+    while let Some(key) = keys.next().await {
+        let key = key.map_err(Error::kv)?;
+        if !key.starts_with(LINKDEF_PREFIX) {
+            continue;
+        }
+        if let Some(bytes) = store.get(key.as_str()).await.map_err(Error::kv)? {
+            let link: LinkDefinition = json_deserialize(&bytes)?;
+            if filter.matches(&link) {
+                links.push(link);
+            }
+        }
+    }
+    Ok(links)
+}
+
+/// Writes a link definition, replacing any existing link with the same actor, contract and name
+pub(crate) async fn put_link(store: &Store, link: &LinkDefinition) -> Result<()> {
+    let bytes = json_serialize(link)?;
+    store
+        .put(
+            link_key(&link.actor_id, &link.contract_id, &link.link_name),
+            bytes.into(),
+        )
+        .await
+        .map_err(Error::kv)?;
+    Ok(())
+}
+
+/// Writes a link definition unless the bucket already has one for the same actor, contract and
+/// link name, returning whether it was written. The check and the write are a single atomic
+/// operation, so of several concurrent writers exactly one succeeds
+pub(crate) async fn put_link_if_absent(store: &Store, link: &LinkDefinition) -> Result<bool> {
+    let key = link_key(&link.actor_id, &link.contract_id, &link.link_name);
+    let bytes = json_serialize(link)?;
+    // A revision of 0 only succeeds if the key has never been written. A deleted link leaves a
+    // marker behind, which is written over by naming the marker's revision instead
+    let mut revision = 0;
+    loop {
+        let error = match store.update(&key, bytes.clone().into(), revision).await {
+            Ok(_) => return Ok(true),
+            Err(e) => e,
+        };
+        match store.entry(key.clone()).await.map_err(Error::kv)? {
+            Some(entry) if entry.operation == Operation::Put => return Ok(false),
+            Some(entry) if entry.revision != revision => revision = entry.revision,
+            _ => return Err(Error::kv(error)),
+        }
+    }
+}
+
+/// Deletes a link definition. Deleting a link that doesn't exist is not an error
+pub(crate) async fn delete_link(
+    store: &Store,
+    actor_id: &str,
+    contract_id: &str,
+    link_name: &str,
+) -> Result<()> {
+    store
+        .delete(link_key(actor_id, contract_id, link_name))
+        .await
+        .map_err(Error::kv)
+}
+
+/// Watches the bucket for link changes until the returned receiver is dropped. Link keys are
+/// hashes, so a deleted key says nothing about the link it held; the links in the bucket are
+/// tracked as the watch runs so that deletions can be reported in full
+pub(crate) async fn watch_links(store: Store) -> Result<mpsc::Receiver<LinkChange>> {
+    watch_changes(store, link_change).await
+}
+
+/// Watches the bucket for claims changes until the returned receiver is dropped
+pub(crate) async fn watch_claims(store: Store) -> Result<mpsc::Receiver<ClaimsChange>> {
+    watch_changes(store, claims_change).await
+}
+
+/// Runs a watch over every key in the bucket, turning each change into `T` with `change`. The
+/// watch starts with the latest value of every key, which is passed to `change` to build up its
+/// state but not reported, so the state can't miss a change made while the watch was starting.
+/// Only changes made after the watch was requested are reported
+async fn watch_changes<T, S>(
+    store: Store,
+    change: fn(&mut S, String, Operation, &[u8]) -> Option<T>,
+) -> Result<mpsc::Receiver<T>>
+where
+    T: Send + 'static,
+    S: Default + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(WATCH_BUFFER);
+    let (ready_tx, ready_rx) = oneshot::channel();
+    tokio::spawn(async move {
+        // Every revision up to this one was written before the watch was requested
+        let last_revision = match store.status().await {
+            Ok(status) => status.info.state.last_sequence,
+            Err(e) => {
+                let _ = ready_tx.send(Err(Error::kv(e)));
+                return;
+            }
+        };
+        // The watch borrows the store, so it has to be started by the task that keeps it running
+        let mut watch = match store.watch_with_history(">").await {
+            Ok(watch) => watch,
+            Err(e) => {
+                let _ = ready_tx.send(Err(Error::kv(e)));
+                return;
+            }
+        };
+        let mut state = S::default();
+        let _ = ready_tx.send(Ok(()));
+        loop {
+            let entry = tokio::select! {
+                entry = watch.next() => entry,
+                _ = sender.closed() => break,
+            };
+            let entry = match entry {
+                Some(Ok(entry)) => entry,
+                Some(Err(error)) => {
+                    warn!(%error, "bucket watch failed");
+                    break;
+                }
+                None => break,
+            };
+            let change = change(&mut state, entry.key, entry.operation, &entry.value);
+            let Some(change) = change.filter(|_| entry.revision > last_revision) else {
+                continue;
+            };
+            if sender.send(change).await.is_err() {
+                break;
+            }
+        }
+        debug!("bucket watch stopped");
+    });
+    ready_rx
+        .await
+        .map_err(|_| Error::kv("bucket watch stopped before it started"))??;
+    Ok(receiver)
+}
+
+/// Turns a change to a bucket key into a claims change. Returns `None` for keys that don't hold
+/// claims and for unreadable claims
+fn claims_change(
+    _: &mut (),
+    key: String,
+    operation: Operation,
+    value: &[u8],
+) -> Option<ClaimsChange> {
+    let subject = key.strip_prefix(CLAIMS_PREFIX)?;
+    match operation {
+        Operation::Put => match json_deserialize(value) {
+            Ok(claims) => Some(ClaimsChange::Put(claims)),
+            Err(error) => {
+                warn!(%error, %key, "claims in bucket could not be read");
+                None
+            }
+        },
+        Operation::Delete | Operation::Purge => Some(ClaimsChange::Delete {
+            subject: subject.to_string(),
+        }),
+    }
+}
+
+/// Turns a change to a bucket key into a link change, keeping `known` up to date. Returns `None`
+/// for keys that don't hold links, unreadable links, and deletions of links that were never seen
+fn link_change(
+    known: &mut HashMap<String, LinkDefinition>,
+    key: String,
+    operation: Operation,
+    value: &[u8],
+) -> Option<LinkChange> {
+    if !key.starts_with(LINKDEF_PREFIX) {
+        return None;
+    }
+    match operation {
+        Operation::Put => match json_deserialize::<LinkDefinition>(value) {
+            Ok(link) => {
+                known.insert(key, link.clone());
+                Some(LinkChange::Put(link))
+            }
+            Err(error) => {
+                warn!(%error, %key, "link definition in bucket could not be read");
+                None
+            }
+        },
+        Operation::Delete | Operation::Purge => {
+            let link = known.remove(&key);
+            if link.is_none() {
+                debug!(%key, "deleted link was never seen");
+            }
+            link.map(|link| LinkChange::Delete {
+                actor_id: link.actor_id,
+                contract_id: link.contract_id,
+                link_name: link.link_name,
+            })
+        }
+    }
+}
+
+/// Links are keyed by a hash of what identifies them, which is the same key hosts use
+pub(crate) fn link_key(actor_id: &str, contract_id: &str, link_name: &str) -> String {
+    let hash = ring::digest::digest(
+        &ring::digest::SHA256,
+        format!("{actor_id}{contract_id}{link_name}").as_bytes(),
+    );
+    format!("{LINKDEF_PREFIX}{}", HEXUPPER.encode(hash.as_ref()))
+}
+
+/// Checks that a claims map is fit to be written to the bucket, returning its subject. The map
+/// must have non-empty `sub` and `iss` fields. If the map carries the original signed token under
+/// `jwt`, the token's subject and issuer must agree with the map. If `trusted_issuers` is not
+/// empty, the issuer must be one of them
+pub(crate) fn validate_claims(
+    claims: &HashMap<String, String>,
+    trusted_issuers: &[String],
+) -> Result<String> {
+    let subject = claims
+        .get(CLAIM_SUBJECT)
+        .filter(|sub| !sub.is_empty())
+        .ok_or_else(|| invalid_claims("Claims must contain a non-empty `sub` field"))?;
+    let issuer = claims
+        .get(CLAIM_ISSUER)
+        .filter(|iss| !iss.is_empty())
+        .ok_or_else(|| invalid_claims("Claims must contain a non-empty `iss` field"))?;
+    if let Some(jwt) = claims.get(CLAIM_JWT) {
+        let token = decode_jwt_claims(jwt)?;
+        if token.sub != *subject {
+            return Err(invalid_claims(format!(
+                "JWT subject {} does not match claims subject {subject}",
+                token.sub
+            )));
+        }
+        if *issuer != token.iss {
+            return Err(invalid_claims(format!(
+                "JWT issuer {} does not match claims issuer",
+                token.iss
+            )));
+        }
+    }
+    if !trusted_issuers.is_empty() && !trusted_issuers.contains(issuer) {
+        return Err(invalid_claims(format!(
+            "Claims issuer {issuer} is not trusted"
+        )));
+    }
+    Ok(subject.to_owned())
+}
+
+fn invalid_claims(reason: impl Into<String>) -> Error {
+    Error::InvalidClaims {
+        reason: reason.into(),
+    }
+}
+
+#[derive(Deserialize)]
+struct JwtClaims {
+    #[serde(default)]
+    sub: String,
+    #[serde(default)]
+    iss: String,
+}
+
+/// Decodes (without verifying) the payload segment of a JWT
+fn decode_jwt_claims(jwt: &str) -> Result<JwtClaims> {
+    let payload = jwt
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| invalid_claims("Embedded JWT is not a valid token"))?;
+    let bytes = BASE64URL_NOPAD
+        .decode(payload.trim_end_matches('=').as_bytes())
+        .map_err(|e| invalid_claims(format!("Embedded JWT payload is not valid base64: {e}")))?;
+    json_deserialize(&bytes)
+        .map_err(|e| invalid_claims(format!("Embedded JWT payload is not valid JSON: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jwt(sub: &str, iss: &str) -> String {
+        let payload = serde_json::json!({ "sub": sub, "iss": iss }).to_string();
+        format!(
+            "eyJhbGciOiJFZDI1NTE5In0.{}.c2ln",
+            BASE64URL_NOPAD.encode(payload.as_bytes())
+        )
+    }
+
+    fn claims(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn validate_claims_requires_subject() {
+        assert!(validate_claims(&claims(&[("iss", "Axxx")]), &[]).is_err());
+        assert!(validate_claims(&claims(&[("sub", ""), ("iss", "Axxx")]), &[]).is_err());
+        assert_eq!(
+            validate_claims(&claims(&[("sub", "Mxxx"), ("iss", "Axxx")]), &[]).unwrap(),
+            "Mxxx"
+        );
+    }
+
+    #[test]
+    fn validate_claims_requires_issuer() {
+        assert!(validate_claims(&claims(&[("sub", "Mxxx")]), &[]).is_err());
+        assert!(validate_claims(&claims(&[("sub", "Mxxx"), ("iss", "")]), &[]).is_err());
+    }
+
+    #[test]
+    fn validate_claims_checks_embedded_jwt() {
+        let ok = claims(&[
+            ("sub", "Mxxx"),
+            ("iss", "Axxx"),
+            ("jwt", &jwt("Mxxx", "Axxx")),
+        ]);
+        assert_eq!(validate_claims(&ok, &[]).unwrap(), "Mxxx");
+
+        let wrong_sub = claims(&[
+            ("sub", "Mxxx"),
+            ("iss", "Axxx"),
+            ("jwt", &jwt("Myyy", "Axxx")),
+        ]);
+        assert!(validate_claims(&wrong_sub, &[]).is_err());
+
+        let wrong_iss = claims(&[
+            ("sub", "Mxxx"),
+            ("iss", "Ayyy"),
+            ("jwt", &jwt("Mxxx", "Axxx")),
+        ]);
+        assert!(validate_claims(&wrong_iss, &[]).is_err());
+
+        let garbage = claims(&[("sub", "Mxxx"), ("iss", "Axxx"), ("jwt", "not-a-jwt")]);
+        assert!(validate_claims(&garbage, &[]).is_err());
+    }
+
+    #[test]
+    fn link_key_hashes_link_identity() {
+        assert_eq!(
+            link_key("Mxxx", "wasmcloud:httpserver", "default"),
+            "LINKDEF_8EE761967527DA1C3FD90BF083AC86519200AE05D0AE0E5AE472747FA10F5B58"
+        );
+    }
+
+    #[test]
+    fn link_changes_track_deleted_links() {
+        let link = LinkDefinition {
+            actor_id: "Mxxx".to_string(),
+            provider_id: "Vxxx".to_string(),
+            contract_id: "wasmcloud:httpserver".to_string(),
+            link_name: "default".to_string(),
+            values: HashMap::new(),
+        };
+        let key = link_key(&link.actor_id, &link.contract_id, &link.link_name);
+        let mut known = HashMap::new();
+
+        let put = link_change(
+            &mut known,
+            key.clone(),
+            Operation::Put,
+            &serde_json::to_vec(&link).unwrap(),
+        );
+        assert_eq!(put, Some(LinkChange::Put(link)));
+
+        let delete = link_change(&mut known, key.clone(), Operation::Delete, b"");
+        assert_eq!(
+            delete,
+            Some(LinkChange::Delete {
+                actor_id: "Mxxx".to_string(),
+                contract_id: "wasmcloud:httpserver".to_string(),
+                link_name: "default".to_string(),
+            })
+        );
+        assert!(known.is_empty());
+
+        // Deletions of unknown links and changes to other keys are skipped
+        assert_eq!(link_change(&mut known, key, Operation::Purge, b""), None);
+        assert_eq!(
+            link_change(&mut known, "CLAIMS_Mxxx".to_string(), Operation::Put, b"{}"),
+            None
+        );
+    }
+
+    #[test]
+    fn claims_changes_carry_the_subject() {
+        let claims = claims(&[("sub", "Mxxx"), ("iss", "Axxx")]);
+        assert_eq!(
+            claims_change(
+                &mut (),
+                claims_key("Mxxx"),
+                Operation::Put,
+                &serde_json::to_vec(&claims).unwrap()
+            ),
+            Some(ClaimsChange::Put(claims))
+        );
+        assert_eq!(
+            claims_change(&mut (), claims_key("Mxxx"), Operation::Delete, b""),
+            Some(ClaimsChange::Delete {
+                subject: "Mxxx".to_string()
+            })
+        );
+        assert_eq!(
+            claims_change(&mut (), GENERATION_KEY.to_string(), Operation::Put, b"17"),
+            None
+        );
+    }
+
+    #[test]
+    fn first_marker_after_none_is_the_baseline() {
+        let marker = |generation: &str| Some(generation.to_string());
+        // A marker written to a bucket that had none
+        assert_eq!(generation_change(None, &marker("a")), None);
+        assert_eq!(generation_change(marker("a"), &marker("a")), None);
+        // The bucket is recreated without a marker, then one is written: reported once
+        assert_eq!(
+            generation_change(marker("a"), &None),
+            Some(GenerationChanged {
+                previous: marker("a"),
+                current: None,
+            })
+        );
+        assert_eq!(generation_change(None, &marker("b")), None);
+        // The bucket is recreated and a marker written between two observations
+        assert_eq!(
+            generation_change(marker("b"), &marker("c")),
+            Some(GenerationChanged {
+                previous: marker("b"),
+                current: marker("c"),
+            })
+        );
+    }
+
+    #[test]
+    fn validate_claims_checks_trusted_issuers() {
+        let trusted = vec!["Axxx".to_string()];
+        assert!(validate_claims(&claims(&[("sub", "Mxxx"), ("iss", "Axxx")]), &trusted).is_ok());
+        assert!(validate_claims(&claims(&[("sub", "Mxxx"), ("iss", "Ayyy")]), &trusted).is_err());
+        assert!(validate_claims(&claims(&[("sub", "Mxxx")]), &trusted).is_err());
+    }
+}
@@ -3,25 +3,93 @@
 //! This library provides a client API for consuming the wasmCloud control interface over a
 //! NATS connection. This library can be used by multiple types of tools, and is also used
 //! by the control interface capability provider and the wash CLI
+//!
+//! ## Supported targets
+//!
+//! The client runs on a tokio runtime: it spawns tasks for subscriptions and uses tokio's timers
+//! and channels throughout. It can't currently be built for `wasm32-unknown-unknown`, as the
+//! `async-nats` connection it wraps depends on tokio's networking and on TLS crates that don't
+//! build for that target, so gating the client's own use of tokio wouldn't be enough. Browser
+//! dashboards should talk to the lattice through a service built on this crate for now
 use std::fmt::Debug;
-use std::{collections::HashMap, time::Duration};
+use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    time::Duration,
+};
 
+use async_nats::jetstream::kv::Store;
 use cloudevents::event::Event;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use sub_stream::collect_timeout;
-use tokio::sync::mpsc::Receiver;
-use tracing::{debug, error, instrument, trace};
+use tokio::sync::mpsc::{Receiver, Sender};
+use tracing::{debug, error, instrument, trace, warn};
 
+pub mod annotations;
 mod broker;
+mod codec;
+#[cfg(any(test, feature = "testing"))]
+pub mod compat;
+mod deadline;
+mod error;
+mod event_filter;
+mod event_receiver;
+#[cfg(test)]
+mod fake_host;
+mod host_queue;
+mod host_status;
+mod interceptor;
+mod kv;
+mod link_filter;
+mod mirror;
+mod multi_lattice;
 mod otel;
+mod preflight;
+mod progress;
+mod purge;
+mod ranking;
+mod resolve;
+mod retry;
+mod scale;
 mod sub_stream;
 mod types;
+mod validate;
+mod wait;
 
+pub use codec::{Codec, CONTENT_TYPE_HEADER};
+#[allow(deprecated)]
+pub use error::BoxedResult;
+pub use error::{AckError, Error, ReadOnlyViolation, RequiresKv, Result};
+pub use event_filter::EventFilter;
+pub use event_receiver::{EventReceiver, OverflowPolicy};
+pub use interceptor::{CtlInterceptor, TracingInterceptor};
+pub use kv::BucketConfig;
+pub use link_filter::LinkFilter;
+pub use mirror::{MirrorConfig, MirrorFailure, MirrorHandle, MirrorStats};
+pub use multi_lattice::MultiLatticeClient;
+pub use preflight::{CheckResult, CheckStatus, PreflightCheck, PreflightOptions, PreflightReport};
+pub use ranking::{AuctionRanker, AuctionSubject, Bidder, LoadRanker};
+pub use retry::RetryPolicy;
 pub use types::*;
 
-use crate::otel::OtelHeaderInjector;
+use crate::deadline::Deadline;
+use crate::host_queue::HostQueues;
+use crate::interceptor::Interceptor;
+use crate::progress::Progress;
+
+/// The most links [`Client::advertise_links`] and [`Client::remove_links`] have in flight at once
+pub const LINK_BATCH_CONCURRENCY: usize = 16;
 
-type Result<T> = ::std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+/// The most inventory requests [`Client::get_all_inventories`] has in flight at once
+pub const INVENTORY_CONCURRENCY: usize = 10;
+
+/// How long [`Client::stop_host_and_monitor`] waits for a host to drain when no timeout is given,
+/// on top of the client's request timeout
+pub const HOST_DRAIN_WAIT: Duration = Duration::from_secs(30);
+
+/// The JetStream stream [`Client::durable_events_receiver`] consumes lattice events from unless
+/// [`ClientBuilder::event_stream`] names another, the name conventionally given to a stream that
+/// captures `wasmbus.evt.>`
+pub const DEFAULT_EVENT_STREAM: &str = "wasmbus_events";
 
 /// Lattice control interface client
 #[derive(Clone)]
@@ -31,6 +99,24 @@ pub struct Client {
     pub lattice_prefix: String,
     timeout: Duration,
     auction_timeout: Duration,
+    read_only: bool,
+    read_only_allows_auctions: bool,
+    js_domain: Option<String>,
+    trusted_issuers: Vec<String>,
+    kv_mode: KvMode,
+    retry: RetryPolicy,
+    codec: Codec,
+    error_on_nack: bool,
+    inbox_prefix: Option<String>,
+    event_queue_group: Option<String>,
+    event_stream: String,
+    interceptor: Interceptor,
+    /// Per-host command queues, present only if host commands are serialized
+    host_queues: Option<Arc<HostQueues>>,
+    /// The lattice metadata bucket, shared between clones of this client once it has been found
+    kvstore: Arc<tokio::sync::Mutex<Option<Store>>>,
+    /// The last observed bucket generation. The outer `None` means no observation has been made
+    generation: Arc<std::sync::Mutex<Option<Option<String>>>>,
 }
 
 impl Debug for Client {
@@ -40,6 +126,19 @@ impl Debug for Client {
             .field("lattice_prefix", &self.lattice_prefix)
             .field("timeout", &self.timeout)
             .field("auction_timeout", &self.auction_timeout)
+            .field("read_only", &self.read_only)
+            .field("read_only_allows_auctions", &self.read_only_allows_auctions)
+            .field("js_domain", &self.js_domain)
+            .field("trusted_issuers", &self.trusted_issuers)
+            .field("kv_mode", &self.kv_mode)
+            .field("retry", &self.retry)
+            .field("codec", &self.codec)
+            .field("error_on_nack", &self.error_on_nack)
+            .field("inbox_prefix", &self.inbox_prefix)
+            .field("event_queue_group", &self.event_queue_group)
+            .field("event_stream", &self.event_stream)
+            .field("interceptor", &self.interceptor.is_set())
+            .field("serialize_host_commands", &self.host_queues.is_some())
             .finish()
     }
 }
@@ -52,6 +151,30 @@ pub struct ClientBuilder {
     lattice_prefix: String,
     timeout: Duration,
     auction_timeout: Duration,
+    read_only: bool,
+    read_only_allows_auctions: bool,
+    js_domain: Option<String>,
+    trusted_issuers: Vec<String>,
+    serialize_host_commands: bool,
+    kv_mode: KvMode,
+    retry: RetryPolicy,
+    codec: Codec,
+    error_on_nack: bool,
+    inbox_prefix: Option<String>,
+    event_queue_group: Option<String>,
+    event_stream: String,
+    interceptor: Option<Arc<dyn CtlInterceptor>>,
+}
+
+/// How the client uses the lattice metadata bucket
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum KvMode {
+    /// Use the bucket if it exists, otherwise fall back to querying the hosts
+    Auto,
+    /// Fail instead of falling back to querying the hosts
+    Required,
+    /// Never look for the bucket
+    Disabled,
 }
 
 impl ClientBuilder {
@@ -64,11 +187,25 @@ impl ClientBuilder {
             lattice_prefix: "default".to_string(),
             timeout: Duration::from_secs(2),
             auction_timeout: Duration::from_secs(5),
+            read_only: false,
+            read_only_allows_auctions: true,
+            js_domain: None,
+            trusted_issuers: Vec::new(),
+            serialize_host_commands: false,
+            kv_mode: KvMode::Auto,
+            retry: RetryPolicy::default(),
+            codec: Codec::default(),
+            error_on_nack: false,
+            inbox_prefix: None,
+            event_queue_group: None,
+            event_stream: DEFAULT_EVENT_STREAM.to_string(),
+            interceptor: None,
         }
     }
 
     /// Sets the topic prefix for the NATS topic used for all control requests. Not to be confused
-    /// with lattice ID/prefix
+    /// with lattice ID/prefix. It may span several subject tokens, e.g. `wasmbus.ctl`, but must not
+    /// contain whitespace or NATS wildcards
     pub fn topic_prefix(self, prefix: impl Into<String>) -> ClientBuilder {
         ClientBuilder {
             topic_prefix: Some(prefix.into()),
@@ -76,8 +213,42 @@ impl ClientBuilder {
         }
     }
 
+    /// Sets the prefix of the reply inboxes the client subscribes to when gathering several
+    /// responses, as it does for auctions and [`Client::get_hosts`], for NATS accounts that only
+    /// allow subscribing to particular inboxes. Like a topic prefix, it may span several subject
+    /// tokens. If not set, inboxes are created by the NATS connection, under `_INBOX` unless the
+    /// connection was given a custom inbox prefix. Requests that expect a single reply always use
+    /// the connection's inboxes, so set the same prefix with `custom_inbox_prefix` when connecting
+    pub fn inbox_prefix(self, prefix: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            inbox_prefix: Some(prefix.into()),
+            ..self
+        }
+    }
+
+    /// Subscribes to lattice events as a member of the named queue group, so that replicas of a
+    /// service receiving events with [`Client::events_receiver`], its filtered and typed variants,
+    /// share them out between themselves instead of each receiving every event. Other operations
+    /// that watch events, such as waiting for a host, always see every event. If not set, every
+    /// receiver gets every event
+    pub fn event_queue_group(self, group: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            event_queue_group: Some(group.into()),
+            ..self
+        }
+    }
+
+    /// Sets the name of the JetStream stream that [`Client::durable_events_receiver`] consumes
+    /// lattice events from. Defaults to [`DEFAULT_EVENT_STREAM`]
+    pub fn event_stream(self, stream_name: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            event_stream: stream_name.into(),
+            ..self
+        }
+    }
+
     /// The lattice ID/prefix used for this client. If this function is not invoked, the prefix will
-    /// be set to `default`
+    /// be set to `default`. It must be a single subject token, without whitespace, `.`, `*` or `>`
     pub fn lattice_prefix(self, prefix: impl Into<String>) -> ClientBuilder {
         ClientBuilder {
             lattice_prefix: prefix.into(),
@@ -107,14 +278,180 @@ impl ClientBuilder {
         }
     }
 
-    /// Constructs the client with the given configuration from the builder
+    /// Puts the client in observation-only mode. Every operation that would mutate the lattice
+    /// (starting, stopping, scaling and updating workloads, link changes, registry credentials)
+    /// fails with a [`ReadOnlyViolation`] before any message is published. Queries and event
+    /// subscriptions are unaffected. Defaults to `false`
+    pub fn read_only(self, read_only: bool) -> ClientBuilder {
+        ClientBuilder { read_only, ..self }
+    }
+
+    /// Whether actor and provider auctions are still permitted when the client is in read-only
+    /// mode. Auctions don't change anything on the hosts, so they are treated as queries unless
+    /// this is set to `false`. Defaults to `true`
+    pub fn read_only_allows_auctions(self, allowed: bool) -> ClientBuilder {
+        ClientBuilder {
+            read_only_allows_auctions: allowed,
+            ..self
+        }
+    }
+
+    /// The JetStream domain to use when looking up the lattice metadata bucket. If not set, the
+    /// default JetStream domain of the connection is used
+    pub fn js_domain(self, domain: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            js_domain: Some(domain.into()),
+            ..self
+        }
+    }
+
+    /// The issuer public keys accepted when writing claims with [`Client::put_claims`]. If not set,
+    /// claims from any issuer can be written
+    pub fn trusted_issuers(
+        self,
+        issuers: impl IntoIterator<Item = impl Into<String>>,
+    ) -> ClientBuilder {
+        ClientBuilder {
+            trusted_issuers: issuers.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Sends commands that target the same host one at a time, each waiting for its
+    /// acknowledgement before the next is published, so that the host processes them in the order
+    /// they were issued. Commands to different hosts are still sent concurrently. Time spent
+    /// waiting behind earlier commands does not count towards a command's timeout. Queue depths
+    /// can be read with [`Client::host_queue_depths`], or exported as they change through
+    /// [`CtlInterceptor::on_host_queue_depth`]. Defaults to `false`
+    pub fn serialize_host_commands(self, serialize: bool) -> ClientBuilder {
+        ClientBuilder {
+            serialize_host_commands: serialize,
+            ..self
+        }
+    }
+
+    /// Sets how requests that time out are retried. See [`RetryPolicy`] for which requests are
+    /// retried. If not set, nothing is retried
+    pub fn retry_policy(self, retry: RetryPolicy) -> ClientBuilder {
+        ClientBuilder { retry, ..self }
+    }
+
+    /// Retries queries that time out up to `retries` times, keeping the rest of the current
+    /// [`RetryPolicy`]. Auctions are retried too unless the policy turns that off with
+    /// [`RetryPolicy::retry_auctions`], and commands only if it allows it with
+    /// [`RetryPolicy::retry_commands`]
+    pub fn retries(self, retries: u32) -> ClientBuilder {
+        ClientBuilder {
+            retry: self.retry.clone().retries(retries),
+            ..self
+        }
+    }
+
+    /// Sets the delay before each retry, keeping the rest of the current [`RetryPolicy`]
+    pub fn retry_backoff(self, backoff: Duration) -> ClientBuilder {
+        ClientBuilder {
+            retry: self.retry.clone().backoff(backoff),
+            ..self
+        }
+    }
+
+    /// Sets how request payloads are encoded. Replies are decoded according to their content type
+    /// whatever this is set to. Defaults to [`Codec::Json`]
+    pub fn codec(self, codec: Codec) -> ClientBuilder {
+        ClientBuilder { codec, ..self }
+    }
+
+    /// Makes every command that a host or the lattice refuses fail with [`Error::NotAccepted`],
+    /// carrying the host's reason and the command that was sent, instead of returning a
+    /// [`CtlOperationAck`] with `accepted` set to false that is easy to mistake for success.
+    /// Batches still report each command's acknowledgement in their [`BatchReport`]. Defaults to
+    /// `false`
+    pub fn error_on_nack(self, error_on_nack: bool) -> ClientBuilder {
+        ClientBuilder {
+            error_on_nack,
+            ..self
+        }
+    }
+
+    /// Calls `interceptor` with the subject and raw payload of every request the client makes and
+    /// every response or error it gets back, e.g. to keep an audit log of control commands. See
+    /// [`TracingInterceptor`] for an interceptor that logs everything
+    pub fn with_interceptor(self, interceptor: Arc<dyn CtlInterceptor>) -> ClientBuilder {
+        ClientBuilder {
+            interceptor: Some(interceptor),
+            ..self
+        }
+    }
+
+    /// Requires the lattice metadata bucket to exist. [`ClientBuilder::try_build`] fails if it
+    /// can't be found, and operations that would otherwise fall back to querying the hosts fail
+    /// with [`RequiresKv`] instead. This catches a wrong lattice prefix or JetStream domain up
+    /// front rather than silently running in legacy topic-only mode. Defaults to `false`
+    pub fn require_kv_store(self, required: bool) -> ClientBuilder {
+        ClientBuilder {
+            kv_mode: if required {
+                KvMode::Required
+            } else {
+                KvMode::Auto
+            },
+            ..self
+        }
+    }
+
+    /// Never uses the lattice metadata bucket, even if it exists, so that every query goes to the
+    /// hosts as in legacy topic-only mode. Operations that only work with the bucket fail with
+    /// [`RequiresKv`]
+    pub fn disable_kv_store(self) -> ClientBuilder {
+        ClientBuilder {
+            kv_mode: KvMode::Disabled,
+            ..self
+        }
+    }
+
+    /// Constructs the client like [`ClientBuilder::build`], but fails with
+    /// [`Error::InvalidArgument`] if the lattice, topic or inbox prefix can't be used in NATS
+    /// subjects, or the event queue group can't be used as a queue group name.
+    /// The metadata bucket is then looked up, unless it is disabled. If the bucket is required with
+    /// [`ClientBuilder::require_kv_store`] and can't be found, this fails with [`RequiresKv`],
+    /// naming the bucket and the JetStream domain that was searched
+    pub async fn try_build(self) -> Result<Client> {
+        let client = self.build();
+        client.validate_prefixes()?;
+        if let Some(group) = &client.event_queue_group {
+            validate::queue_group(group)?;
+        }
+        client.discover_kv_store("try_build").await?;
+        Ok(client)
+    }
+
+    /// Constructs the client with the given configuration from the builder. The prefixes aren't
+    /// checked until the first request, which fails if either is invalid; use
+    /// [`ClientBuilder::try_build`] to be told up front
     pub fn build(self) -> Client {
+        let interceptor = Interceptor::new(self.interceptor);
         Client {
             nc: self.nc,
             topic_prefix: self.topic_prefix,
             lattice_prefix: self.lattice_prefix,
             timeout: self.timeout,
             auction_timeout: self.auction_timeout,
+            read_only: self.read_only,
+            read_only_allows_auctions: self.read_only_allows_auctions,
+            js_domain: self.js_domain,
+            trusted_issuers: self.trusted_issuers,
+            kv_mode: self.kv_mode,
+            retry: self.retry,
+            codec: self.codec,
+            error_on_nack: self.error_on_nack,
+            inbox_prefix: self.inbox_prefix,
+            event_queue_group: self.event_queue_group,
+            event_stream: self.event_stream,
+            host_queues: self
+                .serialize_host_commands
+                .then(|| Arc::new(HostQueues::new(interceptor.clone()))),
+            interceptor,
+            kvstore: Arc::default(),
+            generation: Arc::default(),
         }
     }
 }
@@ -126,6 +463,183 @@ impl Client {
         ClientBuilder::new(nc).build()
     }
 
+    /// Returns true if this client was built in observation-only mode
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Returns true if the client reads and writes the lattice metadata bucket, or false if it is
+    /// operating in legacy topic-only mode, either because the bucket was disabled with
+    /// [`ClientBuilder::disable_kv_store`] or because it doesn't exist
+    pub async fn uses_kv_store(&self) -> bool {
+        self.kv_store().await.is_some()
+    }
+
+    /// Returns a copy of this client that uses `timeout` for its requests to hosts, e.g.
+    /// `client.with_timeout(Duration::from_secs(10)).stop_host(..)`. The copy shares this client's
+    /// connection, metadata bucket and host command queues, so it is cheap to create per call
+    pub fn with_timeout(&self, timeout: Duration) -> Client {
+        Client {
+            timeout,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of this client that waits `timeout` for auction and host query responses.
+    /// Like [`Client::with_timeout`], the copy shares everything else with this client
+    pub fn with_auction_timeout(&self, timeout: Duration) -> Client {
+        Client {
+            auction_timeout: timeout,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a client for another lattice on the same NATS connection, with this client's
+    /// settings and JetStream domain. Unlike the copies made by [`Client::with_timeout`], the new
+    /// client has its own metadata bucket and host command queues, and its bucket is looked up
+    /// before it is returned. Fails like [`ClientBuilder::try_build`] if the prefix is invalid or
+    /// a required bucket can't be found
+    pub async fn for_lattice(&self, lattice_prefix: &str) -> Result<Client> {
+        self.for_lattice_in_domain(lattice_prefix, self.js_domain.clone())
+            .await
+    }
+
+    /// Like [`Client::for_lattice`], for a lattice whose metadata bucket lives in a different
+    /// JetStream domain. `None` means the default domain
+    pub async fn for_lattice_in_domain(
+        &self,
+        lattice_prefix: &str,
+        js_domain: Option<String>,
+    ) -> Result<Client> {
+        let client = Client {
+            lattice_prefix: lattice_prefix.to_string(),
+            js_domain,
+            host_queues: self
+                .host_queues
+                .as_ref()
+                .map(|_| Arc::new(HostQueues::new(self.interceptor.clone()))),
+            kvstore: Arc::default(),
+            generation: Arc::default(),
+            ..self.clone()
+        };
+        client.validate_prefixes()?;
+        client.discover_kv_store("for_lattice").await?;
+        Ok(client)
+    }
+
+    /// Returns the NATS connection this client sends its requests on
+    pub fn nats_client(&self) -> &async_nats::Client {
+        &self.nc
+    }
+
+    /// Flushes the NATS connection, returning once the server has received everything published
+    /// so far. Fails if the connection is down
+    pub async fn flush(&self) -> Result<()> {
+        self.nc.flush().await.map_err(Error::nats)
+    }
+
+    /// Returns true if the NATS connection is currently established. This only reflects the
+    /// connection to the NATS server; use [`Client::ping_lattice`] to check that hosts are reachable
+    pub fn is_connected(&self) -> bool {
+        self.nc.connection_state() == async_nats::connection::State::Connected
+    }
+
+    /// Sends a hosts query and returns whether any host replied within `timeout`. This is a cheap
+    /// way for tools to report "cannot reach lattice" up front, rather than timing out on their
+    /// first real command
+    #[instrument(level = "debug", skip_all)]
+    pub async fn ping_lattice(&self, timeout: Duration) -> Result<bool> {
+        let subject = broker::queries::hosts(&self.topic_prefix, &self.lattice_prefix);
+        debug!("ping_lattice:request {}", &subject);
+        match self.request_once(subject, Vec::new(), timeout).await {
+            Ok(_) => Ok(true),
+            Err(Error::Timeout { .. } | Error::NoResponders { .. }) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns an error if the client is read-only, otherwise does nothing. Must be called by
+    /// every mutating operation before anything is published
+    fn ensure_writable(&self, operation: &'static str) -> Result<()> {
+        if self.read_only {
+            Err(ReadOnlyViolation { operation }.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the lattice metadata bucket, looking it up on first use. If the bucket doesn't exist
+    /// this returns `None` and the lookup is retried on the next call. Always `None` if the bucket
+    /// is disabled
+    pub(crate) async fn kv_store(&self) -> Option<Store> {
+        if self.kv_mode == KvMode::Disabled {
+            return None;
+        }
+        let mut cached = self.kvstore.lock().await;
+        if cached.is_none() {
+            *cached = kv::get_kv_store(
+                self.nc.clone(),
+                &self.lattice_prefix,
+                self.js_domain.clone(),
+            )
+            .await;
+        }
+        cached.clone()
+    }
+
+    /// Looks up the lattice metadata bucket ahead of the first operation that needs it, failing if
+    /// the bucket is required and can't be found
+    async fn discover_kv_store(&self, operation: &'static str) -> Result<()> {
+        if self.kv_store().await.is_none() && self.kv_mode == KvMode::Required {
+            return Err(self.requires_kv(operation).into());
+        }
+        Ok(())
+    }
+
+    /// Returns the lattice metadata bucket for an operation that can fall back to querying the
+    /// hosts, or `None` if it should. Fails instead if the bucket is required
+    async fn kv_store_or_fallback(&self, operation: &'static str) -> Result<Option<Store>> {
+        match self.kv_store().await {
+            None if self.kv_mode == KvMode::Required => Err(self.requires_kv(operation).into()),
+            store => Ok(store),
+        }
+    }
+
+    /// The error for an operation that can't be performed without the lattice metadata bucket
+    fn requires_kv(&self, operation: &'static str) -> RequiresKv {
+        RequiresKv {
+            operation,
+            bucket: kv::bucket_name(&self.lattice_prefix),
+            js_domain: self.js_domain.clone(),
+        }
+    }
+
+    /// Returns the lattice metadata bucket for an operation that writes to it, making sure the
+    /// bucket has a generation marker first. Fails if there is no bucket; the operation checks
+    /// that the client isn't read-only itself, before validating its arguments
+    async fn kv_store_for_write(&self, operation: &'static str) -> Result<Store> {
+        let store = self
+            .kv_store()
+            .await
+            .ok_or_else(|| self.requires_kv(operation))?;
+        kv::ensure_generation(&store).await?;
+        Ok(store)
+    }
+
+    /// Checks that the lattice, topic and inbox prefixes can be used in subjects, so that a bad one
+    /// fails with a clear error rather than a request that nothing can answer
+    fn validate_prefixes(&self) -> Result<()> {
+        validate::lattice_prefix(&self.lattice_prefix)?;
+        if let Some(prefix) = &self.inbox_prefix {
+            validate::inbox_prefix(prefix)?;
+        }
+        match &self.topic_prefix {
+            Some(prefix) => validate::topic_prefix(prefix),
+            None => Ok(()),
+        }
+    }
+
+    /// Sends a query, retrying it according to the client's retry policy if it times out
     #[instrument(level = "debug", skip_all)]
     pub(crate) async fn request_timeout(
         &self,
@@ -133,55 +647,336 @@ impl Client {
         payload: Vec<u8>,
         timeout: Duration,
     ) -> Result<async_nats::Message> {
-        match tokio::time::timeout(
+        self.retry
+            .run(&subject, true, || {
+                self.request_once(subject.clone(), payload.clone(), timeout)
+            })
+            .await
+    }
+
+    /// Sends a command, which is only retried if the retry policy allows commands to be
+    async fn command_request(
+        &self,
+        subject: String,
+        payload: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<async_nats::Message> {
+        self.retry
+            .run(&subject, false, || {
+                self.request_once(subject.clone(), payload.clone(), timeout)
+            })
+            .await
+    }
+
+    /// Makes a single request, without retrying
+    async fn request_once(
+        &self,
+        subject: String,
+        payload: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<async_nats::Message> {
+        self.validate_prefixes()?;
+        self.interceptor.request(&subject, &payload);
+        let start = std::time::Instant::now();
+        let result = match tokio::time::timeout(
             timeout,
-            self.nc.request_with_headers(
-                subject,
-                OtelHeaderInjector::default_with_span().into(),
-                payload.into(),
-            ),
+            self.nc
+                .request_with_headers(subject.clone(), self.codec.headers(), payload.into()),
         )
         .await
         {
-            Err(_) => Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out").into()),
+            Err(_) => Err(Error::Timeout {
+                subject: subject.clone(),
+                duration: timeout,
+            }),
             Ok(Ok(message)) => Ok(message),
-            Ok(Err(e)) => Err(e.into()),
+            Ok(Err(e)) => Err(Error::request(subject.clone(), timeout, e)),
+        };
+        match &result {
+            Ok(message) => self
+                .interceptor
+                .response(&subject, &message.payload, start.elapsed()),
+            Err(e) => self.interceptor.error(&subject, e),
+        }
+        result
+    }
+
+    /// Decodes the acknowledgement of a command. If the client errors on negative acknowledgements
+    /// and this is one, it is turned into an error describing the command, which is only
+    /// serialized here, when it's needed
+    fn acknowledgement<C: Serialize>(
+        &self,
+        msg: &async_nats::Message,
+        operation: &'static str,
+        host_id: Option<&str>,
+        command: Option<&C>,
+    ) -> Result<CtlOperationAck> {
+        let ack: CtlOperationAck = codec::decode(msg)?;
+        if ack.accepted || !self.error_on_nack {
+            return Ok(ack);
         }
+        Err(AckError {
+            error: ack.error,
+            operation: Some(operation),
+            host_id: host_id.map(str::to_string),
+            command: command.and_then(|command| serde_json::to_value(command).ok()),
+        }
+        .into())
+    }
+
+    /// Sends a command to a single host. If host commands are serialized, this first waits for every
+    /// earlier command to the same host to finish
+    async fn host_request(
+        &self,
+        host_id: &str,
+        subject: String,
+        payload: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<async_nats::Message> {
+        let _queued = match &self.host_queues {
+            Some(queues) => Some(queues.enter(host_id).await),
+            None => None,
+        };
+        self.command_request(subject, payload, timeout).await
+    }
+
+    /// Returns the number of commands to the given host that are waiting to be sent or awaiting
+    /// acknowledgement. Always zero unless the client serializes host commands
+    pub fn host_queue_depth(&self, host_id: &str) -> usize {
+        self.host_queues
+            .as_ref()
+            .map(|queues| queues.depth(host_id))
+            .unwrap_or_default()
+    }
+
+    /// Returns the queue depth of every host with commands waiting to be sent or awaiting
+    /// acknowledgement. Always empty unless the client serializes host commands
+    pub fn host_queue_depths(&self) -> HashMap<String, usize> {
+        self.host_queues
+            .as_ref()
+            .map(|queues| queues.depths())
+            .unwrap_or_default()
+    }
+
+    /// Runs a set of cheap checks against the NATS connection and the lattice (round trip time,
+    /// publish and subscribe permissions, metadata bucket visibility, and whether any host
+    /// responds), returning a pass, warning, or failure with a remediation hint for each. Intended
+    /// for tools to run at startup so that misconfiguration is reported clearly. Individual checks
+    /// can't fail the call; the returned report describes what went wrong
+    #[instrument(level = "debug", skip_all)]
+    pub async fn preflight(&self) -> Result<PreflightReport> {
+        self.preflight_with_options(&PreflightOptions::default())
+            .await
+    }
+
+    /// Same as [`preflight`](Client::preflight), skipping the checks excluded by `options`
+    #[instrument(level = "debug", skip_all)]
+    pub async fn preflight_with_options(
+        &self,
+        options: &PreflightOptions,
+    ) -> Result<PreflightReport> {
+        Ok(preflight::run(self, options).await)
     }
 
     /// Queries the lattice for all responsive hosts, waiting for the full period specified by
     /// _timeout_.
     #[instrument(level = "debug", skip_all)]
     pub async fn get_hosts(&self) -> Result<Vec<Host>> {
+        self.get_hosts_with_options(&AuctionOptions::default())
+            .await
+    }
+
+    /// Like [`Client::get_hosts`], but also returns the subject the query was published on and how
+    /// long the responses took to gather, which is normally the full auction timeout
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_hosts_detailed(&self) -> Result<CtlResponse<Vec<Host>>> {
+        self.query_hosts(&AuctionOptions::default()).await
+    }
+
+    /// Queries the lattice for responsive hosts, returning as soon as `count` hosts have responded.
+    /// If fewer hosts respond, this waits for the full auction timeout like [`Client::get_hosts`]
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_hosts_expecting(&self, count: usize) -> Result<Vec<Host>> {
+        self.get_hosts_with_options(&AuctionOptions::new().min_results(count))
+            .await
+    }
+
+    /// Queries the lattice for all responsive hosts, gathering responses as described by
+    /// `options`. Settings that are not provided fall back to the client's configuration
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_hosts_with_options(&self, options: &AuctionOptions) -> Result<Vec<Host>> {
+        self.query_hosts(options).await.map(CtlResponse::into_data)
+    }
+
+    async fn query_hosts(&self, options: &AuctionOptions) -> Result<CtlResponse<Vec<Host>>> {
+        let start = std::time::Instant::now();
         let subject = broker::queries::hosts(&self.topic_prefix, &self.lattice_prefix);
         debug!("get_hosts:publish {}", &subject);
-        self.publish_and_wait(subject, Vec::new()).await
+        let hosts = self
+            .publish_and_wait(subject.clone(), Vec::new(), options)
+            .await?;
+        Ok(CtlResponse::new(hosts, subject, start))
     }
 
     /// Retrieves the contents of a running host
     #[instrument(level = "debug", skip_all)]
     pub async fn get_host_inventory(&self, host_id: &str) -> Result<HostInventory> {
+        self.get_host_inventory_detailed(host_id)
+            .await
+            .map(CtlResponse::into_data)
+    }
+
+    /// Like [`Client::get_host_inventory`], but also returns the subject the query was sent on and
+    /// how long it took to be answered
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_host_inventory_detailed(
+        &self,
+        host_id: &str,
+    ) -> Result<CtlResponse<HostInventory>> {
+        validate::host_id(host_id)?;
+        let start = std::time::Instant::now();
         let subject =
             broker::queries::host_inventory(&self.topic_prefix, &self.lattice_prefix, host_id);
         debug!("get_host_inventory:request {}", &subject);
-        match self.request_timeout(subject, vec![], self.timeout).await {
-            Ok(msg) => Ok(json_deserialize(&msg.payload)?),
-            Err(e) => Err(format!("Did not receive host inventory from target host: {}", e).into()),
-        }
+        let msg = self
+            .request_timeout(subject.clone(), vec![], self.timeout)
+            .await?;
+        Ok(CtlResponse::new(codec::decode(&msg)?, subject, start))
     }
 
-    /// Retrieves the full set of all cached claims in the lattice.   
+    /// Retrieves the full set of all cached claims in the lattice. If the lattice metadata bucket
+    /// exists, the claims are read directly from it, otherwise the hosts are queried
     #[instrument(level = "debug", skip_all)]
     pub async fn get_claims(&self) -> Result<Vec<HashMap<String, String>>> {
+        if let Some(store) = self.kv_store_or_fallback("get_claims").await? {
+            debug!("get_claims:kv {}", kv::bucket_name(&self.lattice_prefix));
+            return kv::get_claims(&store).await;
+        }
         let subject = broker::queries::claims(&self.topic_prefix, &self.lattice_prefix);
         debug!("get_claims:request {}", &subject);
-        match self.request_timeout(subject, vec![], self.timeout).await {
-            Ok(msg) => {
-                let list: GetClaimsResponse = json_deserialize(&msg.payload)?;
-                Ok(list.claims)
-            }
-            Err(e) => Err(format!("Did not receive claims from lattice: {}", e).into()),
+        let msg = self.request_timeout(subject, vec![], self.timeout).await?;
+        let list: GetClaimsResponse = codec::decode(&msg)?;
+        Ok(list.claims)
+    }
+
+    /// Retrieves the claims for a single actor or provider by its public key, or `None` if the
+    /// lattice has no claims for it. If the lattice metadata bucket exists only that entry is
+    /// read, otherwise every claim is queried from the hosts and the matching one returned
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_claims_for(
+        &self,
+        public_key: &str,
+    ) -> Result<Option<HashMap<String, String>>> {
+        if let Some(store) = self.kv_store_or_fallback("get_claims_for").await? {
+            debug!("get_claims_for:kv {}", public_key);
+            return kv::get_claims_for(&store, public_key).await;
         }
+        Ok(self
+            .get_claims()
+            .await?
+            .into_iter()
+            .find(|claims| claims.get("sub").map(String::as_str) == Some(public_key)))
+    }
+
+    /// Returns the receiver end of a channel that is sent every change to the claims in the
+    /// lattice metadata bucket from now on, for keeping a live claims cache. Fails with
+    /// [`Error::RequiresKv`] if the bucket doesn't exist. Dropping the receiver stops the watch
+    #[instrument(level = "debug", skip_all)]
+    pub async fn claims_changes_receiver(&self) -> Result<Receiver<ClaimsChange>> {
+        let store = self
+            .kv_store()
+            .await
+            .ok_or_else(|| self.requires_kv("claims_changes_receiver"))?;
+        kv::watch_claims(store).await
+    }
+
+    /// Writes a set of claims into the lattice metadata bucket, keyed by the claims' subject, so
+    /// that they are known to the lattice before the entity they describe is first started. The
+    /// claims must contain non-empty `sub` and `iss` fields. If they also carry the signed token
+    /// under `jwt`, its subject and issuer must match the map, and if the client was configured
+    /// with [`ClientBuilder::trusted_issuers`] the issuer must be one of them. Requires the
+    /// metadata bucket; fails with [`RequiresKv`] otherwise
+    #[instrument(level = "debug", skip_all)]
+    pub async fn put_claims(&self, claims: HashMap<String, String>) -> Result<()> {
+        self.ensure_writable("put_claims")?;
+        let subject = kv::validate_claims(&claims, &self.trusted_issuers)?;
+        validate::claims_subject(&subject)?;
+        let store = self.kv_store_for_write("put_claims").await?;
+        debug!("put_claims:kv {}", subject);
+        kv::put_claims(&store, &claims).await
+    }
+
+    /// Removes the claims for the given subject (an actor or provider public key) from the lattice
+    /// metadata bucket. This is idempotent. Fails with [`Error::InvalidArgument`] if the subject is
+    /// empty or can't be a single subject token. Requires the metadata bucket; fails with
+    /// [`RequiresKv`] otherwise
+    #[instrument(level = "debug", skip_all)]
+    pub async fn delete_claims(&self, subject: &str) -> Result<()> {
+        self.ensure_writable("delete_claims")?;
+        validate::claims_subject(subject)?;
+        let store = self.kv_store_for_write("delete_claims").await?;
+        debug!("delete_claims:kv {}", subject);
+        kv::delete_claims(&store, subject).await
+    }
+
+    /// Retrieves the named config from the lattice metadata bucket, or `None` if there is no config
+    /// with that name. Config is only stored in the bucket; fails with [`RequiresKv`] if it
+    /// doesn't exist, or with [`Error::InvalidArgument`] if the name is invalid
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_config(&self, name: &str) -> Result<Option<ConfigValues>> {
+        validate::config_name(name)?;
+        let store = self
+            .kv_store()
+            .await
+            .ok_or_else(|| self.requires_kv("get_config"))?;
+        debug!("get_config:kv {}", name);
+        kv::get_config(&store, name).await
+    }
+
+    /// Stores named config in the lattice metadata bucket, replacing any existing values, so that
+    /// providers can read it. Since the name becomes part of the key, it must not be empty and may
+    /// only contain ASCII letters, digits, `-`, `/`, `_` and `=`. Requires the metadata bucket;
+    /// fails with [`RequiresKv`] otherwise
+    #[instrument(level = "debug", skip_all)]
+    pub async fn put_config(&self, name: &str, values: ConfigValues) -> Result<()> {
+        self.ensure_writable("put_config")?;
+        validate::config_name(name)?;
+        let store = self.kv_store_for_write("put_config").await?;
+        debug!("put_config:kv {}", name);
+        kv::put_config(&store, name, &values).await
+    }
+
+    /// Removes named config from the lattice metadata bucket. This is idempotent. Requires the
+    /// metadata bucket; fails with [`RequiresKv`] otherwise
+    #[instrument(level = "debug", skip_all)]
+    pub async fn delete_config(&self, name: &str) -> Result<()> {
+        self.ensure_writable("delete_config")?;
+        validate::config_name(name)?;
+        let store = self.kv_store_for_write("delete_config").await?;
+        debug!("delete_config:kv {}", name);
+        kv::delete_config(&store, name).await
+    }
+
+    /// Lists the names of all config stored in the lattice metadata bucket. Requires the metadata
+    /// bucket; fails with [`RequiresKv`] otherwise
+    #[instrument(level = "debug", skip_all)]
+    pub async fn list_config_names(&self) -> Result<Vec<String>> {
+        let store = self
+            .kv_store()
+            .await
+            .ok_or_else(|| self.requires_kv("list_config_names"))?;
+        kv::list_config_names(&store).await
+    }
+
+    /// Retrieves every named config in the lattice metadata bucket with its values, sorted by
+    /// name. Requires the metadata bucket; fails with [`RequiresKv`] otherwise
+    #[instrument(level = "debug", skip_all)]
+    pub async fn list_configs(&self) -> Result<Vec<NamedConfig>> {
+        let store = self
+            .kv_store()
+            .await
+            .ok_or_else(|| self.requires_kv("list_configs"))?;
+        kv::list_configs(&store).await
     }
 
     /// Performs an actor auction within the lattice, publishing a set of constraints and the
@@ -193,15 +988,51 @@ impl Client {
     pub async fn perform_actor_auction(
         &self,
         actor_ref: &str,
-        constraints: HashMap<String, String>,
+        constraints: impl Into<Constraints>,
+    ) -> Result<Vec<ActorAuctionAck>> {
+        self.perform_actor_auction_with_options(actor_ref, constraints, &AuctionOptions::default())
+            .await
+    }
+
+    /// Performs an actor auction within the lattice, gathering bids as described by `options`.
+    /// Settings that are not provided fall back to the client's configuration
+    #[instrument(level = "debug", skip_all)]
+    pub async fn perform_actor_auction_with_options(
+        &self,
+        actor_ref: &str,
+        constraints: impl Into<Constraints>,
+        options: &AuctionOptions,
     ) -> Result<Vec<ActorAuctionAck>> {
+        let bids = self
+            .perform_actor_auction_stream(actor_ref, constraints, options)
+            .await?;
+        Ok(collect_receiver(bids).await)
+    }
+
+    /// Performs an actor auction within the lattice, sending each bid to the returned receiver as
+    /// soon as it arrives. Bids are gathered as described by `options`; the caller can also stop
+    /// early by dropping the receiver once it has the bids it needs, which unsubscribes from the
+    /// auction's reply inbox
+    #[instrument(level = "debug", skip_all)]
+    pub async fn perform_actor_auction_stream(
+        &self,
+        actor_ref: &str,
+        constraints: impl Into<Constraints>,
+        options: &AuctionOptions,
+    ) -> Result<Receiver<ActorAuctionAck>> {
+        if !self.read_only_allows_auctions {
+            self.ensure_writable("perform_actor_auction")?;
+        }
+        let constraints = constraints.into();
+        constraints.validate()?;
         let subject = broker::actor_auction_subject(&self.topic_prefix, &self.lattice_prefix);
-        let bytes = json_serialize(ActorAuctionRequest {
+        let bytes = self.codec.serialize(ActorAuctionRequest {
             actor_ref: actor_ref.to_string(),
-            constraints,
+            constraints: constraints.into_map(),
         })?;
         debug!("actor_auction:publish {}", &subject);
-        self.publish_and_wait(subject, bytes).await
+        self.publish_and_stream(subject, bytes, options, self.retry.auction_retries())
+            .await
     }
 
     /// Performs a provider auction within the lattice, publishing a set of constraints and the
@@ -214,25 +1045,207 @@ impl Client {
         &self,
         provider_ref: &str,
         link_name: &str,
-        constraints: HashMap<String, String>,
+        constraints: impl Into<Constraints>,
     ) -> Result<Vec<ProviderAuctionAck>> {
-        let subject = broker::provider_auction_subject(&self.topic_prefix, &self.lattice_prefix);
-        let bytes = json_serialize(ProviderAuctionRequest {
-            provider_ref: provider_ref.to_string(),
-            link_name: link_name.to_string(),
+        self.perform_provider_auction_with_options(
+            provider_ref,
+            link_name,
             constraints,
-        })?;
-        debug!("provider_auction:publish {}", &subject);
-        self.publish_and_wait(subject, bytes).await
+            &AuctionOptions::default(),
+        )
+        .await
     }
 
-    /// Sends a request to the given host to start a given actor by its OCI reference. This returns
-    /// an acknowledgement of _receipt_ of the command, not a confirmation that the actor started.
-    /// An acknowledgement will either indicate some form of validation failure, or, if no failure
-    /// occurs, the receipt of the command. To avoid blocking consumers, wasmCloud hosts will
+    /// Performs a provider auction within the lattice, gathering bids as described by `options`.
+    /// Settings that are not provided fall back to the client's configuration
+    #[instrument(level = "debug", skip_all)]
+    pub async fn perform_provider_auction_with_options(
+        &self,
+        provider_ref: &str,
+        link_name: &str,
+        constraints: impl Into<Constraints>,
+        options: &AuctionOptions,
+    ) -> Result<Vec<ProviderAuctionAck>> {
+        let bids = self
+            .perform_provider_auction_stream(provider_ref, link_name, constraints, options)
+            .await?;
+        Ok(collect_receiver(bids).await)
+    }
+
+    /// Performs a provider auction within the lattice, sending each bid to the returned receiver
+    /// as soon as it arrives. See [`Client::perform_actor_auction_stream`] for how gathering stops
+    #[instrument(level = "debug", skip_all)]
+    pub async fn perform_provider_auction_stream(
+        &self,
+        provider_ref: &str,
+        link_name: &str,
+        constraints: impl Into<Constraints>,
+        options: &AuctionOptions,
+    ) -> Result<Receiver<ProviderAuctionAck>> {
+        if !self.read_only_allows_auctions {
+            self.ensure_writable("perform_provider_auction")?;
+        }
+        let constraints = constraints.into();
+        constraints.validate()?;
+        let subject = broker::provider_auction_subject(&self.topic_prefix, &self.lattice_prefix);
+        let bytes = self.codec.serialize(ProviderAuctionRequest {
+            provider_ref: provider_ref.to_string(),
+            link_name: link_name.to_string(),
+            constraints: constraints.into_map(),
+        })?;
+        debug!("provider_auction:publish {}", &subject);
+        self.publish_and_stream(subject, bytes, options, self.retry.auction_retries())
+            .await
+    }
+
+    /// Holds an actor auction and returns the ID of the bidder [`LoadRanker`] ranks best, or
+    /// `None` if no host bid
+    #[instrument(level = "debug", skip_all)]
+    pub async fn auction_and_pick_actor_host(
+        &self,
+        actor_ref: &str,
+        constraints: impl Into<Constraints>,
+    ) -> Result<Option<String>> {
+        self.auction_and_pick_actor_host_with_ranker(actor_ref, constraints, &LoadRanker::new())
+            .await
+    }
+
+    /// Like [`Client::auction_and_pick_actor_host`], choosing with the given ranker
+    #[instrument(level = "debug", skip_all)]
+    pub async fn auction_and_pick_actor_host_with_ranker(
+        &self,
+        actor_ref: &str,
+        constraints: impl Into<Constraints>,
+        ranker: &dyn AuctionRanker,
+    ) -> Result<Option<String>> {
+        let bids = self.perform_actor_auction(actor_ref, constraints).await?;
+        let ranked = self.rank_actor_bids(bids, ranker).await;
+        Ok(ranked.into_iter().next().map(|bid| bid.host_id))
+    }
+
+    /// Holds a provider auction and returns the ID of the bidder [`LoadRanker`] ranks best, or
+    /// `None` if no host bid
+    #[instrument(level = "debug", skip_all)]
+    pub async fn auction_and_pick_provider_host(
+        &self,
+        provider_ref: &str,
+        link_name: &str,
+        constraints: impl Into<Constraints>,
+    ) -> Result<Option<String>> {
+        self.auction_and_pick_provider_host_with_ranker(
+            provider_ref,
+            link_name,
+            constraints,
+            &LoadRanker::new(),
+        )
+        .await
+    }
+
+    /// Like [`Client::auction_and_pick_provider_host`], choosing with the given ranker
+    #[instrument(level = "debug", skip_all)]
+    pub async fn auction_and_pick_provider_host_with_ranker(
+        &self,
+        provider_ref: &str,
+        link_name: &str,
+        constraints: impl Into<Constraints>,
+        ranker: &dyn AuctionRanker,
+    ) -> Result<Option<String>> {
+        let bids = self
+            .perform_provider_auction(provider_ref, link_name, constraints)
+            .await?;
+        let ranked = self.rank_provider_bids(bids, ranker).await;
+        Ok(ranked.into_iter().next().map(|bid| bid.host_id))
+    }
+
+    /// Orders the bids from an actor auction best first according to `ranker`, which is given
+    /// each bidder's answer to the hosts query and its inventory. Bids from hosts the ranker
+    /// leaves out are dropped. A host that can't be queried is still ranked, with what couldn't be
+    /// retrieved missing, so ranking itself never fails
+    #[instrument(level = "debug", skip_all)]
+    pub async fn rank_actor_bids(
+        &self,
+        bids: Vec<ActorAuctionAck>,
+        ranker: &dyn AuctionRanker,
+    ) -> Vec<ActorAuctionAck> {
+        let Some(first) = bids.first() else {
+            return bids;
+        };
+        let subject = AuctionSubject::Actor {
+            actor_ref: &first.actor_ref,
+        };
+        let bidders = self.bidders(bids.iter().map(|bid| &bid.host_id)).await;
+        let ranked = ranker.rank(&subject, bidders);
+        ranking::order_bids(bids, |bid| &bid.host_id, &ranked)
+    }
+
+    /// Like [`Client::rank_actor_bids`], for the bids from a provider auction
+    #[instrument(level = "debug", skip_all)]
+    pub async fn rank_provider_bids(
+        &self,
+        bids: Vec<ProviderAuctionAck>,
+        ranker: &dyn AuctionRanker,
+    ) -> Vec<ProviderAuctionAck> {
+        let Some(first) = bids.first() else {
+            return bids;
+        };
+        let subject = AuctionSubject::Provider {
+            provider_ref: &first.provider_ref,
+            link_name: &first.link_name,
+        };
+        let bidders = self.bidders(bids.iter().map(|bid| &bid.host_id)).await;
+        let ranked = ranker.rank(&subject, bidders);
+        ranking::order_bids(bids, |bid| &bid.host_id, &ranked)
+    }
+
+    /// Gathers what a ranker needs to know about each of the given hosts, with up to
+    /// [`INVENTORY_CONCURRENCY`] inventory requests in flight at once. Anything that can't be
+    /// retrieved is logged and left out
+    async fn bidders<'a>(&self, host_ids: impl Iterator<Item = &'a String>) -> Vec<Bidder> {
+        use futures::StreamExt as _;
+        let host_ids: BTreeSet<&String> = host_ids.collect();
+        let mut hosts: HashMap<String, Host> = match self.get_hosts().await {
+            Ok(hosts) => hosts
+                .into_iter()
+                .map(|host| (host.id.clone(), host))
+                .collect(),
+            Err(error) => {
+                warn!(%error, "ranking bidders without querying hosts");
+                HashMap::new()
+            }
+        };
+        futures::stream::iter(host_ids)
+            .map(|host_id| {
+                let host = hosts.remove(host_id);
+                async move {
+                    let inventory = match self.get_host_inventory(host_id).await {
+                        Ok(inventory) => Some(inventory),
+                        Err(error) => {
+                            warn!(%error, %host_id, "ranking bidder without its inventory");
+                            None
+                        }
+                    };
+                    Bidder {
+                        host_id: host_id.clone(),
+                        host,
+                        inventory,
+                    }
+                }
+            })
+            .buffered(INVENTORY_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    /// Sends a request to the given host to start a given actor by its OCI reference. This returns
+    /// an acknowledgement of _receipt_ of the command, not a confirmation that the actor started.
+    /// An acknowledgement will either indicate some form of validation failure, or, if no failure
+    /// occurs, the receipt of the command. To avoid blocking consumers, wasmCloud hosts will
     /// acknowledge the start actor command prior to fetching the actor's OCI bytes. If a client
     /// needs deterministic results as to whether the actor completed its startup process, the
-    /// client will have to monitor the appropriate event in the control event stream
+    /// client will have to monitor the appropriate event in the control event stream.
+    ///
+    /// As this is deprecated, it has no `_detailed` variant. It sends a scale command, so use
+    /// [`Client::scale_actor_detailed`] to time it
     #[instrument(level = "debug", skip_all)]
     #[deprecated(since = "0.30.0", note = "please use `scale_actor` instead")]
     pub async fn start_actor(
@@ -242,11 +1255,29 @@ impl Client {
         count: u16,
         annotations: Option<HashMap<String, String>>,
     ) -> Result<CtlOperationAck> {
+        self.ensure_writable("start_actor")?;
         // It makes no logical sense to start 0 actors, so we represent that as an unbounded max instead.
         let max = if count == 0 { None } else { Some(count) };
         self.scale_actor(host_id, actor_ref, max, annotations).await
     }
 
+    /// Like [`Client::start_actor`], but the command carries the given [`CommandId`] instead of a
+    /// new one, so that a caller retrying the start itself can tell the host it is the same start
+    #[instrument(level = "debug", skip_all)]
+    pub async fn start_actor_with_id(
+        &self,
+        host_id: &str,
+        actor_ref: &str,
+        count: u16,
+        annotations: Option<HashMap<String, String>>,
+        command_id: CommandId,
+    ) -> Result<CtlOperationAck> {
+        self.ensure_writable("start_actor_with_id")?;
+        let max = if count == 0 { None } else { Some(count) };
+        self.scale_actor_with_id(host_id, actor_ref, max, annotations, command_id)
+            .await
+    }
+
     /// Sends a request to the given host to scale a given actor. This returns an acknowledgement of
     /// _receipt_ of the command, not a confirmation that the actor scaled. An acknowledgement will
     /// either indicate some form of validation failure, or, if no failure occurs, the receipt of
@@ -269,19 +1300,319 @@ impl Client {
         max_concurrent: Option<u16>,
         annotations: Option<HashMap<String, String>>,
     ) -> Result<CtlOperationAck> {
+        self.scale_actor_detailed(host_id, actor_ref, max_concurrent, annotations)
+            .await
+            .map(CtlResponse::into_data)
+    }
+
+    /// Like [`Client::scale_actor`], but also returns the subject the command was sent on and how
+    /// long it took to be acknowledged
+    #[instrument(level = "debug", skip_all)]
+    pub async fn scale_actor_detailed(
+        &self,
+        host_id: &str,
+        actor_ref: &str,
+        max_concurrent: Option<u16>,
+        annotations: Option<HashMap<String, String>>,
+    ) -> Result<CtlResponse<CtlOperationAck>> {
+        self.send_scale_actor(
+            host_id,
+            actor_ref,
+            max_concurrent,
+            annotations,
+            CommandId::new(),
+        )
+        .await
+    }
+
+    /// Like [`Client::scale_actor`], but the command carries the given [`CommandId`] instead of a
+    /// new one. The client keeps a command's ID for its own retries, so this is only needed by
+    /// callers that retry the scale themselves and want the host to see it as the same command
+    #[instrument(level = "debug", skip_all)]
+    pub async fn scale_actor_with_id(
+        &self,
+        host_id: &str,
+        actor_ref: &str,
+        max_concurrent: Option<u16>,
+        annotations: Option<HashMap<String, String>>,
+        command_id: CommandId,
+    ) -> Result<CtlOperationAck> {
+        self.ensure_writable("scale_actor_with_id")?;
+        self.send_scale_actor(host_id, actor_ref, max_concurrent, annotations, command_id)
+            .await
+            .map(CtlResponse::into_data)
+    }
+
+    async fn send_scale_actor(
+        &self,
+        host_id: &str,
+        actor_ref: &str,
+        max_concurrent: Option<u16>,
+        annotations: Option<HashMap<String, String>>,
+        command_id: CommandId,
+    ) -> Result<CtlResponse<CtlOperationAck>> {
+        self.ensure_writable("scale_actor")?;
+        validate::host_id(host_id)?;
+        let start = std::time::Instant::now();
         let subject =
             broker::commands::scale_actor(&self.topic_prefix, &self.lattice_prefix, host_id);
         debug!("scale_actor:request {}", &subject);
-        let bytes = json_serialize(ScaleActorCommand {
+        let command = ScaleActorCommand {
             max_concurrent,
             actor_ref: actor_ref.to_string(),
             host_id: host_id.to_string(),
             annotations,
-        })?;
-        match self.request_timeout(subject, bytes, self.timeout).await {
-            Ok(msg) => Ok(json_deserialize(&msg.payload)?),
-            Err(e) => Err(format!("Did not receive scale actor acknowledgement: {}", e).into()),
+            command_id: Some(command_id),
+        };
+        let bytes = self.codec.serialize(&command)?;
+        let msg = self
+            .host_request(host_id, subject.clone(), bytes, self.timeout)
+            .await?;
+        Ok(CtlResponse::new(
+            self.acknowledgement(&msg, "scale_actor", Some(host_id), Some(&command))?,
+            subject,
+            start,
+        ))
+    }
+
+    /// Deploys an actor across `host_count` distinct hosts chosen by an actor auction, scaling it to
+    /// `max_concurrent` on each. If `progress` is provided, a [`ProgressEvent`] is sent for each
+    /// step as it happens. Returns the acknowledgement from each selected host, in host ID order
+    #[instrument(level = "debug", skip_all)]
+    pub async fn spread_actor(
+        &self,
+        actor_ref: &str,
+        constraints: impl Into<Constraints>,
+        host_count: usize,
+        max_concurrent: Option<u16>,
+        progress: Option<Sender<ProgressEvent>>,
+    ) -> Result<Vec<CtlOperationAck>> {
+        self.ensure_writable("spread_actor")?;
+        let progress = Progress::new(progress);
+        progress
+            .emit(ProgressEvent::AuctionStarted {
+                reference: actor_ref.to_string(),
+            })
+            .await;
+        let options = AuctionOptions::new().min_results(host_count);
+        let bids = match self
+            .perform_actor_auction_with_options(actor_ref, constraints, &options)
+            .await
+        {
+            Ok(bids) => bids,
+            Err(e) => {
+                progress
+                    .emit(ProgressEvent::PhaseFailed {
+                        phase: "auction".to_string(),
+                        host: None,
+                        error: e.to_string(),
+                    })
+                    .await;
+                return Err(e);
+            }
+        };
+        let mut hosts: Vec<String> = Vec::with_capacity(host_count);
+        for bid in bids {
+            if !hosts.contains(&bid.host_id) {
+                hosts.push(bid.host_id);
+            }
+        }
+        if hosts.len() < host_count {
+            let error = Error::NotEnoughBids {
+                reference: actor_ref.to_string(),
+                required: host_count,
+                received: hosts.len(),
+            };
+            progress
+                .emit(ProgressEvent::PhaseFailed {
+                    phase: "auction".to_string(),
+                    host: None,
+                    error: error.to_string(),
+                })
+                .await;
+            return Err(error);
+        }
+        hosts.truncate(host_count);
+        hosts.sort();
+        for host in &hosts {
+            progress
+                .emit(ProgressEvent::HostSelected { host: host.clone() })
+                .await;
+        }
+
+        let mut acks = Vec::with_capacity(hosts.len());
+        for host in &hosts {
+            progress.command_sent(host, "scale_actor").await;
+            match self
+                .scale_actor(host, actor_ref, max_concurrent, None)
+                .await
+            {
+                Ok(ack) => {
+                    let error = (!ack.accepted).then(|| ack.error.clone());
+                    progress.command_result(host, "scale_actor", error).await;
+                    acks.push(ack);
+                }
+                Err(e) => {
+                    progress
+                        .command_result(host, "scale_actor", Some(e.to_string()))
+                        .await;
+                    return Err(e);
+                }
+            }
+        }
+        Ok(acks)
+    }
+
+    /// Scales an actor to `desired_count` instances in total across the hosts that satisfy
+    /// `constraints`. Current counts are taken from the inventory of every host; instances are
+    /// added to the hosts that bid in an actor auction or already run the actor, emptiest first,
+    /// and removed from the fullest hosts first. Each host is then sent a `scale_actor` command
+    /// with its new count, or a `stop_actor` command if the count drops to zero.
+    ///
+    /// If `annotations` is given, only instances carrying all of them are counted and changed, and
+    /// new instances are started with them, so that instances managed by something else are left
+    /// alone. A host that rejects or doesn't acknowledge its command is recorded in the report
+    /// rather than stopping the others. Hosts whose inventory can't be retrieved are left alone
+    #[instrument(level = "debug", skip_all)]
+    pub async fn scale_actor_to(
+        &self,
+        actor_ref: &str,
+        actor_id: &str,
+        desired_count: u32,
+        constraints: impl Into<Constraints>,
+        annotations: Option<AnnotationMap>,
+    ) -> Result<ScaleReport> {
+        self.ensure_writable("scale_actor_to")?;
+        let constraints = constraints.into();
+        constraints.validate()?;
+        let mut report = ScaleReport {
+            desired_count,
+            ..Default::default()
+        };
+        let mut current = BTreeMap::new();
+        for (host, inventory) in self.get_all_inventories().await? {
+            match inventory {
+                Ok(inventory) if constraints.matches(&inventory.labels) => {
+                    let count =
+                        scale::instance_count(&inventory.actors, actor_id, annotations.as_ref());
+                    if count > 0 {
+                        current.insert(host.id, count);
+                    }
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    warn!(%error, host_id = %host.id, "skipping unreachable host while scaling");
+                    report.unreachable_hosts.push(host.id);
+                }
+            }
+        }
+        report.previous_count = current.values().map(|count| u32::from(*count)).sum();
+
+        let mut candidates = BTreeSet::new();
+        if desired_count > report.previous_count {
+            let bids = self
+                .perform_actor_auction(actor_ref, constraints.clone())
+                .await?;
+            candidates.extend(
+                bids.into_iter()
+                    .map(|bid| bid.host_id)
+                    .filter(|host_id| !report.unreachable_hosts.contains(host_id)),
+            );
+            if candidates.is_empty() && current.is_empty() {
+                return Err(Error::NotEnoughBids {
+                    reference: actor_ref.to_string(),
+                    required: 1,
+                    received: 0,
+                });
+            }
+        }
+
+        for change in scale::plan(&current, &candidates, desired_count) {
+            debug!(host_id = %change.host_id, from = change.from, to = change.to, "scale_actor_to");
+            let result = if change.to == 0 {
+                self.stop_actor(&change.host_id, actor_ref, annotations.clone())
+                    .await
+            } else {
+                self.scale_actor(
+                    &change.host_id,
+                    actor_ref,
+                    Some(change.to),
+                    annotations.clone(),
+                )
+                .await
+            };
+            report.changes.push(HostScaleChange {
+                host_id: change.host_id,
+                from: change.from,
+                to: change.to,
+                ack: result.unwrap_or_else(negative_ack),
+            });
+        }
+        Ok(report)
+    }
+
+    /// Stops the instances of an actor on a host that carry all of the given annotations, leaving
+    /// any others running, e.g. to stop what wadm manages without touching instances started by
+    /// hand. The host only stops instances whose annotations are exactly those sent with
+    /// `stop_actor`, so the matching instances are first looked up in the host's inventory and
+    /// one command is sent for each distinct set of annotations they carry. `actor_ref` may be
+    /// the actor's public key or image reference.
+    ///
+    /// Returns one entry per command sent, which is none if no instances match. As with
+    /// [`Client::scale_actor_to`], a command the host rejects or doesn't acknowledge is recorded
+    /// rather than stopping the others
+    #[instrument(level = "debug", skip_all)]
+    pub async fn stop_actor_instances(
+        &self,
+        host_id: &str,
+        actor_ref: &str,
+        annotations: &Annotations,
+    ) -> Result<Vec<InstanceStop>> {
+        self.ensure_writable("stop_actor_instances")?;
+        validate::host_id(host_id)?;
+        let inventory = self.get_host_inventory(host_id).await?;
+        let mut stops: Vec<InstanceStop> = Vec::new();
+        for instance in inventory
+            .actors_with_annotations(annotations)
+            .into_iter()
+            .filter(|actor| actor.id == actor_ref || actor.image_ref.as_deref() == Some(actor_ref))
+            .flat_map(|actor| actor.instances)
+        {
+            // Without annotations to look for, a single unfiltered command stops everything
+            let instance_annotations = if annotations.is_empty() {
+                AnnotationMap::new()
+            } else {
+                instance.annotations.unwrap_or_default()
+            };
+            match stops
+                .iter_mut()
+                .find(|stop| stop.annotations == instance_annotations)
+            {
+                Some(stop) => {
+                    stop.instance_count =
+                        stop.instance_count.saturating_add(instance.max_concurrent)
+                }
+                None => stops.push(InstanceStop {
+                    annotations: instance_annotations,
+                    instance_count: instance.max_concurrent,
+                    ack: CtlOperationAck::default(),
+                }),
+            }
+        }
+        for stop in &mut stops {
+            debug!(
+                host_id,
+                annotations = ?stop.annotations,
+                count = stop.instance_count,
+                "stop_actor_instances"
+            );
+            let annotations = (!stop.annotations.is_empty()).then(|| stop.annotations.clone());
+            stop.ack = self
+                .stop_actor(host_id, actor_ref, annotations)
+                .await
+                .unwrap_or_else(negative_ack);
         }
+        Ok(stops)
     }
 
     /// Publishes a registry credential map to the control interface of the lattice. All hosts will
@@ -290,22 +1621,60 @@ impl Client {
     /// credentials when using this function in production as the data contains secrets
     #[instrument(level = "debug", skip_all)]
     pub async fn put_registries(&self, registries: RegistryCredentialMap) -> Result<()> {
+        self.ensure_writable("put_registries")?;
+        self.validate_prefixes()?;
         let subject = broker::publish_registries(&self.topic_prefix, &self.lattice_prefix);
         debug!("put_registries:publish {}", &subject);
-        let bytes = json_serialize(&registries)?;
-        let resp = self
-            .nc
-            .publish_with_headers(
-                subject,
-                OtelHeaderInjector::default_with_span().into(),
-                bytes.into(),
-            )
-            .await;
-        if let Err(e) = resp {
-            Err(format!("Failed to push registry credential map: {}", e).into())
-        } else {
-            Ok(())
+        let bytes = self.codec.serialize(&registries)?;
+        self.nc
+            .publish_with_headers(subject, self.codec.headers(), bytes.into())
+            .await
+            .map_err(Error::nats)
+    }
+
+    /// Publishes a registry credential map to a single host, for example one that just joined an
+    /// existing lattice, without broadcasting the credentials to every other host. The same
+    /// security considerations apply as for [`Client::put_registries`]
+    #[instrument(level = "debug", skip_all)]
+    pub async fn put_registries_to_host(
+        &self,
+        host_id: &str,
+        registries: RegistryCredentialMap,
+    ) -> Result<CtlOperationAck> {
+        self.ensure_writable("put_registries_to_host")?;
+        validate::host_id(host_id)?;
+        let subject =
+            broker::publish_registries_to_host(&self.topic_prefix, &self.lattice_prefix, host_id);
+        debug!("put_registries_to_host:request {}", &subject);
+        let bytes = self.codec.serialize(&registries)?;
+        let msg = self
+            .host_request(host_id, subject, bytes, self.timeout)
+            .await?;
+        self.acknowledgement(
+            &msg,
+            "put_registries_to_host",
+            Some(host_id),
+            None::<&RegistryCredentialMap>,
+        )
+    }
+
+    /// Retrieves the registry credentials configured for the lattice. If the lattice metadata
+    /// bucket holds a `REGISTRIES` entry it is returned, otherwise the hosts are queried
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_registries(&self) -> Result<RegistryCredentialMap> {
+        if let Some(store) = self.kv_store_or_fallback("get_registries").await? {
+            if let Some(registries) = kv::get_registries(&store).await? {
+                debug!(
+                    "get_registries:kv {}",
+                    kv::bucket_name(&self.lattice_prefix)
+                );
+                return Ok(registries);
+            }
         }
+        let subject = broker::queries::registries(&self.topic_prefix, &self.lattice_prefix);
+        debug!("get_registries:request {}", &subject);
+        let msg = self.request_timeout(subject, vec![], self.timeout).await?;
+        codec::decode(&msg)
     }
 
     /// Puts a link into the lattice. Returns an error if it was unable to put the link
@@ -318,6 +1687,7 @@ impl Client {
         link_name: &str,
         values: HashMap<String, String>,
     ) -> Result<CtlOperationAck> {
+        self.ensure_writable("advertise_link")?;
         let ld = LinkDefinition {
             actor_id: actor_id.to_string(),
             provider_id: provider_id.to_string(),
@@ -329,11 +1699,14 @@ impl Client {
         let subject = broker::advertise_link(&self.topic_prefix, &self.lattice_prefix);
         debug!("advertise_link:request {}", &subject);
 
-        let bytes = crate::json_serialize(&ld)?;
-        match self.request_timeout(subject, bytes, self.timeout).await {
-            Ok(msg) => Ok(json_deserialize(&msg.payload)?),
-            Err(e) => Err(format!("Did not receive advertise link acknowledgement: {}", e).into()),
-        }
+        let bytes = self.codec.serialize(&ld)?;
+        let msg = self.command_request(subject, bytes, self.timeout).await?;
+        // Link values often hold secrets, so they are left out of any error
+        let described = LinkDefinition {
+            values: HashMap::new(),
+            ..ld
+        };
+        self.acknowledgement(&msg, "advertise_link", None, Some(&described))
     }
 
     /// Removes a link from the lattice metadata keyvalue bucket. Returns an error if it was unable
@@ -345,6 +1718,7 @@ impl Client {
         contract_id: &str,
         link_name: &str,
     ) -> Result<CtlOperationAck> {
+        self.ensure_writable("remove_link")?;
         let subject = broker::remove_link(&self.topic_prefix, &self.lattice_prefix);
         debug!("remove_link:request {}", &subject);
         let ld = LinkDefinition {
@@ -353,27 +1727,288 @@ impl Client {
             link_name: link_name.to_string(),
             ..Default::default()
         };
-        let bytes = crate::json_serialize(&ld)?;
-        match self.request_timeout(subject, bytes, self.timeout).await {
-            Ok(msg) => Ok(json_deserialize(&msg.payload)?),
-            Err(e) => Err(format!("Did not receive remove link acknowledgement: {}", e).into()),
+        let bytes = self.codec.serialize(&ld)?;
+        let msg = self.command_request(subject, bytes, self.timeout).await?;
+        self.acknowledgement(&msg, "remove_link", None, Some(&ld))
+    }
+
+    /// Advertises a batch of links. If the lattice metadata bucket exists the links are written to
+    /// it directly, otherwise each link is sent to the hosts as with [`Client::advertise_link`].
+    /// Either way, up to [`LINK_BATCH_CONCURRENCY`] links are in flight at once. Returns one
+    /// acknowledgement per link, in the same order as `links`, so that a link that fails doesn't
+    /// hide the others that succeeded
+    #[instrument(level = "debug", skip_all)]
+    pub async fn advertise_links(
+        &self,
+        links: Vec<LinkDefinition>,
+    ) -> Result<Vec<CtlOperationAck>> {
+        use futures::StreamExt as _;
+        self.ensure_writable("advertise_links")?;
+        let store = self.link_store("advertise_links").await?;
+        debug!("advertise_links: {} links", links.len());
+        Ok(futures::stream::iter(links)
+            .map(|ld| {
+                let store = store.as_ref();
+                async move {
+                    link_ack(match store {
+                        Some(store) => kv::put_link(store, &ld).await.map(|_| CtlOperationAck {
+                            accepted: true,
+                            error: String::new(),
+                        }),
+                        None => {
+                            self.advertise_link(
+                                &ld.actor_id,
+                                &ld.provider_id,
+                                &ld.contract_id,
+                                &ld.link_name,
+                                ld.values,
+                            )
+                            .await
+                        }
+                    })
+                }
+            })
+            .buffered(LINK_BATCH_CONCURRENCY)
+            .collect()
+            .await)
+    }
+
+    /// Removes a batch of links, in the same way that [`Client::advertise_links`] advertises them.
+    /// Returns one acknowledgement per link, in the same order as `links`
+    #[instrument(level = "debug", skip_all)]
+    pub async fn remove_links(
+        &self,
+        links: Vec<RemoveLinkDefinitionRequest>,
+    ) -> Result<Vec<CtlOperationAck>> {
+        use futures::StreamExt as _;
+        self.ensure_writable("remove_links")?;
+        let store = self.link_store("remove_links").await?;
+        debug!("remove_links: {} links", links.len());
+        Ok(futures::stream::iter(links)
+            .map(|link| {
+                let store = store.as_ref();
+                async move {
+                    link_ack(match store {
+                        Some(store) => kv::delete_link(
+                            store,
+                            &link.actor_id,
+                            &link.contract_id,
+                            &link.link_name,
+                        )
+                        .await
+                        .map(|_| CtlOperationAck {
+                            accepted: true,
+                            error: String::new(),
+                        }),
+                        None => {
+                            self.remove_link(&link.actor_id, &link.contract_id, &link.link_name)
+                                .await
+                        }
+                    })
+                }
+            })
+            .buffered(LINK_BATCH_CONCURRENCY)
+            .collect()
+            .await)
+    }
+
+    /// Returns the lattice metadata bucket for a bulk link operation, making sure it has a
+    /// generation marker, or `None` if links have to be sent to the hosts instead
+    async fn link_store(&self, operation: &'static str) -> Result<Option<Store>> {
+        match self.kv_store_or_fallback(operation).await? {
+            Some(store) => {
+                kv::ensure_generation(&store).await?;
+                Ok(Some(store))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Queries the hosts in the lattice and then the inventory of each of them, with up to
+    /// [`INVENTORY_CONCURRENCY`] inventory requests in flight at once. Every host that answered
+    /// the hosts query is returned, in the same order, paired with its inventory or with the
+    /// error from requesting it, so that a host that has stopped responding shows up as
+    /// unreachable rather than failing the whole call
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_all_inventories(&self) -> Result<Vec<(Host, Result<HostInventory>)>> {
+        use futures::StreamExt as _;
+        let hosts = self.get_hosts().await?;
+        debug!("get_all_inventories: {} hosts", hosts.len());
+        Ok(futures::stream::iter(hosts)
+            .map(|host| async move {
+                let inventory = self.get_host_inventory(&host.id).await;
+                (host, inventory)
+            })
+            .buffered(INVENTORY_CONCURRENCY)
+            .collect()
+            .await)
+    }
+
+    /// Gathers the lattice's hosts and their inventories (as [`Client::get_all_inventories`]
+    /// does), its link definitions and its claims all at once into a single
+    /// [`LatticeSnapshot`], for tools that need a view of the whole lattice at one point in time.
+    /// A part that can't be retrieved, such as the claims timing out or a host not returning its
+    /// inventory, is recorded in the snapshot with its error instead of failing the call, so only
+    /// an invalid lattice or topic prefix makes it fail
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_lattice_snapshot(&self) -> Result<LatticeSnapshot> {
+        self.validate_prefixes()?;
+        let captured_at_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|since| since.as_millis() as u64)
+            .unwrap_or_default();
+        let (inventories, links, claims) = futures::join!(
+            self.get_all_inventories(),
+            self.query_links(),
+            self.get_claims()
+        );
+        let mut snapshot = LatticeSnapshot {
+            captured_at_ms,
+            ..Default::default()
+        };
+        match inventories {
+            Ok(inventories) => {
+                let (hosts, inventories) = inventories
+                    .into_iter()
+                    .map(|(host, inventory)| {
+                        let host_id = host.id.clone();
+                        let inventory = match inventory {
+                            Ok(inventory) => InventorySnapshot {
+                                host_id,
+                                inventory: Some(inventory),
+                                error: None,
+                            },
+                            Err(error) => InventorySnapshot {
+                                host_id,
+                                inventory: None,
+                                error: Some(error.to_string()),
+                            },
+                        };
+                        (host, inventory)
+                    })
+                    .unzip();
+                snapshot.hosts = Some(hosts);
+                snapshot.inventories = inventories;
+            }
+            Err(error) => snapshot.hosts_error = Some(error.to_string()),
+        }
+        match links {
+            Ok(links) => snapshot.links = Some(LinkDefinitionList { links }),
+            Err(error) => snapshot.links_error = Some(error.to_string()),
         }
+        match claims {
+            Ok(claims) => snapshot.claims = Some(GetClaimsResponse { claims }),
+            Err(error) => snapshot.claims_error = Some(error.to_string()),
+        }
+        debug!(
+            "get_lattice_snapshot: {} hosts, complete: {}",
+            snapshot.inventories.len(),
+            snapshot.is_complete()
+        );
+        Ok(snapshot)
+    }
+
+    /// Finds everything in the lattice that belongs to the named wadm application by querying the
+    /// inventory of every responsive host along with the lattice's link definitions. Hosts that
+    /// stop responding between the host query and the inventory request are skipped
+    #[instrument(level = "debug", skip_all)]
+    pub async fn find_app(&self, app_name: &str) -> Result<AppFootprint> {
+        let inventories = self
+            .get_all_inventories()
+            .await?
+            .into_iter()
+            .filter_map(|(host, inventory)| match inventory {
+                Ok(inventory) => Some(inventory),
+                Err(error) => {
+                    warn!(%error, host_id = %host.id, "skipping host while finding app");
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let links = self.query_links().await?;
+        Ok(AppFootprint::from_inventories(
+            app_name,
+            &inventories,
+            &links,
+        ))
     }
 
-    /// Retrieves the list of link definitions stored in the lattice metadata key-value bucket. If
-    /// the client was created with caching, this will return the cached list of links. Otherwise,
-    /// it will query the bucket for the list of links.
+    /// Retrieves the lattice's link definitions. If the lattice metadata bucket exists the links
+    /// are read from it, otherwise the hosts are queried for them over the legacy topic
     #[instrument(level = "debug", skip_all)]
     pub async fn query_links(&self) -> Result<Vec<LinkDefinition>> {
+        self.query_links_detailed()
+            .await
+            .map(CtlResponse::into_data)
+    }
+
+    /// Like [`Client::query_links`], but also returns the subject the query was sent on and how
+    /// long it took to be answered. Links read from the lattice metadata bucket are reported with
+    /// the bucket's name in place of the subject
+    #[instrument(level = "debug", skip_all)]
+    pub async fn query_links_detailed(&self) -> Result<CtlResponse<Vec<LinkDefinition>>> {
+        let start = std::time::Instant::now();
+        if let Some(store) = self.kv_store_or_fallback("query_links").await? {
+            let bucket = kv::bucket_name(&self.lattice_prefix);
+            debug!("query_links:kv {}", &bucket);
+            let links = kv::get_links(&store, &LinkFilter::new()).await?;
+            return Ok(CtlResponse::new(links, bucket, start));
+        }
+        self.query_topic_links(start).await
+    }
+
+    /// Queries the hosts for their link definitions over the legacy topic, ignoring the lattice
+    /// metadata bucket
+    async fn query_topic_links(
+        &self,
+        start: std::time::Instant,
+    ) -> Result<CtlResponse<Vec<LinkDefinition>>> {
         let subject = broker::queries::link_definitions(&self.topic_prefix, &self.lattice_prefix);
         debug!("query_links:request {}", &subject);
-        match self.request_timeout(subject, vec![], self.timeout).await {
-            Ok(msg) => {
-                let list: LinkDefinitionList = json_deserialize(&msg.payload)?;
-                Ok(list.links)
-            }
-            Err(e) => Err(format!("Did not receive a response to links query: {}", e).into()),
+        let msg = self
+            .request_timeout(subject.clone(), vec![], self.timeout)
+            .await?;
+        let list: LinkDefinitionList = codec::decode(&msg)?;
+        Ok(CtlResponse::new(list.links, subject, start))
+    }
+
+    /// Retrieves the links from the given actor
+    #[instrument(level = "debug", skip_all)]
+    pub async fn query_links_for_actor(&self, actor_id: &str) -> Result<Vec<LinkDefinition>> {
+        self.query_links_filtered(LinkFilter::new().actor_id(actor_id))
+            .await
+    }
+
+    /// Retrieves the links to the given provider under the given link name
+    #[instrument(level = "debug", skip_all)]
+    pub async fn query_links_for_provider(
+        &self,
+        provider_id: &str,
+        link_name: &str,
+    ) -> Result<Vec<LinkDefinition>> {
+        self.query_links_filtered(
+            LinkFilter::new()
+                .provider_id(provider_id)
+                .link_name(link_name),
+        )
+        .await
+    }
+
+    /// Retrieves the links that match `filter`. If the lattice metadata bucket exists the links
+    /// are read from it, skipping those that don't match, otherwise the hosts are queried as with
+    /// [`Client::query_links`] and the response is filtered
+    #[instrument(level = "debug", skip_all)]
+    pub async fn query_links_filtered(&self, filter: LinkFilter) -> Result<Vec<LinkDefinition>> {
+        if let Some(store) = self.kv_store_or_fallback("query_links_filtered").await? {
+            debug!("query_links_filtered:kv {:?}", filter);
+            return kv::get_links(&store, &filter).await;
         }
+        let mut links = self
+            .query_topic_links(std::time::Instant::now())
+            .await?
+            .into_data();
+        links.retain(|link| filter.matches(link));
+        Ok(links)
     }
 
     /// Issue a command to a host instructing that it replace an existing actor (indicated by its
@@ -392,29 +2027,108 @@ impl Client {
         new_actor_ref: &str,
         annotations: Option<HashMap<String, String>>,
     ) -> Result<CtlOperationAck> {
+        self.update_actor_detailed(host_id, existing_actor_id, new_actor_ref, annotations)
+            .await
+            .map(CtlResponse::into_data)
+    }
+
+    /// Like [`Client::update_actor`], but also returns the subject the command was sent on and how
+    /// long it took to be acknowledged
+    #[instrument(level = "debug", skip_all)]
+    pub async fn update_actor_detailed(
+        &self,
+        host_id: &str,
+        existing_actor_id: &str,
+        new_actor_ref: &str,
+        annotations: Option<HashMap<String, String>>,
+    ) -> Result<CtlResponse<CtlOperationAck>> {
+        self.ensure_writable("update_actor")?;
+        validate::host_id(host_id)?;
+        let start = std::time::Instant::now();
         let subject =
             broker::commands::update_actor(&self.topic_prefix, &self.lattice_prefix, host_id);
         debug!("update_actor:request {}", &subject);
-        let bytes = json_serialize(UpdateActorCommand {
+        let command = UpdateActorCommand {
             host_id: host_id.to_string(),
             actor_id: existing_actor_id.to_string(),
             new_actor_ref: new_actor_ref.to_string(),
             annotations,
-        })?;
-        match self.request_timeout(subject, bytes, self.timeout).await {
-            Ok(msg) => Ok(json_deserialize(&msg.payload)?),
-            Err(e) => Err(format!("Did not receive update actor acknowledgement: {}", e).into()),
-        }
+        };
+        let bytes = self.codec.serialize(&command)?;
+        let msg = self
+            .host_request(host_id, subject.clone(), bytes, self.timeout)
+            .await?;
+        Ok(CtlResponse::new(
+            self.acknowledgement(&msg, "update_actor", Some(host_id), Some(&command))?,
+            subject,
+            start,
+        ))
     }
 
-    /// Issues a command to a host to start a provider with a given OCI reference using the
-    /// specified link name (or "default" if none is specified). The target wasmCloud host will
-    /// acknowledge the receipt of this command _before_ downloading the provider's bytes from the
-    /// OCI registry, indicating either a validation failure or success. If a client needs
-    /// deterministic guarantees that the provider has completed its startup process, such a client
-    /// needs to monitor the control event stream for the appropriate event.
+    /// Works out the public key of the actor an OCI image reference or call alias refers to, by
+    /// looking through the lattice's claims (see [`Client::get_claims`]) and the inventory of
+    /// every responsive host. A public key that appears in either is returned as it is. Returns
+    /// `None` if nothing matches, and fails with [`Error::AmbiguousReference`] rather than
+    /// picking one if the reference matches more than one actor, e.g. while an image reference
+    /// is being moved to a new build. Hosts whose inventory can't be retrieved are skipped
     #[instrument(level = "debug", skip_all)]
-    pub async fn start_provider(
+    pub async fn resolve_actor_id(&self, actor_ref: &str) -> Result<Option<String>> {
+        let claims = self.get_claims().await?;
+        let inventories = self
+            .get_all_inventories()
+            .await?
+            .into_iter()
+            .filter_map(|(host, inventory)| match inventory {
+                Ok(inventory) => Some(inventory),
+                Err(error) => {
+                    warn!(%error, host_id = %host.id, "skipping host while resolving actor");
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let candidates = resolve::candidates(actor_ref, &claims, &inventories);
+        debug!("resolve_actor_id: {} {:?}", actor_ref, candidates);
+        if candidates.len() > 1 {
+            return Err(Error::AmbiguousReference {
+                reference: actor_ref.to_string(),
+                candidates: candidates.into_iter().collect(),
+            });
+        }
+        Ok(candidates.into_iter().next())
+    }
+
+    /// Like [`Client::update_actor`], but with the running actor given by the image reference or
+    /// call alias it was started with, resolved with [`Client::resolve_actor_id`]. Fails with
+    /// [`Error::InvalidArgument`] if no actor matches `existing_actor_ref`, without sending
+    /// anything to the host
+    #[instrument(level = "debug", skip_all)]
+    pub async fn update_actor_by_ref(
+        &self,
+        host_id: &str,
+        existing_actor_ref: &str,
+        new_actor_ref: &str,
+        annotations: Option<HashMap<String, String>>,
+    ) -> Result<CtlOperationAck> {
+        self.ensure_writable("update_actor_by_ref")?;
+        validate::host_id(host_id)?;
+        let Some(actor_id) = self.resolve_actor_id(existing_actor_ref).await? else {
+            return Err(Error::InvalidArgument {
+                argument: "actor reference",
+                reason: format!("no actor in the lattice matches {existing_actor_ref}"),
+            });
+        };
+        self.update_actor(host_id, &actor_id, new_actor_ref, annotations)
+            .await
+    }
+
+    /// Issues a command to a host to start a provider with a given OCI reference using the
+    /// specified link name (or "default" if none is specified). The target wasmCloud host will
+    /// acknowledge the receipt of this command _before_ downloading the provider's bytes from the
+    /// OCI registry, indicating either a validation failure or success. If a client needs
+    /// deterministic guarantees that the provider has completed its startup process, such a client
+    /// needs to monitor the control event stream for the appropriate event.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn start_provider(
         &self,
         host_id: &str,
         provider_ref: &str,
@@ -422,21 +2136,53 @@ impl Client {
         annotations: Option<HashMap<String, String>>,
         provider_configuration: Option<String>,
     ) -> Result<CtlOperationAck> {
+        self.start_provider_detailed(
+            host_id,
+            provider_ref,
+            link_name,
+            annotations,
+            provider_configuration,
+        )
+        .await
+        .map(CtlResponse::into_data)
+    }
+
+    /// Like [`Client::start_provider`], but also returns the subject the command was sent on and how
+    /// long it took to be acknowledged
+    #[instrument(level = "debug", skip_all)]
+    pub async fn start_provider_detailed(
+        &self,
+        host_id: &str,
+        provider_ref: &str,
+        link_name: Option<String>,
+        annotations: Option<HashMap<String, String>>,
+        provider_configuration: Option<String>,
+    ) -> Result<CtlResponse<CtlOperationAck>> {
+        self.ensure_writable("start_provider")?;
+        // An empty host ID is passed through here, as it always has been
+        validate::subject_token("host ID", host_id)?;
+        let start = std::time::Instant::now();
         let subject =
             broker::commands::start_provider(&self.topic_prefix, &self.lattice_prefix, host_id);
         debug!("start_provider:request {}", &subject);
-        let bytes = json_serialize(StartProviderCommand {
+        let command = StartProviderCommand {
             host_id: host_id.to_string(),
             provider_ref: provider_ref.to_string(),
             link_name: link_name.unwrap_or_else(|| "default".to_string()),
             annotations,
+            command_id: Some(CommandId::new()),
             configuration: provider_configuration,
-        })?;
+        };
+        let bytes = self.codec.serialize(&command)?;
 
-        match self.request_timeout(subject, bytes, self.timeout).await {
-            Ok(msg) => Ok(json_deserialize(&msg.payload)?),
-            Err(e) => Err(format!("Did not receive start provider acknowledgement: {}", e).into()),
-        }
+        let msg = self
+            .host_request(host_id, subject.clone(), bytes, self.timeout)
+            .await?;
+        Ok(CtlResponse::new(
+            self.acknowledgement(&msg, "start_provider", Some(host_id), Some(&command))?,
+            subject,
+            start,
+        ))
     }
 
     /// Issues a command to a host to stop a provider for the given OCI reference, link name, and
@@ -452,20 +2198,44 @@ impl Client {
         contract_id: &str,
         annotations: Option<HashMap<String, String>>,
     ) -> Result<CtlOperationAck> {
+        self.stop_provider_detailed(host_id, provider_ref, link_name, contract_id, annotations)
+            .await
+            .map(CtlResponse::into_data)
+    }
+
+    /// Like [`Client::stop_provider`], but also returns the subject the command was sent on and how
+    /// long it took to be acknowledged
+    #[instrument(level = "debug", skip_all)]
+    pub async fn stop_provider_detailed(
+        &self,
+        host_id: &str,
+        provider_ref: &str,
+        link_name: &str,
+        contract_id: &str,
+        annotations: Option<HashMap<String, String>>,
+    ) -> Result<CtlResponse<CtlOperationAck>> {
+        self.ensure_writable("stop_provider")?;
+        validate::host_id(host_id)?;
+        let start = std::time::Instant::now();
         let subject =
             broker::commands::stop_provider(&self.topic_prefix, &self.lattice_prefix, host_id);
         debug!("stop_provider:request {}", &subject);
-        let bytes = json_serialize(StopProviderCommand {
+        let command = StopProviderCommand {
             host_id: host_id.to_string(),
             provider_ref: provider_ref.to_string(),
             link_name: link_name.to_string(),
             contract_id: contract_id.to_string(),
             annotations,
-        })?;
-        match self.request_timeout(subject, bytes, self.timeout).await {
-            Ok(msg) => Ok(json_deserialize(&msg.payload)?),
-            Err(e) => Err(format!("Did not receive stop provider acknowledgement: {}", e).into()),
-        }
+        };
+        let bytes = self.codec.serialize(&command)?;
+        let msg = self
+            .host_request(host_id, subject.clone(), bytes, self.timeout)
+            .await?;
+        Ok(CtlResponse::new(
+            self.acknowledgement(&msg, "stop_provider", Some(host_id), Some(&command))?,
+            subject,
+            start,
+        ))
     }
 
     /// Issues a command to a host to stop an actor for the given OCI reference. The target
@@ -479,18 +2249,41 @@ impl Client {
         actor_ref: &str,
         annotations: Option<HashMap<String, String>>,
     ) -> Result<CtlOperationAck> {
+        self.stop_actor_detailed(host_id, actor_ref, annotations)
+            .await
+            .map(CtlResponse::into_data)
+    }
+
+    /// Like [`Client::stop_actor`], but also returns the subject the command was sent on and how
+    /// long it took to be acknowledged
+    #[instrument(level = "debug", skip_all)]
+    pub async fn stop_actor_detailed(
+        &self,
+        host_id: &str,
+        actor_ref: &str,
+        annotations: Option<HashMap<String, String>>,
+    ) -> Result<CtlResponse<CtlOperationAck>> {
+        self.ensure_writable("stop_actor")?;
+        validate::host_id(host_id)?;
+        let start = std::time::Instant::now();
         let subject =
             broker::commands::stop_actor(&self.topic_prefix, &self.lattice_prefix, host_id);
         debug!("stop_actor:request {}", &subject);
-        let bytes = json_serialize(StopActorCommand {
+        let command = StopActorCommand {
             host_id: host_id.to_string(),
             actor_ref: actor_ref.to_string(),
             annotations,
-        })?;
-        match self.request_timeout(subject, bytes, self.timeout).await {
-            Ok(msg) => Ok(json_deserialize(&msg.payload)?),
-            Err(e) => Err(format!("Did not receive stop actor acknowledgement: {}", e).into()),
-        }
+            command_id: Some(CommandId::new()),
+        };
+        let bytes = self.codec.serialize(&command)?;
+        let msg = self
+            .host_request(host_id, subject.clone(), bytes, self.timeout)
+            .await?;
+        Ok(CtlResponse::new(
+            self.acknowledgement(&msg, "stop_actor", Some(host_id), Some(&command))?,
+            subject,
+            start,
+        ))
     }
 
     /// Issues a command to a specific host to perform a graceful termination. The target host will
@@ -503,160 +2296,3563 @@ impl Client {
         host_id: &str,
         timeout_ms: Option<u64>,
     ) -> Result<CtlOperationAck> {
+        self.stop_host_detailed(host_id, timeout_ms)
+            .await
+            .map(CtlResponse::into_data)
+    }
+
+    /// Like [`Client::stop_host`], but also returns the subject the command was sent on and how
+    /// long it took to be acknowledged
+    #[instrument(level = "debug", skip_all)]
+    pub async fn stop_host_detailed(
+        &self,
+        host_id: &str,
+        timeout_ms: Option<u64>,
+    ) -> Result<CtlResponse<CtlOperationAck>> {
+        self.ensure_writable("stop_host")?;
+        validate::host_id(host_id)?;
+        let start = std::time::Instant::now();
         let subject =
             broker::commands::stop_host(&self.topic_prefix, &self.lattice_prefix, host_id);
         debug!("stop_host:request {}", &subject);
-        let bytes = json_serialize(StopHostCommand {
+        let command = StopHostCommand {
             host_id: host_id.to_owned(),
             timeout: timeout_ms,
-        })?;
+        };
+        let bytes = self.codec.serialize(&command)?;
 
-        match self.request_timeout(subject, bytes, self.timeout).await {
-            Ok(msg) => Ok(json_deserialize(&msg.payload)?),
-            Err(e) => Err(format!("Did not receive stop host acknowledgement: {}", e).into()),
-        }
+        let msg = self
+            .host_request(host_id, subject.clone(), bytes, self.timeout)
+            .await?;
+        Ok(CtlResponse::new(
+            self.acknowledgement(&msg, "stop_host", Some(host_id), Some(&command))?,
+            subject,
+            start,
+        ))
     }
 
-    async fn publish_and_wait<D: DeserializeOwned>(
+    /// Sets a label on a host, replacing any existing value for the key. Labels are matched
+    /// against auction constraints, so this changes which actor and provider auctions the host
+    /// bids in. Fails with [`Error::InvalidArgument`] if the key is empty or contains NATS
+    /// wildcard characters
+    #[instrument(level = "debug", skip_all)]
+    pub async fn put_label(
         &self,
-        subject: String,
-        payload: Vec<u8>,
-    ) -> Result<Vec<D>> {
-        let reply = self.nc.new_inbox();
-        let sub = self.nc.subscribe(reply.clone()).await?;
-        self.nc
-            .publish_with_reply_and_headers(
-                subject.clone(),
-                reply,
-                OtelHeaderInjector::default_with_span().into(),
-                payload.into(),
-            )
+        host_id: &str,
+        key: &str,
+        value: &str,
+    ) -> Result<CtlOperationAck> {
+        self.ensure_writable("put_label")?;
+        validate::host_id(host_id)?;
+        validate::label_key(key)?;
+        let subject =
+            broker::commands::put_label(&self.topic_prefix, &self.lattice_prefix, host_id);
+        debug!("put_label:request {}", &subject);
+        let command = HostLabel {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        let bytes = self.codec.serialize(&command)?;
+        let msg = self
+            .host_request(host_id, subject, bytes, self.timeout)
             .await?;
-        let nc = self.nc.clone();
-        tokio::spawn(async move {
-            if let Err(error) = nc.flush().await {
-                error!(%error, "flush after publish");
-            }
-        });
-        Ok(collect_timeout::<D>(sub, self.auction_timeout, subject.as_str()).await)
+        self.acknowledgement(&msg, "put_label", Some(host_id), Some(&command))
     }
 
-    /// Returns the receiver end of a channel that subscribes to the lattice control event stream.
-    /// Any [`Event`](struct@Event)s that are published after this channel is created
-    /// will be added to the receiver channel's buffer, which can be observed or handled if needed.
-    /// See the example for how you could use this receiver to handle events.
-    ///
-    /// # Example
-    /// ```rust
-    /// use wasmcloud_control_interface::{Client, ClientBuilder};
-    /// async {
-    ///   let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
-    ///   let client = ClientBuilder::new(nc)
-    ///                 .rpc_timeout(std::time::Duration::from_millis(1000))
-    ///                 .auction_timeout(std::time::Duration::from_millis(1000))
-    ///                 .build();
-    ///   let mut receiver = client.events_receiver().await.unwrap();
-    ///   tokio::spawn( async move {
-    ///       while let Some(evt) = receiver.recv().await {
-    ///           println!("Event received: {:?}", evt);
-    ///       }
-    ///   });
-    ///   // perform other operations on client
-    ///   client.get_host_inventory("NAEXHW...").await.unwrap();
-    /// };
-    /// ```
-    ///
-    /// Once you're finished with the event receiver, be sure to call `drop` with the receiver
-    /// as an argument. This closes the channel and will prevent the sender from endlessly
-    /// sending messages into the channel buffer.
-    ///
-    /// # Example
-    /// ```rust
-    /// use wasmcloud_control_interface::{Client, ClientBuilder};
-    /// async {
-    ///   let nc = async_nats::connect("0.0.0.0:4222").await.unwrap();
-    ///   let client = ClientBuilder::new(nc)
-    ///                 .rpc_timeout(std::time::Duration::from_millis(1000))
-    ///                 .auction_timeout(std::time::Duration::from_millis(1000))
-    ///                 .build();    
-    ///   let mut receiver = client.events_receiver().await.unwrap();
-    ///   // read the docs for flume receiver. You can use it in either sync or async code
-    ///   // The receiver can be cloned() as needed.
-    ///   // If you drop the receiver. The subscriber will exit
-    ///   // If the nats connection ic closed, the loop below will exit.
-    ///   while let Some(evt) = receiver.recv().await {
-    ///       println!("Event received: {:?}", evt);
-    ///   }
-    /// };
-    /// ```
-    pub async fn events_receiver(&self) -> Result<Receiver<Event>> {
+    /// Removes a label from a host. The key is validated as for [`Client::put_label`]
+    #[instrument(level = "debug", skip_all)]
+    pub async fn delete_label(&self, host_id: &str, key: &str) -> Result<CtlOperationAck> {
+        self.ensure_writable("delete_label")?;
+        validate::host_id(host_id)?;
+        validate::label_key(key)?;
+        let subject =
+            broker::commands::delete_label(&self.topic_prefix, &self.lattice_prefix, host_id);
+        debug!("delete_label:request {}", &subject);
+        let command = HostLabel {
+            key: key.to_string(),
+            ..Default::default()
+        };
+        let bytes = self.codec.serialize(&command)?;
+        let msg = self
+            .host_request(host_id, subject, bytes, self.timeout)
+            .await?;
+        self.acknowledgement(&msg, "delete_label", Some(host_id), Some(&command))
+    }
+
+    /// Stops a host like [`Client::stop_host`], then reports its progress as it drains. The
+    /// returned receiver is sent the host's actor and provider stopped events, followed by either
+    /// [`HostDrainEvent::Stopped`] once the host confirms it has stopped, or
+    /// [`HostDrainEvent::Unconfirmed`] if it hasn't by the deadline: `timeout_ms` (or
+    /// [`HOST_DRAIN_WAIT`] if not given) plus the client's request timeout. The receiver is then
+    /// closed. Fails with [`Error::NotAccepted`] if the host refuses the command. Dropping the
+    /// receiver stops the monitoring but not the host's shutdown
+    #[instrument(level = "debug", skip_all)]
+    pub async fn stop_host_and_monitor(
+        &self,
+        host_id: &str,
+        timeout_ms: Option<u64>,
+    ) -> Result<Receiver<HostDrainEvent>> {
         use futures::StreamExt as _;
-        let (sender, receiver) = tokio::sync::mpsc::channel(5000);
+        self.ensure_writable("stop_host_and_monitor")?;
+        validate::host_id(host_id)?;
+        let subject = broker::control_event(&self.lattice_prefix);
         let mut sub = self
             .nc
-            .subscribe(broker::control_event(&self.lattice_prefix))
-            .await?;
+            .subscribe(subject.clone())
+            .await
+            .map_err(Error::nats)?;
+        if let Err(e) = self.stop_host(host_id, timeout_ms).await?.into_result() {
+            let _ = sub.unsubscribe().await;
+            return Err(e.into());
+        }
+        let deadline = timeout_ms.map_or(HOST_DRAIN_WAIT, Duration::from_millis) + self.timeout;
+        let (sender, receiver) = tokio::sync::mpsc::channel(sub_stream::RESULT_BUFFER);
+        let host_id = host_id.to_string();
         tokio::spawn(async move {
-            while let Some(msg) = sub.next().await {
-                let evt = match json_deserialize::<Event>(&msg.payload) {
-                    Ok(evt) => evt,
-                    Err(_) => {
-                        error!("Object received on event stream was not a CloudEvent");
-                        continue;
-                    }
+            let sleep = tokio::time::sleep(deadline);
+            tokio::pin!(sleep);
+            let last = loop {
+                let msg = tokio::select! {
+                    msg = sub.next() => match msg {
+                        Some(msg) => msg,
+                        None => break HostDrainEvent::Unconfirmed,
+                    },
+                    _ = &mut sleep => break HostDrainEvent::Unconfirmed,
+                    _ = sender.closed() => break HostDrainEvent::Unconfirmed,
                 };
-                trace!("received event: {:?}", evt);
-                // If the channel is disconnected, stop sending events
-                if sender.send(evt).await.is_err() {
-                    let _ = sub.unsubscribe().await;
-                    break;
+                let Ok(event) = json_deserialize::<Event>(&msg.payload) else {
+                    continue;
+                };
+                if cloudevents::AttributesReader::source(&event).as_str() != host_id {
+                    continue;
                 }
-            }
+                let progress = match LatticeEvent::from(event) {
+                    LatticeEvent::ActorStopped(event) => HostDrainEvent::ActorStopped(event),
+                    LatticeEvent::ProviderStopped(event) => HostDrainEvent::ProviderStopped(event),
+                    LatticeEvent::HostStopped(event) => break HostDrainEvent::Stopped(event),
+                    _ => continue,
+                };
+                if sender.send(progress).await.is_err() {
+                    break HostDrainEvent::Unconfirmed;
+                }
+            };
+            debug!(%host_id, confirmed = matches!(last, HostDrainEvent::Stopped(_)), "host drain finished");
+            let _ = sender.send(last).await;
+            let _ = sub.unsubscribe().await;
         });
         Ok(receiver)
     }
-}
 
-/// Helper function that serializes the data and maps the error
-fn json_serialize<T>(
-    item: T,
-) -> ::std::result::Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>>
-where
-    T: Serialize,
-{
-    serde_json::to_vec(&item).map_err(|e| format!("JSON serialization failure: {}", e).into())
-}
+    /// Starts an actor like [`Client::scale_actor`], allowing it to handle up to `count` requests
+    /// concurrently (`0` meaning unbounded), then waits up to `wait_timeout` for the host to report
+    /// that it started. Fails with [`Error::NotAccepted`] if the host refuses the command,
+    /// [`Error::CommandFailed`] if it reports that the actor failed to start, and
+    /// [`Error::Timeout`] if the start isn't reported in time. Only events from `host_id` are
+    /// considered, so starts of the same actor on different hosts can be awaited concurrently
+    #[instrument(level = "debug", skip_all)]
+    pub async fn start_actor_and_wait(
+        &self,
+        host_id: &str,
+        actor_ref: &str,
+        count: u16,
+        annotations: Option<HashMap<String, String>>,
+        wait_timeout: Duration,
+    ) -> Result<CommandCompletion<ActorStarted>> {
+        self.ensure_writable("start_actor_and_wait")?;
+        let max = if count == 0 { None } else { Some(count) };
+        self.command_and_wait(
+            host_id,
+            self.scale_actor(host_id, actor_ref, max, annotations),
+            // However much concurrency is allowed, the host starts a single instance
+            1,
+            wait_timeout,
+            |event| match event {
+                LatticeEvent::ActorStarted(started)
+                    if started.data.public_key == actor_ref
+                        || started.data.image_ref.as_deref() == Some(actor_ref) =>
+                {
+                    Some(wait::Outcome::Done(started))
+                }
+                LatticeEvent::ActorStartFailed(failed) if failed.data.actor_ref == actor_ref => {
+                    Some(wait::Outcome::Failed(failed.data.error))
+                }
+                _ => None,
+            },
+        )
+        .await
+    }
 
-/// Helper function that deserializes the data and maps the error
-fn json_deserialize<'de, T: Deserialize<'de>>(
-    buf: &'de [u8],
-) -> ::std::result::Result<T, Box<dyn std::error::Error + Send + Sync>> {
-    serde_json::from_slice(buf).map_err(|e| format!("JSON deserialization failure: {}", e).into())
-}
+    /// Starts a provider like [`Client::start_provider`], then waits up to `wait_timeout` for the
+    /// host to report that it started. Fails in the same ways as [`Client::start_actor_and_wait`]
+    #[instrument(level = "debug", skip_all)]
+    pub async fn start_provider_and_wait(
+        &self,
+        host_id: &str,
+        provider_ref: &str,
+        link_name: Option<String>,
+        annotations: Option<HashMap<String, String>>,
+        provider_configuration: Option<String>,
+        wait_timeout: Duration,
+    ) -> Result<CommandCompletion<ProviderStarted>> {
+        self.ensure_writable("start_provider_and_wait")?;
+        let link = link_name.clone().unwrap_or_else(|| "default".to_string());
+        self.command_and_wait(
+            host_id,
+            self.start_provider(
+                host_id,
+                provider_ref,
+                link_name,
+                annotations,
+                provider_configuration,
+            ),
+            1,
+            wait_timeout,
+            |event| match event {
+                LatticeEvent::ProviderStarted(started)
+                    if started.data.link_name == link
+                        && (started.data.public_key == provider_ref
+                            || started.data.image_ref.as_deref() == Some(provider_ref)) =>
+                {
+                    Some(wait::Outcome::Done(started))
+                }
+                LatticeEvent::ProviderStartFailed(failed)
+                    if failed.data.link_name == link
+                        && failed.data.provider_ref == provider_ref =>
+                {
+                    Some(wait::Outcome::Failed(failed.data.error))
+                }
+                _ => None,
+            },
+        )
+        .await
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::time::Duration;
+    /// Stops a provider like [`Client::stop_provider`], then waits up to `wait_timeout` for the
+    /// host to report that it stopped. `provider_ref` must be the provider's public key, as that
+    /// is how the host identifies it in the event
+    #[instrument(level = "debug", skip_all)]
+    pub async fn stop_provider_and_wait(
+        &self,
+        host_id: &str,
+        provider_ref: &str,
+        link_name: &str,
+        contract_id: &str,
+        annotations: Option<HashMap<String, String>>,
+        wait_timeout: Duration,
+    ) -> Result<CommandCompletion<ProviderStopped>> {
+        self.ensure_writable("stop_provider_and_wait")?;
+        self.command_and_wait(
+            host_id,
+            self.stop_provider(host_id, provider_ref, link_name, contract_id, annotations),
+            1,
+            wait_timeout,
+            |event| match event {
+                LatticeEvent::ProviderStopped(stopped)
+                    if stopped.data.public_key == provider_ref
+                        && stopped.data.link_name == link_name
+                        && stopped.data.contract_id == contract_id =>
+                {
+                    Some(wait::Outcome::Done(stopped))
+                }
+                _ => None,
+            },
+        )
+        .await
+    }
 
-    /// Note: This test is a means of manually watching the event stream as CloudEvents are received
-    /// It does not assert functionality, and so we've marked it as ignore to ensure it's not run by default
-    /// It currently listens for 120 seconds then exits
-    #[tokio::test]
-    #[ignore]
-    async fn test_events_receiver() {
-        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
-        let client = ClientBuilder::new(nc)
-            .timeout(Duration::from_millis(1000))
-            .auction_timeout(Duration::from_millis(1000))
-            .build();
-        let mut receiver = client.events_receiver().await.unwrap();
-        tokio::spawn(async move {
-            while let Some(evt) = receiver.recv().await {
-                println!("Event received: {:?}", evt);
-            }
-        });
-        println!("Listening to Cloud Events for 120 seconds. Then we will quit.");
-        tokio::time::sleep(std::time::Duration::from_secs(120)).await;
+    /// Stops an actor like [`Client::stop_actor`], then waits up to `wait_timeout` for the host to
+    /// report that every instance stopped. The host's inventory is read first to find the actor's
+    /// public key and how many instances it is running, so `actor_ref` may be either the public
+    /// key or the reference the actor was started from
+    #[instrument(level = "debug", skip_all)]
+    pub async fn stop_actor_and_wait(
+        &self,
+        host_id: &str,
+        actor_ref: &str,
+        annotations: Option<HashMap<String, String>>,
+        wait_timeout: Duration,
+    ) -> Result<CommandCompletion<ActorStopped>> {
+        self.ensure_writable("stop_actor_and_wait")?;
+        let inventory = self.get_host_inventory(host_id).await?;
+        let (public_key, instances) = inventory
+            .actors
+            .iter()
+            .find(|actor| actor.id == actor_ref || actor.image_ref.as_deref() == Some(actor_ref))
+            .map(|actor| (actor.id.clone(), actor.instances.len()))
+            .unwrap_or_else(|| (actor_ref.to_string(), 1));
+        self.command_and_wait(
+            host_id,
+            self.stop_actor(host_id, actor_ref, annotations),
+            instances.max(1),
+            wait_timeout,
+            |event| match event {
+                LatticeEvent::ActorStopped(stopped) if stopped.data.public_key == public_key => {
+                    Some(wait::Outcome::Done(stopped))
+                }
+                _ => None,
+            },
+        )
+        .await
+    }
+
+    /// Updates an actor like [`Client::update_actor`], then waits up to `wait_timeout` for the host
+    /// to report that the update finished. Fails with [`Error::CommandFailed`] if the host reports
+    /// that the update failed
+    #[instrument(level = "debug", skip_all)]
+    pub async fn update_actor_and_wait(
+        &self,
+        host_id: &str,
+        existing_actor_id: &str,
+        new_actor_ref: &str,
+        annotations: Option<HashMap<String, String>>,
+        wait_timeout: Duration,
+    ) -> Result<CommandCompletion<ActorUpdated>> {
+        self.ensure_writable("update_actor_and_wait")?;
+        self.command_and_wait(
+            host_id,
+            self.update_actor(host_id, existing_actor_id, new_actor_ref, annotations),
+            1,
+            wait_timeout,
+            |event| match event {
+                LatticeEvent::ActorUpdated(updated)
+                    if updated.data.public_key == existing_actor_id =>
+                {
+                    Some(wait::Outcome::Done(updated))
+                }
+                LatticeEvent::ActorUpdateFailed(failed)
+                    if failed.data.public_key == existing_actor_id =>
+                {
+                    Some(wait::Outcome::Failed(failed.data.error))
+                }
+                _ => None,
+            },
+        )
+        .await
+    }
+
+    /// Sends `command` to `host_id` and waits for `expected` events from that host that `matcher`
+    /// reports as done. The event subscription is made before the command is sent so that no event
+    /// can be missed
+    async fn command_and_wait<T>(
+        &self,
+        host_id: &str,
+        command: impl std::future::Future<Output = Result<CtlOperationAck>>,
+        expected: usize,
+        wait_timeout: Duration,
+        matcher: impl FnMut(LatticeEvent) -> Option<wait::Outcome<T>>,
+    ) -> Result<CommandCompletion<T>> {
+        use futures::StreamExt as _;
+        let subject = broker::control_event(&self.lattice_prefix);
+        let mut sub = self
+            .nc
+            .subscribe(subject.clone())
+            .await
+            .map_err(Error::nats)?;
+        let result = async {
+            let ack = command.await?;
+            ack.clone().into_result()?;
+            let payloads = (&mut sub).map(|msg| msg.payload);
+            let events =
+                wait::for_events(payloads, &subject, host_id, expected, wait_timeout, matcher)
+                    .await?;
+            Ok(CommandCompletion { ack, events })
+        }
+        .await;
+        let _ = sub.unsubscribe().await;
+        result
+    }
+
+    /// Sends an ordered batch of commands to a single host, returning one acknowledgement per
+    /// processed command in the same order as the batch. Hosts that support batching receive the
+    /// whole batch in a single request. If no host is listening on the batch subject (i.e. the
+    /// host predates batching), the commands are transparently sent one at a time instead, which
+    /// preserves ordering but costs one round trip per command.
+    ///
+    /// A command that fails to send in sequential mode is recorded as a non-accepted
+    /// acknowledgement. When [`CommandBatch::stop_on_failure`] is set, processing stops at the first
+    /// command that is not accepted, and the returned list will be shorter than the batch.
+    /// Otherwise the remaining commands are still sent
+    #[instrument(level = "debug", skip_all)]
+    pub async fn send_batch(
+        &self,
+        host_id: &str,
+        batch: CommandBatch,
+    ) -> Result<Vec<CtlOperationAck>> {
+        self.send_batch_inner(host_id, batch, Deadline::unbounded())
+            .await
+            .map(|report| report.acks)
+    }
+
+    /// Same as [`send_batch`](Client::send_batch), but the whole batch must complete within
+    /// `deadline`. Each request uses the smaller of the client timeout and the time left, and once
+    /// the deadline has passed no further commands are sent. The returned report holds the
+    /// acknowledgements received so far, lists the commands that were never attempted and says
+    /// whether the deadline was exceeded, including when it cut a request short. A batch sent to
+    /// a batching host that doesn't answer before the deadline is reported with no
+    /// acknowledgements. An error is only returned if nothing could be sent
+    #[instrument(level = "debug", skip_all)]
+    pub async fn send_batch_with_deadline(
+        &self,
+        host_id: &str,
+        batch: CommandBatch,
+        deadline: Duration,
+    ) -> Result<BatchReport> {
+        self.send_batch_inner(host_id, batch, Deadline::after(deadline))
+            .await
+    }
+
+    async fn send_batch_inner(
+        &self,
+        host_id: &str,
+        mut batch: CommandBatch,
+        deadline: Deadline,
+    ) -> Result<BatchReport> {
+        self.ensure_writable("send_batch")?;
+        validate::host_id(host_id)?;
+        batch.assign_command_ids();
+        let Some(timeout) = deadline.timeout(self.timeout) else {
+            return Ok(BatchReport {
+                not_attempted: batch.commands,
+                deadline_exceeded: true,
+                ..Default::default()
+            });
+        };
+        let subject = broker::commands::batch(&self.topic_prefix, &self.lattice_prefix, host_id);
+        debug!("send_batch:request {}", &subject);
+        let bytes = self.codec.serialize(&batch)?;
+        match self.host_request(host_id, subject, bytes, timeout).await {
+            Ok(msg) => {
+                let resp: CommandBatchResponse = codec::decode(&msg)?;
+                Ok(BatchReport {
+                    acks: resp.acks,
+                    ..Default::default()
+                })
+            }
+            Err(Error::NoResponders { .. }) => {
+                debug!("host {host_id} does not support batching, sending commands sequentially");
+                self.send_batch_sequential(host_id, batch, deadline).await
+            }
+            Err(Error::Timeout { .. }) if deadline.expired() => Ok(BatchReport {
+                deadline_exceeded: true,
+                ..Default::default()
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn send_batch_sequential(
+        &self,
+        host_id: &str,
+        batch: CommandBatch,
+        deadline: Deadline,
+    ) -> Result<BatchReport> {
+        let mut report = BatchReport {
+            acks: Vec::with_capacity(batch.commands.len()),
+            ..Default::default()
+        };
+        let mut commands = batch.commands.into_iter();
+        while let Some(command) = commands.next() {
+            let Some(timeout) = deadline.timeout(self.timeout) else {
+                report.not_attempted.push(command);
+                report.not_attempted.extend(commands);
+                report.deadline_exceeded = true;
+                break;
+            };
+            let ack = match self.send_batch_command(host_id, command, timeout).await {
+                Ok(ack) => ack,
+                Err(e) => {
+                    // A request cut short by the deadline still counts as the deadline firing,
+                    // even if it was the last command in the batch
+                    report.deadline_exceeded |= deadline.expired();
+                    CtlOperationAck {
+                        accepted: false,
+                        error: e.to_string(),
+                    }
+                }
+            };
+            let accepted = ack.accepted;
+            report.acks.push(ack);
+            if !accepted && batch.stop_on_failure {
+                break;
+            }
+        }
+        Ok(report)
+    }
+
+    async fn send_batch_command(
+        &self,
+        host_id: &str,
+        command: BatchCommand,
+        timeout: Duration,
+    ) -> Result<CtlOperationAck> {
+        let (subject, bytes) = match command {
+            BatchCommand::ScaleActor(cmd) => (
+                broker::commands::scale_actor(&self.topic_prefix, &self.lattice_prefix, host_id),
+                self.codec.serialize(cmd)?,
+            ),
+            BatchCommand::StopActor(cmd) => (
+                broker::commands::stop_actor(&self.topic_prefix, &self.lattice_prefix, host_id),
+                self.codec.serialize(cmd)?,
+            ),
+            BatchCommand::UpdateActor(cmd) => (
+                broker::commands::update_actor(&self.topic_prefix, &self.lattice_prefix, host_id),
+                self.codec.serialize(cmd)?,
+            ),
+            BatchCommand::StartProvider(cmd) => (
+                broker::commands::start_provider(&self.topic_prefix, &self.lattice_prefix, host_id),
+                self.codec.serialize(cmd)?,
+            ),
+            BatchCommand::StopProvider(cmd) => (
+                broker::commands::stop_provider(&self.topic_prefix, &self.lattice_prefix, host_id),
+                self.codec.serialize(cmd)?,
+            ),
+        };
+        debug!("send_batch:request {}", &subject);
+        let msg = self.host_request(host_id, subject, bytes, timeout).await?;
+        codec::decode(&msg)
+    }
+
+    async fn publish_and_wait<D: DeserializeOwned + Send + 'static>(
+        &self,
+        subject: String,
+        payload: Vec<u8>,
+        options: &AuctionOptions,
+    ) -> Result<Vec<D>> {
+        let receiver = self
+            .publish_and_stream(subject, payload, options, self.retry.query_retries())
+            .await?;
+        Ok(collect_receiver(receiver).await)
+    }
+
+    /// Like `publish_and_wait`, but sends each response to the returned receiver as it arrives.
+    /// Dropping the receiver stops gathering and unsubscribes from the reply inbox. If nothing
+    /// answers within the window, the request is published again up to `retries` times
+    async fn publish_and_stream<D: DeserializeOwned + Send + 'static>(
+        &self,
+        subject: String,
+        payload: Vec<u8>,
+        options: &AuctionOptions,
+        retries: u32,
+    ) -> Result<Receiver<D>> {
+        use futures::StreamExt as _;
+        let sub = self
+            .publish_with_inbox(subject.clone(), payload.clone())
+            .await?;
+        let (sender, receiver) = tokio::sync::mpsc::channel(sub_stream::RESULT_BUFFER);
+        let window = options.window.unwrap_or(self.auction_timeout);
+        let (min_results, idle_gap) = (options.min_results, options.idle_gap);
+        let client = self.clone();
+        tokio::spawn(async move {
+            let mut sub = sub;
+            let mut published = std::time::Instant::now();
+            let mut retry = 0;
+            loop {
+                let interceptor = &client.interceptor;
+                let sent = sub_stream::forward_timeout(
+                    (&mut sub).map(|msg| {
+                        interceptor.response(&subject, &msg.payload, published.elapsed());
+                        msg
+                    }),
+                    window,
+                    min_results,
+                    idle_gap,
+                    subject.as_str(),
+                    sender.clone(),
+                )
+                .await;
+                let _ = sub.unsubscribe().await;
+                if sent > 0 || sender.is_closed() || retry >= retries {
+                    break;
+                }
+                let delay = client.retry.delay(retry);
+                debug!(%subject, ?delay, "nothing answered, retrying");
+                tokio::time::sleep(delay).await;
+                retry += 1;
+                sub = match client
+                    .publish_with_inbox(subject.clone(), payload.clone())
+                    .await
+                {
+                    Ok(sub) => sub,
+                    Err(error) => {
+                        error!(%subject, %error, "failed to retry request");
+                        break;
+                    }
+                };
+                published = std::time::Instant::now();
+            }
+        });
+        Ok(receiver)
+    }
+
+    /// Creates a reply inbox under the client's inbox prefix, if it has one
+    fn new_inbox(&self) -> String {
+        let inbox = self.nc.new_inbox();
+        match &self.inbox_prefix {
+            // The connection's inbox ends in a unique token, which is kept under our prefix
+            Some(prefix) => broker::inbox(prefix, inbox.rsplit('.').next().unwrap_or_default()),
+            None => inbox,
+        }
+    }
+
+    /// Publishes `payload` with a fresh reply inbox, returning the subscription to that inbox
+    async fn publish_with_inbox(
+        &self,
+        subject: String,
+        payload: Vec<u8>,
+    ) -> Result<async_nats::Subscriber> {
+        self.validate_prefixes()?;
+        self.interceptor.request(&subject, &payload);
+        let reply = self.new_inbox();
+        let published = async {
+            let sub = self
+                .nc
+                .subscribe(reply.clone())
+                .await
+                .map_err(Error::nats)?;
+            self.nc
+                .publish_with_reply_and_headers(
+                    subject.clone(),
+                    reply,
+                    self.codec.headers(),
+                    payload.into(),
+                )
+                .await
+                .map_err(Error::nats)?;
+            Ok(sub)
+        };
+        let sub = published
+            .await
+            .inspect_err(|e| self.interceptor.error(&subject, e))?;
+        let nc = self.nc.clone();
+        tokio::spawn(async move {
+            if let Err(error) = nc.flush().await {
+                error!(%error, "flush after publish");
+            }
+        });
+        Ok(sub)
+    }
+
+    /// Returns the generation marker of the lattice metadata bucket. The marker is written the
+    /// first time a client writes to the bucket and changes whenever the bucket is deleted and
+    /// recreated. Returns `None` if there is no bucket or nothing has written a marker yet
+    #[instrument(level = "debug", skip_all)]
+    pub async fn lattice_generation(&self) -> Result<Option<String>> {
+        match self.kv_store().await {
+            Some(store) => kv::get_generation(&store).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Writes a generation marker to the lattice metadata bucket if it doesn't already have one,
+    /// returning the bucket's generation. Tools that provision lattices can call this right after
+    /// creating the bucket; otherwise the marker is written on the first write by any client.
+    /// Returns `None` if there is no bucket
+    #[instrument(level = "debug", skip_all)]
+    pub async fn ensure_lattice_generation(&self) -> Result<Option<String>> {
+        self.ensure_writable("ensure_lattice_generation")?;
+        match self.kv_store().await {
+            Some(store) => Ok(Some(kv::ensure_generation(&store).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Creates the lattice metadata bucket in the client's JetStream domain if it doesn't exist yet,
+    /// returning true if this call created it. Run this when standing up a new lattice so that
+    /// hosts and clients use the bucket from the start, rather than keeping state on the legacy
+    /// topics that is lost once they switch to it. An existing bucket is left as it is, so calling
+    /// this again, or from several clients at once, is harmless
+    #[instrument(level = "debug", skip_all)]
+    pub async fn ensure_lattice_metadata_bucket(&self, config: BucketConfig) -> Result<bool> {
+        self.ensure_writable("ensure_lattice_metadata_bucket")?;
+        let (store, created) = kv::create_kv_store(
+            self.nc.clone(),
+            &self.lattice_prefix,
+            self.js_domain.clone(),
+            &config,
+        )
+        .await?;
+        kv::ensure_generation(&store).await?;
+        if self.kv_mode != KvMode::Disabled {
+            *self.kvstore.lock().await = Some(store);
+        }
+        Ok(created)
+    }
+
+    /// Copies the link definitions the hosts report over the legacy topic into the lattice metadata
+    /// bucket, for a lattice that is moving to the bucket. Links the bucket already has are left
+    /// alone, so the migration can be run again safely. Fails with [`RequiresKv`] if there is no
+    /// bucket; see [`Client::ensure_lattice_metadata_bucket`]
+    #[instrument(level = "debug", skip_all)]
+    pub async fn migrate_topic_links_to_kv(&self) -> Result<LinkMigration> {
+        self.ensure_writable("migrate_topic_links_to_kv")?;
+        let store = self.kv_store_for_write("migrate_topic_links_to_kv").await?;
+        let mut migration = LinkMigration::default();
+        let links = self
+            .query_topic_links(std::time::Instant::now())
+            .await?
+            .into_data();
+        for link in links {
+            if kv::put_link_if_absent(&store, &link).await? {
+                migration.copied += 1;
+            } else {
+                migration.already_present += 1;
+            }
+        }
+        debug!(
+            copied = migration.copied,
+            already_present = migration.already_present,
+            "migrated links to the lattice metadata bucket"
+        );
+        Ok(migration)
+    }
+
+    /// Works out what [`Client::purge_host`] would remove for the host whose last known inventory
+    /// is `inventory`, without removing anything. See [`Client::purge_host`]
+    #[instrument(level = "debug", skip_all)]
+    pub async fn purge_host_plan(&self, inventory: &HostInventory) -> Result<PurgePlan> {
+        let store = self
+            .kv_store()
+            .await
+            .ok_or_else(|| self.requires_kv("purge_host_plan"))?;
+        self.plan_purge(&store, inventory).await
+    }
+
+    /// Removes what a host that died without cleaning up after itself left behind in the lattice
+    /// metadata bucket: the claims of the actors and providers in its last known `inventory` that
+    /// don't run on any other host, and the links to those providers. The inventory can come from an earlier
+    /// [`Client::get_host_inventory`] or [`Client::get_all_inventories`].
+    ///
+    /// Every other host's inventory is queried first, and nothing is removed if any of them can't
+    /// be retrieved, or if the host itself still answers, since either would make it impossible to
+    /// tell what is still in use; a host that still answers fails with [`Error::InvalidArgument`].
+    /// Links to providers that never ran on the host are left alone, including links defined
+    /// ahead of a provider that hasn't started yet. Returns what was removed. Fails with
+    /// [`RequiresKv`] if there is no bucket, as there is nothing to purge for a lattice that uses
+    /// the legacy topics
+    #[instrument(level = "debug", skip_all)]
+    pub async fn purge_host(&self, inventory: &HostInventory) -> Result<PurgePlan> {
+        self.ensure_writable("purge_host")?;
+        let store = self.kv_store_for_write("purge_host").await?;
+        let plan = self.plan_purge(&store, inventory).await?;
+        for subject in &plan.claims {
+            debug!("purge_host:claims {}", subject);
+            kv::delete_claims(&store, subject).await?;
+        }
+        for link in &plan.links {
+            debug!(
+                "purge_host:link {} {} {}",
+                link.actor_id, link.contract_id, link.link_name
+            );
+            kv::delete_link(&store, &link.actor_id, &link.contract_id, &link.link_name).await?;
+        }
+        Ok(plan)
+    }
+
+    async fn plan_purge(&self, store: &Store, inventory: &HostInventory) -> Result<PurgePlan> {
+        validate::host_id(&inventory.host_id)?;
+        let mut others = Vec::new();
+        for (host, other) in self.get_all_inventories().await? {
+            if host.id == inventory.host_id {
+                return Err(Error::InvalidArgument {
+                    argument: "inventory",
+                    reason: format!("host {} is still responding and can't be purged", host.id),
+                });
+            }
+            others.push(other?);
+        }
+        let claims = kv::get_claims(store).await?;
+        let links = kv::get_links(store, &LinkFilter::new()).await?;
+        Ok(purge::plan(inventory, &others, &claims, &links))
+    }
+
+    /// Compares the current generation of the lattice metadata bucket against the one observed by
+    /// the previous check. If it differs, any state this client holds about the bucket is flushed
+    /// and the change is returned. The first check made by a client only records the generation,
+    /// as does the first check to find a marker after the bucket was missing or had none, so a
+    /// recreated bucket is reported once however its marker comes to be written
+    #[instrument(level = "debug", skip_all)]
+    pub async fn check_lattice_generation(&self) -> Result<Option<GenerationChanged>> {
+        // Always look the bucket up again rather than trusting the cached handle, since the cached
+        // bucket may be the one that was deleted
+        let store = kv::get_kv_store(
+            self.nc.clone(),
+            &self.lattice_prefix,
+            self.js_domain.clone(),
+        )
+        .await;
+        let current = match &store {
+            Some(store) => kv::get_generation(store).await?,
+            None => None,
+        };
+        let previous = self
+            .generation
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .replace(current.clone());
+        let Some(previous) = previous else {
+            return Ok(None);
+        };
+        if previous != current {
+            *self.kvstore.lock().await = store;
+        }
+        let change = kv::generation_change(previous, &current);
+        if let Some(change) = &change {
+            debug!(?change.previous, ?change.current, "lattice generation changed");
+        }
+        Ok(change)
+    }
+
+    /// Returns the receiver end of a channel that is notified whenever the lattice metadata bucket
+    /// is recreated. The generation is checked every `interval` while the NATS connection is up,
+    /// so a client that reconnects after the bucket was reset is notified on the next check.
+    /// Dropping the receiver stops the background checks
+    pub async fn generation_changes_receiver(
+        &self,
+        interval: Duration,
+    ) -> Result<Receiver<GenerationChanged>> {
+        use async_nats::connection::State;
+
+        let (sender, receiver) = tokio::sync::mpsc::channel(16);
+        // Record the current generation so that only changes from here on are reported
+        self.check_lattice_generation().await?;
+        let client = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = sender.closed() => break,
+                }
+                if client.nc.connection_state() != State::Connected {
+                    continue;
+                }
+                match client.check_lattice_generation().await {
+                    Ok(Some(changed)) => {
+                        if sender.send(changed).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(error) => warn!(%error, "failed to check lattice generation"),
+                }
+            }
+        });
+        Ok(receiver)
+    }
+
+    /// Returns the receiver end of a channel that is sent every change to the link definitions in
+    /// the lattice metadata bucket from now on. Fails with [`Error::RequiresKv`] if the bucket
+    /// doesn't exist, since there is nothing to watch. Dropping the receiver stops the watch
+    #[instrument(level = "debug", skip_all)]
+    pub async fn link_changes_receiver(&self) -> Result<Receiver<LinkChange>> {
+        let store = self
+            .kv_store()
+            .await
+            .ok_or_else(|| self.requires_kv("link_changes_receiver"))?;
+        kv::watch_links(store).await
+    }
+
+    /// Returns the receiver end of a channel that subscribes to the lattice control event stream.
+    /// Any [`Event`](struct@Event)s that are published after this channel is created
+    /// will be added to the receiver channel's buffer, which can be observed or handled if needed.
+    /// See the example for how you could use this receiver to handle events.
+    ///
+    /// # Example
+    /// ```rust
+    /// use wasmcloud_control_interface::{Client, ClientBuilder};
+    /// async {
+    ///   let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+    ///   let client = ClientBuilder::new(nc)
+    ///                 .rpc_timeout(std::time::Duration::from_millis(1000))
+    ///                 .auction_timeout(std::time::Duration::from_millis(1000))
+    ///                 .build();
+    ///   let mut receiver = client.events_receiver().await.unwrap();
+    ///   tokio::spawn( async move {
+    ///       while let Some(evt) = receiver.recv().await {
+    ///           println!("Event received: {:?}", evt);
+    ///       }
+    ///   });
+    ///   // perform other operations on client
+    ///   client.get_host_inventory("NAEXHW...").await.unwrap();
+    /// };
+    /// ```
+    ///
+    /// Once you're finished with the event receiver, be sure to call `drop` with the receiver
+    /// as an argument. This closes the channel and will prevent the sender from endlessly
+    /// sending messages into the channel buffer.
+    ///
+    /// # Example
+    /// ```rust
+    /// use wasmcloud_control_interface::{Client, ClientBuilder};
+    /// async {
+    ///   let nc = async_nats::connect("0.0.0.0:4222").await.unwrap();
+    ///   let client = ClientBuilder::new(nc)
+    ///                 .rpc_timeout(std::time::Duration::from_millis(1000))
+    ///                 .auction_timeout(std::time::Duration::from_millis(1000))
+    ///                 .build();    
+    ///   let mut receiver = client.events_receiver().await.unwrap();
+    ///   // read the docs for flume receiver. You can use it in either sync or async code
+    ///   // The receiver can be cloned() as needed.
+    ///   // If you drop the receiver. The subscriber will exit
+    ///   // If the nats connection ic closed, the loop below will exit.
+    ///   while let Some(evt) = receiver.recv().await {
+    ///       println!("Event received: {:?}", evt);
+    ///   }
+    /// };
+    /// ```
+    pub async fn events_receiver(&self) -> Result<Receiver<Event>> {
+        self.subscribe_events(Some).await
+    }
+
+    /// Like [`Client::events_receiver`], but only events matching `filter` are sent to the
+    /// receiver. The filter is applied as events arrive, so unwanted events (such as heartbeats in
+    /// a busy lattice) never take up space in the channel buffer. A filter that matches nothing
+    /// still returns a receiver, which simply never produces an event
+    pub async fn events_receiver_filtered(&self, filter: EventFilter) -> Result<Receiver<Event>> {
+        self.subscribe_events(move |evt| filter.matches(&evt).then_some(evt))
+            .await
+    }
+
+    /// Like [`Client::events_receiver`], but each event's data is parsed into the
+    /// [`LatticeEvent`] variant matching its type, along with the ID of the host that published it
+    /// and when. Events of an unknown type, or whose data doesn't match their type, are delivered
+    /// as [`LatticeEvent::Other`] rather than dropped
+    pub async fn typed_events_receiver(&self) -> Result<Receiver<LatticeEvent>> {
+        self.subscribe_events(|evt| Some(LatticeEvent::from(evt)))
+            .await
+    }
+
+    /// Like [`Client::events_receiver`], but events are consumed through the durable JetStream
+    /// consumer with the given name on the stream set with [`ClientBuilder::event_stream`], so a
+    /// service that restarts picks up where it left off instead of missing the events published
+    /// while it was down. The consumer is created the first time, filtered to this lattice's
+    /// events and starting from the oldest one in the stream, and attached to from then on.
+    ///
+    /// Each event is acknowledged once it has been taken from the receiver, so an event that was
+    /// still buffered when the service stopped is delivered again once the consumer's ack wait
+    /// runs out. Fails with [`Error::StreamNotFound`] if the stream doesn't exist, as how long
+    /// events are kept is up to whoever deploys the lattice. Consuming events doesn't change
+    /// anything in the lattice, so it is allowed on read-only clients
+    #[instrument(level = "debug", skip_all)]
+    pub async fn durable_events_receiver(&self, consumer_name: &str) -> Result<Receiver<Event>> {
+        use async_nats::jetstream::consumer::{pull, AckPolicy};
+        use async_nats::jetstream::context::GetStreamErrorKind;
+        use async_nats::jetstream::{AckKind, ErrorCode};
+        use futures::StreamExt as _;
+        validate::jetstream_name("stream name", &self.event_stream)?;
+        validate::jetstream_name("consumer name", consumer_name)?;
+        let stream = self
+            .jetstream()
+            .get_stream(&self.event_stream)
+            .await
+            .map_err(|e| match e.kind() {
+                GetStreamErrorKind::JetStream(error)
+                    if error.error_code() == ErrorCode::STREAM_NOT_FOUND =>
+                {
+                    Error::StreamNotFound {
+                        stream: self.event_stream.clone(),
+                    }
+                }
+                _ => Error::nats(e),
+            })?;
+        debug!(
+            "durable_events_receiver:consumer {} {}",
+            self.event_stream, consumer_name
+        );
+        let consumer = stream
+            .get_or_create_consumer(
+                consumer_name,
+                pull::Config {
+                    durable_name: Some(consumer_name.to_string()),
+                    filter_subject: broker::control_event(&self.lattice_prefix),
+                    ack_policy: AckPolicy::Explicit,
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(Error::nats)?;
+        let mut messages = consumer.messages().await.map_err(Error::nats)?;
+        let (sender, receiver) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(async move {
+            let Ok(mut permit) = sender.reserve().await else {
+                return;
+            };
+            while let Some(msg) = messages.next().await {
+                let msg = match msg {
+                    Ok(msg) => msg,
+                    Err(error) => {
+                        warn!(%error, "failed to receive event from stream");
+                        continue;
+                    }
+                };
+                // An event that can't be parsed is acknowledged anyway, as redelivering it won't
+                // help. Otherwise the channel only has room again once the event has been taken
+                // from the receiver, so that is when it is acknowledged. If the receiver was
+                // dropped instead, the event is handed straight back to the stream for whoever
+                // attaches next
+                if let Some(evt) = parse_event(&msg.payload) {
+                    permit.send(evt);
+                    permit = match sender.reserve().await {
+                        Ok(permit) => permit,
+                        Err(_) => {
+                            let _ = msg.ack_with(AckKind::Nak(None)).await;
+                            break;
+                        }
+                    };
+                }
+                if let Err(error) = msg.ack().await {
+                    warn!(%error, "failed to acknowledge event");
+                }
+            }
+        });
+        Ok(receiver)
+    }
+
+    /// Tracks which hosts in the lattice are alive from their heartbeats and host started and
+    /// stopped events, sending a [`HostStatusUpdate`] to the returned receiver whenever a host
+    /// comes up, stops, or goes `stale_after` without being heard from. Only hosts heard from
+    /// after this is called are tracked, so hosts that are already running are reported with
+    /// their next heartbeat. Dropping the receiver stops the tracking and unsubscribes
+    #[instrument(level = "debug", skip_all)]
+    pub async fn host_status_receiver(
+        &self,
+        stale_after: Duration,
+    ) -> Result<Receiver<HostStatusUpdate>> {
+        use futures::StreamExt as _;
+        let mut sub = self
+            .nc
+            .subscribe(broker::control_event(&self.lattice_prefix))
+            .await
+            .map_err(Error::nats)?;
+        let (sender, receiver) = tokio::sync::mpsc::channel(sub_stream::RESULT_BUFFER);
+        tokio::spawn(async move {
+            host_status::track((&mut sub).map(|msg| msg.payload), stale_after, sender).await;
+            let _ = sub.unsubscribe().await;
+        });
+        Ok(receiver)
+    }
+
+    /// Waits up to `timeout` for a heartbeat from the given host, returning the host as described
+    /// by the heartbeat, e.g. to find out when a host that was just launched has joined the
+    /// lattice. Hosts publish a heartbeat as they start and then periodically, so for a host that
+    /// is already running `timeout` needs to cover the heartbeat interval
+    #[instrument(level = "debug", skip_all)]
+    pub async fn await_host(&self, host_id: &str, timeout: Duration) -> Result<Host> {
+        use futures::StreamExt as _;
+        let subject = broker::control_event(&self.lattice_prefix);
+        let mut sub = self
+            .nc
+            .subscribe(subject.clone())
+            .await
+            .map_err(Error::nats)?;
+        let payloads = (&mut sub).map(|msg| msg.payload);
+        let heartbeats =
+            wait::for_events(
+                payloads,
+                &subject,
+                host_id,
+                1,
+                timeout,
+                |event| match event {
+                    LatticeEvent::HostHeartbeat(heartbeat) => Some(wait::Outcome::Done(heartbeat)),
+                    _ => None,
+                },
+            )
+            .await;
+        let _ = sub.unsubscribe().await;
+        let heartbeat = heartbeats?.pop().expect("waited for one heartbeat");
+        Ok(host_status::heartbeat_host(heartbeat))
+    }
+
+    /// Like [`Client::events_receiver`], but with a buffer of `buffer` events, which must not be
+    /// zero, and `policy` deciding what happens to events that arrive while it is full. With
+    /// [`OverflowPolicy::Block`] this behaves like [`Client::events_receiver`]; the other policies
+    /// keep the subscription keeping up with NATS however slow the consumer is, at the cost of
+    /// dropping events, which the returned receiver counts
+    pub async fn events_receiver_with_policy(
+        &self,
+        buffer: usize,
+        policy: OverflowPolicy,
+    ) -> Result<EventReceiver> {
+        use futures::StreamExt as _;
+        if buffer == 0 {
+            return Err(Error::InvalidArgument {
+                argument: "buffer",
+                reason: "must hold at least one event".to_string(),
+            });
+        }
+        let mut sub = self.event_subscription().await?;
+        let (sender, receiver) = event_receiver::channel(buffer, policy);
+        tokio::spawn(async move {
+            while let Some(msg) = sub.next().await {
+                let Some(evt) = parse_event(&msg.payload) else {
+                    continue;
+                };
+                if !sender.send(evt).await {
+                    let _ = sub.unsubscribe().await;
+                    break;
+                }
+            }
+        });
+        Ok(receiver)
+    }
+
+    /// Subscribes to the lattice control event stream, in the client's event queue group if it
+    /// has one
+    async fn event_subscription(&self) -> Result<async_nats::Subscriber> {
+        let subject = broker::control_event(&self.lattice_prefix);
+        match &self.event_queue_group {
+            Some(group) => {
+                validate::queue_group(group)?;
+                self.nc.queue_subscribe(subject, group.clone()).await
+            }
+            None => self.nc.subscribe(subject).await,
+        }
+        .map_err(Error::nats)
+    }
+
+    /// Subscribes to the lattice control event stream, passing each CloudEvent through `map`
+    /// before it is sent to the returned receiver. Events `map` returns `None` for are skipped
+    async fn subscribe_events<T: Send + 'static>(
+        &self,
+        map: impl Fn(Event) -> Option<T> + Send + 'static,
+    ) -> Result<Receiver<T>> {
+        use futures::StreamExt as _;
+        let (sender, receiver) = tokio::sync::mpsc::channel(5000);
+        let mut sub = self.event_subscription().await?;
+        tokio::spawn(async move {
+            while let Some(msg) = sub.next().await {
+                let Some(evt) = parse_event(&msg.payload) else {
+                    continue;
+                };
+                let Some(item) = map(evt) else {
+                    // Skipped events never reach `send`, so notice a dropped receiver here
+                    if sender.is_closed() {
+                        let _ = sub.unsubscribe().await;
+                        break;
+                    }
+                    continue;
+                };
+                // If the channel is disconnected, stop sending events
+                if sender.send(item).await.is_err() {
+                    let _ = sub.unsubscribe().await;
+                    break;
+                }
+            }
+        });
+        Ok(receiver)
+    }
+
+    /// Republishes every control event for this lattice into the named JetStream stream, creating
+    /// the stream with the retention settings in `config` if it doesn't exist. Each event is
+    /// published with its CloudEvent ID as the JetStream message ID, so an event received more than
+    /// once (for example by two mirrors running side by side) is only stored once. Events are
+    /// mirrored for as long as the returned handle is alive.
+    ///
+    /// Mirroring doesn't change anything in the lattice, so it is allowed on read-only clients
+    #[instrument(level = "debug", skip_all)]
+    pub async fn mirror_events_to_stream(
+        &self,
+        stream_name: &str,
+        config: MirrorConfig,
+    ) -> Result<MirrorHandle> {
+        let context = self.jetstream();
+        let subject = config
+            .subject
+            .clone()
+            .unwrap_or_else(|| format!("{stream_name}.{}", self.lattice_prefix));
+        debug!("mirror_events_to_stream:publish {}", &subject);
+        // Subscribe first so that events published while the stream is being created are kept
+        let events = self
+            .nc
+            .subscribe(broker::control_event(&self.lattice_prefix))
+            .await
+            .map_err(Error::nats)?;
+        mirror::start(context, events, stream_name, subject, config).await
+    }
+
+    /// A JetStream context in the client's JetStream domain
+    fn jetstream(&self) -> async_nats::jetstream::Context {
+        match &self.js_domain {
+            Some(domain) => async_nats::jetstream::with_domain(self.nc.clone(), domain),
+            None => async_nats::jetstream::new(self.nc.clone()),
+        }
+    }
+}
+
+/// Turns the outcome of one link in a bulk link operation into its acknowledgement
+fn link_ack(result: Result<CtlOperationAck>) -> CtlOperationAck {
+    result.unwrap_or_else(negative_ack)
+}
+
+/// Records a command that failed as a negative acknowledgement, for operations that report each
+/// command's outcome rather than stopping at the first failure. A rejection on a client that
+/// errors on them keeps just the host's reason, as it would have been acknowledged otherwise
+fn negative_ack(e: Error) -> CtlOperationAck {
+    CtlOperationAck {
+        accepted: false,
+        error: match e {
+            Error::NotAccepted(rejected) => rejected.error,
+            e => e.to_string(),
+        },
+    }
+}
+
+/// Parses a message from the control event stream, logging and skipping anything that isn't a
+/// CloudEvent
+fn parse_event(payload: &[u8]) -> Option<Event> {
+    match json_deserialize::<Event>(payload) {
+        Ok(evt) => {
+            trace!("received event: {:?}", evt);
+            Some(evt)
+        }
+        Err(_) => {
+            error!("Object received on event stream was not a CloudEvent");
+            None
+        }
+    }
+}
+
+/// Reads everything sent to `receiver` until its sender is dropped
+async fn collect_receiver<T>(mut receiver: Receiver<T>) -> Vec<T> {
+    let mut items = Vec::new();
+    while let Some(item) = receiver.recv().await {
+        items.push(item);
+    }
+    items
+}
+
+/// Helper function that serializes the data and maps the error
+fn json_serialize<T>(item: T) -> Result<Vec<u8>>
+where
+    T: Serialize,
+{
+    serde_json::to_vec(&item).map_err(Error::Serialization)
+}
+
+/// Helper function that deserializes the data and maps the error
+fn json_deserialize<'de, T: Deserialize<'de>>(buf: &'de [u8]) -> Result<T> {
+    serde_json::from_slice(buf).map_err(Error::Serialization)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_host::{FakeHost, FakeHostConfig};
+    use std::time::Duration;
+
+    /// Note: This test is a means of manually watching the event stream as CloudEvents are received
+    /// It does not assert functionality, and so we've marked it as ignore to ensure it's not run by default
+    /// It currently listens for 120 seconds then exits
+    #[tokio::test]
+    #[ignore]
+    async fn test_events_receiver() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let client = ClientBuilder::new(nc)
+            .timeout(Duration::from_millis(1000))
+            .auction_timeout(Duration::from_millis(1000))
+            .build();
+        let mut receiver = client.events_receiver().await.unwrap();
+        tokio::spawn(async move {
+            while let Some(evt) = receiver.recv().await {
+                println!("Event received: {:?}", evt);
+            }
+        });
+        println!("Listening to Cloud Events for 120 seconds. Then we will quit.");
+        tokio::time::sleep(std::time::Duration::from_secs(120)).await;
+    }
+
+    /// Note: This test requires a local NATS server
+    #[tokio::test]
+    #[ignore]
+    async fn test_event_queue_group_shares_events() {
+        use cloudevents::EventBuilder as _;
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let replica = || {
+            ClientBuilder::new(nc.clone())
+                .lattice_prefix("queuetest")
+                .event_queue_group("monitors")
+                .build()
+        };
+        let mut first = replica().events_receiver().await.unwrap();
+        let mut second = replica().events_receiver().await.unwrap();
+        nc.flush().await.unwrap();
+
+        for id in 0..20 {
+            let event = cloudevents::EventBuilderV10::new()
+                .id(id.to_string())
+                .source("NHOST")
+                .ty(LatticeEvent::HOST_HEARTBEAT)
+                .data("application/json", serde_json::json!({}))
+                .build()
+                .unwrap();
+            nc.publish(
+                broker::control_event("queuetest"),
+                serde_json::to_vec(&event).unwrap().into(),
+            )
+            .await
+            .unwrap();
+        }
+        nc.flush().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut received = 0;
+        for receiver in [&mut first, &mut second] {
+            while receiver.try_recv().is_ok() {
+                received += 1;
+            }
+        }
+        assert_eq!(received, 20, "each event should go to exactly one replica");
+    }
+
+    /// Note: This test requires a local NATS server with JetStream enabled
+    #[tokio::test]
+    #[ignore]
+    async fn test_durable_events_are_redelivered_after_a_restart() {
+        use cloudevents::{AttributesReader as _, EventBuilder as _};
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let js = async_nats::jetstream::new(nc.clone());
+        let stream = js
+            .get_or_create_stream(async_nats::jetstream::stream::Config {
+                name: "durabletest_events".to_string(),
+                subjects: vec![broker::control_event("durabletest")],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let _ = stream.delete_consumer("monitor").await;
+        stream.purge().await.unwrap();
+        // Created up front with a short ack wait, so the test doesn't wait the default 30 seconds
+        // for redelivery. The client attaches to it as it would to one it created itself
+        stream
+            .create_consumer(async_nats::jetstream::consumer::pull::Config {
+                durable_name: Some("monitor".to_string()),
+                filter_subject: broker::control_event("durabletest"),
+                ack_policy: async_nats::jetstream::consumer::AckPolicy::Explicit,
+                ack_wait: Duration::from_secs(1),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        for id in 0..3 {
+            let event = cloudevents::EventBuilderV10::new()
+                .id(id.to_string())
+                .source("NHOST")
+                .ty(LatticeEvent::HOST_HEARTBEAT)
+                .data("application/json", serde_json::json!({}))
+                .build()
+                .unwrap();
+            js.publish(
+                broker::control_event("durabletest"),
+                serde_json::to_vec(&event).unwrap().into(),
+            )
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        }
+
+        // Crash after taking the first event, with the second buffered: dropping the runtime
+        // kills the consuming task and the connection without giving anything back to the stream
+        std::thread::spawn(|| {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async {
+                let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+                let client = ClientBuilder::new(nc)
+                    .lattice_prefix("durabletest")
+                    .event_stream("durabletest_events")
+                    .build();
+                let mut receiver = client.durable_events_receiver("monitor").await.unwrap();
+                assert_eq!(receiver.recv().await.unwrap().id(), "0");
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            });
+        })
+        .join()
+        .unwrap();
+
+        let client = ClientBuilder::new(nc.clone())
+            .lattice_prefix("durabletest")
+            .event_stream("durabletest_events")
+            .build();
+        let mut receiver = client.durable_events_receiver("monitor").await.unwrap();
+        let mut ids = Vec::new();
+        while ids.len() < 2 {
+            let event = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+                .await
+                .expect("the unacknowledged events should be redelivered")
+                .unwrap();
+            ids.push(event.id().to_string());
+        }
+        assert_eq!(ids, ["1", "2"]);
+
+        let missing = ClientBuilder::new(nc)
+            .event_stream("no_such_events")
+            .build()
+            .durable_events_receiver("monitor")
+            .await;
+        assert!(
+            matches!(&missing, Err(Error::StreamNotFound { stream }) if stream == "no_such_events"),
+            "{missing:?}"
+        );
+    }
+
+    /// Note: This test requires a local NATS server
+    #[tokio::test]
+    #[ignore]
+    async fn test_retries_requests_that_time_out() {
+        use futures::StreamExt as _;
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let client = ClientBuilder::new(nc.clone())
+            .lattice_prefix("retrytest")
+            .timeout(Duration::from_millis(200))
+            .retries(2)
+            .retry_backoff(Duration::from_millis(50))
+            .build();
+        // Ignores the first two inventory requests, then answers
+        let mut sub = nc
+            .subscribe(broker::queries::host_inventory(&None, "retrytest", "Nxxx"))
+            .await
+            .unwrap();
+        nc.flush().await.unwrap();
+        let responder = tokio::spawn(async move {
+            let mut received = 0;
+            while let Some(msg) = sub.next().await {
+                received += 1;
+                if received == 3 {
+                    let inventory = HostInventory {
+                        host_id: "Nxxx".to_string(),
+                        ..Default::default()
+                    };
+                    let payload = serde_json::to_vec(&inventory).unwrap();
+                    nc.publish(msg.reply.unwrap(), payload.into())
+                        .await
+                        .unwrap();
+                    break;
+                }
+            }
+            received
+        });
+        let inventory = client.get_host_inventory("Nxxx").await.unwrap();
+        assert_eq!(inventory.host_id, "Nxxx");
+        assert_eq!(responder.await.unwrap(), 3);
+    }
+
+    /// A NATS client that never connects, for exercising code paths that must not reach the network
+    pub(crate) async fn offline_nats() -> async_nats::Client {
+        async_nats::ConnectOptions::new()
+            .retry_on_initial_connect()
+            .connect("127.0.0.1:1")
+            .await
+            .unwrap()
+    }
+
+    fn assert_read_only_violation<T: Debug>(res: Result<T>, operation: &str) {
+        let err = res.expect_err("mutating operation should be refused");
+        match err {
+            Error::ReadOnlyViolation(violation) => assert_eq!(violation.operation, operation),
+            other => panic!("expected a read-only violation, got {other}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout_maps_to_timeout() {
+        let client = ClientBuilder::new(offline_nats().await)
+            .timeout(Duration::from_millis(50))
+            .build();
+        match client.get_host_inventory("Nxxx").await.unwrap_err() {
+            Error::Timeout { subject, duration } => {
+                assert_eq!(subject, "wasmbus.ctl.default.get.Nxxx.inv");
+                assert_eq!(duration, Duration::from_millis(50));
+            }
+            other => panic!("expected a timeout, got {other}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interceptor_sees_requests_and_errors() {
+        #[derive(Default)]
+        struct Recorder(std::sync::Mutex<Vec<String>>);
+        impl CtlInterceptor for Recorder {
+            fn on_request(&self, subject: &str, payload: &[u8]) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("{subject} {}", payload.len()));
+                panic!("interceptors can't fail the call");
+            }
+            fn on_error(&self, _subject: &str, err: &Error) {
+                self.0.lock().unwrap().push(err.to_string());
+            }
+        }
+
+        let recorder = Arc::new(Recorder::default());
+        let client = ClientBuilder::new(offline_nats().await)
+            .timeout(Duration::from_millis(20))
+            .with_interceptor(recorder.clone())
+            .build();
+        assert!(matches!(
+            client.get_host_inventory("Nxxx").await,
+            Err(Error::Timeout { .. })
+        ));
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                "wasmbus.ctl.default.get.Nxxx.inv 0",
+                "timed out after 20ms waiting for a response on wasmbus.ctl.default.get.Nxxx.inv"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_ids_are_kept_across_retries() {
+        #[derive(Default)]
+        struct Recorder(std::sync::Mutex<Vec<String>>);
+        impl CtlInterceptor for Recorder {
+            fn on_request(&self, _subject: &str, payload: &[u8]) {
+                let command: ScaleActorCommand = serde_json::from_slice(payload).unwrap();
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(command.command_id.unwrap().to_string());
+            }
+        }
+
+        let recorder = Arc::new(Recorder::default());
+        let client = ClientBuilder::new(offline_nats().await)
+            .timeout(Duration::from_millis(10))
+            .retry_policy(
+                RetryPolicy::new()
+                    .retries(2)
+                    .backoff(Duration::from_millis(1))
+                    .retry_commands(true),
+            )
+            .with_interceptor(recorder.clone())
+            .build();
+        let sent = || std::mem::take(&mut *recorder.0.lock().unwrap());
+
+        assert!(client
+            .scale_actor("Nxxx", "echo", None, None)
+            .await
+            .is_err());
+        let first = sent();
+        assert_eq!(first.len(), 3);
+        assert!(first.iter().all(|id| *id == first[0]));
+
+        assert!(client
+            .scale_actor("Nxxx", "echo", None, None)
+            .await
+            .is_err());
+        assert_ne!(sent()[0], first[0], "each operation should get its own ID");
+
+        assert!(client
+            .scale_actor_with_id("Nxxx", "echo", None, None, "caller-chosen".into())
+            .await
+            .is_err());
+        assert_eq!(sent(), ["caller-chosen"; 3]);
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_hosts_queries_are_retried() {
+        #[derive(Default)]
+        struct Counter(std::sync::Mutex<Vec<String>>);
+        impl CtlInterceptor for Counter {
+            fn on_request(&self, subject: &str, _payload: &[u8]) {
+                self.0.lock().unwrap().push(subject.to_string());
+            }
+        }
+
+        let counter = Arc::new(Counter::default());
+        let client = ClientBuilder::new(offline_nats().await)
+            .auction_timeout(Duration::from_millis(10))
+            .retry_policy(
+                RetryPolicy::new()
+                    .retries(2)
+                    .backoff(Duration::from_millis(1))
+                    .retry_auctions(false),
+            )
+            .with_interceptor(counter.clone())
+            .build();
+        assert!(client.get_hosts().await.unwrap().is_empty());
+        // Turning off auction retries leaves the hosts query alone
+        assert_eq!(
+            *counter.0.lock().unwrap(),
+            ["wasmbus.ctl.default.ping.hosts"; 3]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_commands_get_command_ids() {
+        #[derive(Default)]
+        struct Recorder(std::sync::Mutex<Vec<CommandBatch>>);
+        impl CtlInterceptor for Recorder {
+            fn on_request(&self, _subject: &str, payload: &[u8]) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(serde_json::from_slice(payload).unwrap());
+            }
+        }
+
+        let recorder = Arc::new(Recorder::default());
+        let client = ClientBuilder::new(offline_nats().await)
+            .timeout(Duration::from_millis(10))
+            .with_interceptor(recorder.clone())
+            .build();
+        let caller_chosen = StopActorCommand {
+            actor_ref: "echo".to_string(),
+            host_id: "Nxxx".to_string(),
+            annotations: None,
+            command_id: Some("caller-chosen".into()),
+        };
+        let batch = CommandBatch::new()
+            .push(scale("Nxxx", "echo"))
+            .push(BatchCommand::StopActor(caller_chosen.clone()));
+        assert!(client.send_batch("Nxxx", batch).await.is_err());
+
+        let sent = recorder.0.lock().unwrap().pop().unwrap();
+        match &sent.commands[..] {
+            [BatchCommand::ScaleActor(scale), BatchCommand::StopActor(stop)] => {
+                assert!(scale.command_id.is_some());
+                assert_eq!(stop, &caller_chosen);
+            }
+            other => panic!("unexpected batch {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lattice_snapshot_records_failed_parts() {
+        let client = ClientBuilder::new(offline_nats().await)
+            .timeout(Duration::from_millis(10))
+            .auction_timeout(Duration::from_millis(10))
+            .disable_kv_store()
+            .build();
+        let snapshot = client.get_lattice_snapshot().await.unwrap();
+        assert!(snapshot.captured_at_ms > 0);
+        assert_eq!(snapshot.hosts, Some(Vec::new()));
+        assert!(snapshot.links.is_none() && snapshot.claims.is_none());
+        assert!(snapshot.links_error.unwrap().starts_with("timed out"));
+        assert!(snapshot.claims_error.unwrap().starts_with("timed out"));
+
+        let bad_prefix = ClientBuilder::new(offline_nats().await)
+            .lattice_prefix("a.b")
+            .build();
+        assert!(matches!(
+            bad_prefix.get_lattice_snapshot().await,
+            Err(Error::InvalidArgument { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_label_keys_are_refused_before_publishing() {
+        let client = ClientBuilder::new(offline_nats().await)
+            .timeout(Duration::from_secs(60))
+            .build();
+        let err = client
+            .put_label("Nxxx", "zone.*", "east")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidArgument {
+                argument: "label key",
+                ..
+            }
+        ));
+        assert!(matches!(
+            client.delete_label("Nxxx", "").await,
+            Err(Error::InvalidArgument { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_constraints_are_refused_before_publishing() {
+        let client = ClientBuilder::new(offline_nats().await)
+            .auction_timeout(Duration::from_secs(60))
+            .build();
+        let err = client
+            .perform_actor_auction("echo", Constraints::new().label("zone.>", "a"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidArgument {
+                argument: "constraint key",
+                ..
+            }
+        ));
+        let constraints = HashMap::from([(String::new(), "linux".to_string())]);
+        assert!(matches!(
+            client
+                .perform_provider_auction("httpserver", "default", constraints)
+                .await,
+            Err(Error::InvalidArgument { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_kv_store_is_never_used() {
+        let client = ClientBuilder::new(offline_nats().await)
+            .js_domain("edge")
+            .disable_kv_store()
+            .try_build()
+            .await
+            .unwrap();
+        assert!(!client.uses_kv_store().await);
+        let err = client.link_changes_receiver().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "link_changes_receiver requires the lattice metadata bucket LATTICEDATA_default, \
+             which was not found in JetStream domain edge"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_identifiers_fail_before_publishing() {
+        let nc = offline_nats().await;
+        let err = ClientBuilder::new(nc.clone())
+            .lattice_prefix("my lattice")
+            .try_build()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid lattice prefix: contains characters not permitted in NATS subjects \
+             (whitespace, `.`, `*` or `>`)"
+        );
+        assert!(ClientBuilder::new(nc.clone())
+            .topic_prefix("wasmbus.>")
+            .try_build()
+            .await
+            .is_err());
+
+        let client = ClientBuilder::new(nc.clone()).lattice_prefix("a.b").build();
+        assert!(matches!(
+            client.get_hosts().await,
+            Err(Error::InvalidArgument {
+                argument: "lattice prefix",
+                ..
+            })
+        ));
+
+        let client = ClientBuilder::new(nc).build();
+        assert!(matches!(
+            client.get_host_inventory("N 1").await,
+            Err(Error::InvalidArgument {
+                argument: "host ID",
+                ..
+            })
+        ));
+        assert!(matches!(
+            client.stop_host("", None).await,
+            Err(Error::InvalidArgument {
+                argument: "host ID",
+                ..
+            })
+        ));
+        assert!(matches!(
+            client.scale_actor("N*", "echo", None, None).await,
+            Err(Error::InvalidArgument { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_inboxes_use_the_configured_prefix() {
+        let nc = offline_nats().await;
+        let default = ClientBuilder::new(nc.clone()).build();
+        assert!(default.new_inbox().starts_with("_INBOX."));
+
+        let client = ClientBuilder::new(nc.clone())
+            .inbox_prefix("_INBOX_tenant.ops")
+            .build();
+        let (first, second) = (client.new_inbox(), client.new_inbox());
+        assert_ne!(first, second);
+        for inbox in [first, second] {
+            let token = inbox
+                .strip_prefix("_INBOX_tenant.ops.")
+                .unwrap_or_else(|| panic!("{inbox} should be under the inbox prefix"));
+            assert!(!token.is_empty() && !token.contains('.'));
+        }
+
+        let err = ClientBuilder::new(nc.clone())
+            .inbox_prefix("_INBOX.>")
+            .try_build()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidArgument {
+                argument: "inbox prefix",
+                ..
+            }
+        ));
+        let err = ClientBuilder::new(nc.clone())
+            .event_queue_group("my monitors")
+            .try_build()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidArgument {
+                argument: "queue group",
+                ..
+            }
+        ));
+        let bad_group = ClientBuilder::new(nc).event_queue_group("").build();
+        assert!(matches!(
+            bad_group.events_receiver().await,
+            Err(Error::InvalidArgument {
+                argument: "queue group",
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_events_receiver_with_policy_checks_its_arguments() {
+        let nc = offline_nats().await;
+        let client = ClientBuilder::new(nc.clone()).build();
+        assert!(matches!(
+            client
+                .events_receiver_with_policy(0, OverflowPolicy::Block)
+                .await,
+            Err(Error::InvalidArgument {
+                argument: "buffer",
+                ..
+            })
+        ));
+        let bad_group = ClientBuilder::new(nc).event_queue_group("").build();
+        assert!(matches!(
+            bad_group
+                .events_receiver_with_policy(16, OverflowPolicy::DropOldest)
+                .await,
+            Err(Error::InvalidArgument {
+                argument: "queue group",
+                ..
+            })
+        ));
+        let mut receiver = client
+            .events_receiver_with_policy(16, OverflowPolicy::DropNewest)
+            .await
+            .unwrap();
+        receiver.close();
+        assert!(receiver.recv().await.is_none());
+        assert_eq!(receiver.dropped_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_with_timeout_overrides_one_call() {
+        let client = ClientBuilder::new(offline_nats().await)
+            .timeout(Duration::from_secs(60))
+            .build();
+        let snappy = client.with_timeout(Duration::from_millis(20));
+        assert!(matches!(
+            snappy.get_host_inventory("Nxxx").await,
+            Err(Error::Timeout { duration, .. }) if duration == Duration::from_millis(20)
+        ));
+        assert_eq!(client.timeout, Duration::from_secs(60));
+
+        let auctions = client.with_auction_timeout(Duration::from_millis(20));
+        assert_eq!(auctions.auction_timeout, Duration::from_millis(20));
+        assert_eq!(auctions.timeout, Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_offline_client_reports_unreachable_lattice() {
+        let client = Client::new(offline_nats().await);
+        assert!(!client.is_connected());
+        assert!(!client
+            .ping_lattice(Duration::from_millis(50))
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn test_read_only_refuses_every_mutation() {
+        let client = ClientBuilder::new(offline_nats().await)
+            .read_only(true)
+            .read_only_allows_auctions(false)
+            .build();
+        assert!(client.is_read_only());
+        assert!(format!("{client:?}").contains("read_only: true"));
+
+        assert_read_only_violation(
+            client.start_actor("host", "echo", 1, None).await,
+            "start_actor",
+        );
+        assert_read_only_violation(
+            client
+                .scale_actor_detailed("host", "echo", Some(1), None)
+                .await,
+            "scale_actor",
+        );
+        assert_read_only_violation(
+            client
+                .scale_actor_to("echo", "MECHO", 3, Constraints::new(), None)
+                .await,
+            "scale_actor_to",
+        );
+        assert_read_only_violation(
+            client.scale_actor("host", "echo", Some(1), None).await,
+            "scale_actor",
+        );
+        assert_read_only_violation(
+            client
+                .scale_actor_with_id("host", "echo", Some(1), None, CommandId::new())
+                .await,
+            "scale_actor_with_id",
+        );
+        assert_read_only_violation(
+            client
+                .start_actor_with_id("host", "echo", 1, None, CommandId::new())
+                .await,
+            "start_actor_with_id",
+        );
+        assert_read_only_violation(client.stop_actor("host", "echo", None).await, "stop_actor");
+        assert_read_only_violation(
+            client
+                .stop_actor_instances("host", "echo", &Annotations::managed_by("wadm"))
+                .await,
+            "stop_actor_instances",
+        );
+        assert_read_only_violation(
+            client.update_actor("host", "Mxxx", "echo:2", None).await,
+            "update_actor",
+        );
+        assert_read_only_violation(
+            client
+                .update_actor_by_ref("host", "echo:1", "echo:2", None)
+                .await,
+            "update_actor_by_ref",
+        );
+        assert_read_only_violation(
+            client
+                .start_provider("host", "httpserver", None, None, None)
+                .await,
+            "start_provider",
+        );
+        assert_read_only_violation(
+            client
+                .stop_provider("host", "Vxxx", "default", "wasmcloud:httpserver", None)
+                .await,
+            "stop_provider",
+        );
+        assert_read_only_violation(client.stop_host("host", None).await, "stop_host");
+        assert_read_only_violation(
+            client.ensure_lattice_generation().await,
+            "ensure_lattice_generation",
+        );
+        assert_read_only_violation(
+            client
+                .ensure_lattice_metadata_bucket(BucketConfig::new())
+                .await,
+            "ensure_lattice_metadata_bucket",
+        );
+        assert_read_only_violation(
+            client.migrate_topic_links_to_kv().await,
+            "migrate_topic_links_to_kv",
+        );
+        assert_read_only_violation(
+            client.purge_host(&HostInventory::default()).await,
+            "purge_host",
+        );
+        assert_read_only_violation(
+            client
+                .put_claims(HashMap::from([("sub".to_string(), "Mxxx".to_string())]))
+                .await,
+            "put_claims",
+        );
+        assert_read_only_violation(client.delete_claims("Mxxx").await, "delete_claims");
+        assert_read_only_violation(
+            client
+                .advertise_link(
+                    "Mxxx",
+                    "Vxxx",
+                    "wasmcloud:httpserver",
+                    "default",
+                    HashMap::new(),
+                )
+                .await,
+            "advertise_link",
+        );
+        assert_read_only_violation(
+            client
+                .remove_link("Mxxx", "wasmcloud:httpserver", "default")
+                .await,
+            "remove_link",
+        );
+        assert_read_only_violation(
+            client
+                .advertise_links(vec![LinkDefinition::default()])
+                .await,
+            "advertise_links",
+        );
+        assert_read_only_violation(
+            client
+                .remove_links(vec![RemoveLinkDefinitionRequest::default()])
+                .await,
+            "remove_links",
+        );
+        assert_read_only_violation(
+            client.put_registries(RegistryCredentialMap::new()).await,
+            "put_registries",
+        );
+        assert_read_only_violation(
+            client.send_batch("host", CommandBatch::new()).await,
+            "send_batch",
+        );
+        assert_read_only_violation(
+            client
+                .spread_actor("echo", HashMap::new(), 2, Some(1), None)
+                .await,
+            "spread_actor",
+        );
+        assert_read_only_violation(
+            client
+                .start_actor_and_wait("Nxxx", "echo", 1, None, Duration::from_secs(1))
+                .await,
+            "start_actor_and_wait",
+        );
+        assert_read_only_violation(
+            client
+                .start_provider_and_wait(
+                    "Nxxx",
+                    "httpserver",
+                    None,
+                    None,
+                    None,
+                    Duration::from_secs(1),
+                )
+                .await,
+            "start_provider_and_wait",
+        );
+        assert_read_only_violation(
+            client
+                .stop_provider_and_wait(
+                    "Nxxx",
+                    "Vxxx",
+                    "default",
+                    "wasmcloud:httpserver",
+                    None,
+                    Duration::from_secs(1),
+                )
+                .await,
+            "stop_provider_and_wait",
+        );
+        assert_read_only_violation(
+            client
+                .stop_actor_and_wait("Nxxx", "Mxxx", None, Duration::from_secs(1))
+                .await,
+            "stop_actor_and_wait",
+        );
+        assert_read_only_violation(
+            client
+                .put_registries_to_host("Nxxx", RegistryCredentialMap::new())
+                .await,
+            "put_registries_to_host",
+        );
+        assert_read_only_violation(client.put_label("Nxxx", "zone", "east").await, "put_label");
+        assert_read_only_violation(
+            client.stop_host_and_monitor("Nxxx", None).await,
+            "stop_host_and_monitor",
+        );
+        assert_read_only_violation(client.delete_label("Nxxx", "zone").await, "delete_label");
+        assert_read_only_violation(
+            client.put_config("http", ConfigValues::new()).await,
+            "put_config",
+        );
+        assert_read_only_violation(client.delete_config("http").await, "delete_config");
+        assert_read_only_violation(
+            client
+                .update_actor_and_wait("Nxxx", "Mxxx", "echo:0.2", None, Duration::from_secs(1))
+                .await,
+            "update_actor_and_wait",
+        );
+        assert_read_only_violation(
+            client.perform_actor_auction("echo", HashMap::new()).await,
+            "perform_actor_auction",
+        );
+        assert_read_only_violation(
+            client
+                .perform_provider_auction("httpserver", "default", HashMap::new())
+                .await,
+            "perform_provider_auction",
+        );
+    }
+
+    /// Note: This test requires a local NATS server with JetStream enabled
+    #[tokio::test]
+    #[ignore]
+    async fn test_generation_change_invalidates_cache() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let js = async_nats::jetstream::new(nc.clone());
+        let bucket = kv::bucket_name("generationtest");
+        let _ = js.delete_key_value(&bucket).await;
+        let config = || async_nats::jetstream::kv::Config {
+            bucket: bucket.clone(),
+            ..Default::default()
+        };
+        js.create_key_value(config()).await.unwrap();
+
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("generationtest")
+            .build();
+        let first = client.ensure_lattice_generation().await.unwrap().unwrap();
+        assert_eq!(
+            client.lattice_generation().await.unwrap(),
+            Some(first.clone())
+        );
+        assert_eq!(client.check_lattice_generation().await.unwrap(), None);
+
+        js.delete_key_value(&bucket).await.unwrap();
+        let store = js.create_key_value(config()).await.unwrap();
+        let second = kv::ensure_generation(&store).await.unwrap();
+        assert_ne!(first, second);
+
+        assert_eq!(
+            client.check_lattice_generation().await.unwrap(),
+            Some(GenerationChanged {
+                previous: Some(first),
+                current: Some(second.clone()),
+            })
+        );
+        // The cached bucket handle was replaced, so reads reflect the new bucket
+        assert_eq!(client.lattice_generation().await.unwrap(), Some(second));
+        assert_eq!(client.check_lattice_generation().await.unwrap(), None);
+        js.delete_key_value(&bucket).await.unwrap();
+    }
+
+    /// Note: This test requires a local NATS server with JetStream enabled
+    #[tokio::test]
+    #[ignore]
+    async fn test_require_kv_store() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let js = async_nats::jetstream::new(nc.clone());
+        let bucket = kv::bucket_name("requiretest");
+        let _ = js.delete_key_value(&bucket).await;
+
+        let builder = || {
+            ClientBuilder::new(nc.clone())
+                .lattice_prefix("requiretest")
+                .require_kv_store(true)
+        };
+        let err = builder().try_build().await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::RequiresKv(RequiresKv { operation: "try_build", ref bucket, js_domain: None })
+                if *bucket == kv::bucket_name("requiretest")
+        ));
+        // A client built without the check still refuses to fall back
+        let err = builder().build().get_claims().await.unwrap_err();
+        assert!(matches!(err, Error::RequiresKv(_)));
+
+        js.create_key_value(async_nats::jetstream::kv::Config {
+            bucket: bucket.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let client = builder().try_build().await.unwrap();
+        assert!(client.uses_kv_store().await);
+        assert!(client.get_claims().await.unwrap().is_empty());
+        assert!(
+            ClientBuilder::new(nc.clone())
+                .lattice_prefix("requiretest")
+                .build()
+                .uses_kv_store()
+                .await
+        );
+
+        js.delete_key_value(&bucket).await.unwrap();
+    }
+
+    /// Note: This test requires a local NATS server with JetStream enabled
+    #[tokio::test]
+    #[ignore]
+    async fn test_put_and_delete_claims() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let js = async_nats::jetstream::new(nc.clone());
+        let bucket = kv::bucket_name("claimstest");
+        let _ = js.delete_key_value(&bucket).await;
+
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("claimstest")
+            .trusted_issuers(["Axxx"])
+            .build();
+        let claims = HashMap::from([
+            ("sub".to_string(), "Mxxx".to_string()),
+            ("iss".to_string(), "Axxx".to_string()),
+            ("name".to_string(), "echo".to_string()),
+        ]);
+        let err = client.put_claims(claims.clone()).await.unwrap_err();
+        assert!(matches!(err, Error::RequiresKv(_)));
+
+        js.create_key_value(async_nats::jetstream::kv::Config {
+            bucket: bucket.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        client.put_claims(claims.clone()).await.unwrap();
+        assert_eq!(client.get_claims().await.unwrap(), vec![claims.clone()]);
+        assert_eq!(client.get_claims_for("Mxxx").await.unwrap(), Some(claims));
+        assert!(client.lattice_generation().await.unwrap().is_some());
+
+        let no_issuer = HashMap::from([("sub".to_string(), "Mzzz".to_string())]);
+        assert!(matches!(
+            client.put_claims(no_issuer).await,
+            Err(Error::InvalidClaims { .. })
+        ));
+
+        let untrusted = HashMap::from([
+            ("sub".to_string(), "Myyy".to_string()),
+            ("iss".to_string(), "Ayyy".to_string()),
+        ]);
+        assert!(client.put_claims(untrusted).await.is_err());
+
+        client.delete_claims("Mxxx").await.unwrap();
+        assert!(client.get_claims().await.unwrap().is_empty());
+        assert_eq!(client.get_claims_for("Mxxx").await.unwrap(), None);
+        js.delete_key_value(&bucket).await.unwrap();
+    }
+
+    /// Note: This test requires a local NATS server
+    #[tokio::test]
+    #[ignore]
+    async fn test_await_host() {
+        use cloudevents::EventBuilder as _;
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let client = ClientBuilder::new(nc.clone())
+            .lattice_prefix("awaittest")
+            .build();
+        assert!(matches!(
+            client
+                .await_host("NAWAIT", Duration::from_millis(100))
+                .await,
+            Err(Error::Timeout { .. })
+        ));
+
+        let heartbeat = cloudevents::EventBuilderV10::new()
+            .id("1")
+            .source("NAWAIT")
+            .ty(LatticeEvent::HOST_HEARTBEAT)
+            .data(
+                "application/json",
+                serde_json::json!({"friendly_name": "wash-up"}),
+            )
+            .build()
+            .unwrap();
+        let publisher = nc.clone();
+        let subject = broker::control_event("awaittest");
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let payload = serde_json::to_vec(&heartbeat).unwrap();
+            publisher.publish(subject, payload.into()).await.unwrap();
+        });
+        let host = client
+            .await_host("NAWAIT", Duration::from_secs(2))
+            .await
+            .unwrap();
+        assert_eq!(host.id, "NAWAIT");
+        assert_eq!(host.friendly_name, "wash-up");
+    }
+
+    /// Note: This test requires a local NATS server
+    #[tokio::test]
+    #[ignore]
+    async fn test_stop_host_and_monitor() {
+        use cloudevents::EventBuilder as _;
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let _host =
+            FakeHost::start(nc.clone(), "draintest", "NDRAIN", FakeHostConfig::default()).await;
+        let client = ClientBuilder::new(nc.clone())
+            .lattice_prefix("draintest")
+            .timeout(Duration::from_millis(200))
+            .build();
+
+        let event = |ty: &str, source: &str, data: serde_json::Value| {
+            let event = cloudevents::EventBuilderV10::new()
+                .id("1")
+                .source(source)
+                .ty(ty)
+                .data("application/json", data)
+                .build()
+                .unwrap();
+            serde_json::to_vec(&event).unwrap()
+        };
+        let mut drain = client
+            .stop_host_and_monitor("NDRAIN", Some(0))
+            .await
+            .unwrap();
+        let subject = broker::control_event("draintest");
+        let stopped = serde_json::json!({"public_key": "Mxxx", "instance_id": "a"});
+        for payload in [
+            event(LatticeEvent::ACTOR_STOPPED, "NOTHER", stopped.clone()),
+            event(LatticeEvent::ACTOR_STOPPED, "NDRAIN", stopped),
+            event(LatticeEvent::HOST_STOPPED, "NDRAIN", serde_json::json!({})),
+        ] {
+            nc.publish(subject.clone(), payload.into()).await.unwrap();
+        }
+        assert!(matches!(
+            drain.recv().await,
+            Some(HostDrainEvent::ActorStopped(event)) if event.host_id == "NDRAIN"
+        ));
+        assert!(matches!(
+            drain.recv().await,
+            Some(HostDrainEvent::Stopped(_))
+        ));
+        assert!(drain.recv().await.is_none());
+
+        // Without a host stopped event the deadline ends the drain
+        let mut drain = client
+            .stop_host_and_monitor("NDRAIN", Some(0))
+            .await
+            .unwrap();
+        assert_eq!(drain.recv().await, Some(HostDrainEvent::Unconfirmed));
+        assert!(drain.recv().await.is_none());
+    }
+
+    /// Note: This test requires a local NATS server
+    #[tokio::test]
+    #[ignore]
+    async fn test_get_all_inventories_reports_unreachable_hosts() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let _up = FakeHost::start(nc.clone(), "invtest", "NUP", FakeHostConfig::default()).await;
+        let down = FakeHost::start(
+            nc.clone(),
+            "invtest",
+            "NDOWN",
+            FakeHostConfig {
+                ignore: vec!["inv"],
+                ..Default::default()
+            },
+        )
+        .await;
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("invtest")
+            .timeout(Duration::from_millis(200))
+            .auction_timeout(Duration::from_millis(200))
+            .build();
+
+        let mut inventories = client.get_all_inventories().await.unwrap();
+        inventories.sort_by(|(a, _), (b, _)| a.id.cmp(&b.id));
+        assert_eq!(inventories.len(), 2);
+        assert_eq!(inventories[0].0.id, "NDOWN");
+        assert!(matches!(inventories[0].1, Err(Error::Timeout { .. })));
+        assert_eq!(inventories[1].0.id, "NUP");
+        assert_eq!(inventories[1].1.as_ref().unwrap().host_id, "NUP");
+        assert_eq!(down.received(), ["ping", "inv"]);
+    }
+
+    /// Note: This test requires a local NATS server
+    #[tokio::test]
+    #[ignore]
+    async fn test_scale_actor_to_spreads_new_instances() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let n1 = FakeHost::start(nc.clone(), "scaletest", "N1", FakeHostConfig::default()).await;
+        let n2 = FakeHost::start(
+            nc.clone(),
+            "scaletest",
+            "N2",
+            FakeHostConfig {
+                reject: vec!["scale"],
+                ..Default::default()
+            },
+        )
+        .await;
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("scaletest")
+            .auction_timeout(Duration::from_millis(200))
+            .build();
+
+        let report = client
+            .scale_actor_to("echo", "MECHO", 3, Constraints::new(), None)
+            .await
+            .unwrap();
+        assert_eq!(report.previous_count, 0);
+        assert_eq!(
+            report
+                .changes
+                .iter()
+                .map(|change| (change.host_id.as_str(), change.to, change.ack.accepted))
+                .collect::<Vec<_>>(),
+            [("N1", 2, true), ("N2", 1, false)]
+        );
+        assert_eq!(report.acknowledged_count(), 2);
+        assert!(!report.is_complete());
+        assert!(n1.received().ends_with(&["scale".to_string()]));
+        assert!(n2.received().ends_with(&["scale".to_string()]));
+    }
+
+    /// Note: This test requires a local NATS server
+    #[tokio::test]
+    #[ignore]
+    async fn test_auction_picks_the_least_loaded_host() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let busy = FakeHostConfig {
+            actors: vec![ActorDescription {
+                id: "MECHO".to_string(),
+                image_ref: Some("echo".to_string()),
+                instances: vec![ActorInstance {
+                    max_concurrent: 4,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let _n1 = FakeHost::start(nc.clone(), "picktest", "N1", busy).await;
+        let _n2 = FakeHost::start(nc.clone(), "picktest", "N2", FakeHostConfig::default()).await;
+        // Bids, but never answers inventory queries
+        let _n0 = FakeHost::start(
+            nc.clone(),
+            "picktest",
+            "N0",
+            FakeHostConfig {
+                ignore: vec!["inv"],
+                ..Default::default()
+            },
+        )
+        .await;
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("picktest")
+            .timeout(Duration::from_millis(200))
+            .auction_timeout(Duration::from_millis(200))
+            .build();
+
+        let bids = client
+            .perform_actor_auction("echo", Constraints::new())
+            .await
+            .unwrap();
+        let ranked = client.rank_actor_bids(bids, &LoadRanker::new()).await;
+        assert_eq!(
+            ranked
+                .iter()
+                .map(|bid| bid.host_id.as_str())
+                .collect::<Vec<_>>(),
+            ["N2", "N1", "N0"]
+        );
+        assert_eq!(
+            client
+                .auction_and_pick_actor_host("echo", Constraints::new())
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("N2")
+        );
+
+        struct Nobody;
+        impl AuctionRanker for Nobody {
+            fn rank(&self, _: &AuctionSubject<'_>, _: Vec<Bidder>) -> Vec<Bidder> {
+                Vec::new()
+            }
+        }
+        assert_eq!(
+            client
+                .auction_and_pick_actor_host_with_ranker("echo", Constraints::new(), &Nobody)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    /// Note: This test requires a local NATS server
+    #[tokio::test]
+    #[ignore]
+    async fn test_actor_refs_resolve_to_a_single_public_key() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let claims = |subject: &str, call_alias: &str| {
+            HashMap::from([
+                ("sub".to_string(), subject.to_string()),
+                ("call_alias".to_string(), call_alias.to_string()),
+            ])
+        };
+        let host = FakeHost::start(
+            nc.clone(),
+            "resolveref",
+            "N1",
+            FakeHostConfig {
+                actors: vec![ActorDescription {
+                    id: "MECHO".to_string(),
+                    image_ref: Some("echo:1".to_string()),
+                    ..Default::default()
+                }],
+                claims: vec![claims("MECHO", "hello"), claims("MOTHER", "echo:1")],
+                ..Default::default()
+            },
+        )
+        .await;
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("resolveref")
+            .disable_kv_store()
+            .build();
+
+        assert_eq!(
+            client.resolve_actor_id("hello").await.unwrap().as_deref(),
+            Some("MECHO")
+        );
+        assert_eq!(client.resolve_actor_id("echo:2").await.unwrap(), None);
+        match client.resolve_actor_id("echo:1").await {
+            Err(Error::AmbiguousReference { candidates, .. }) => {
+                assert_eq!(candidates, ["MECHO", "MOTHER"])
+            }
+            other => panic!("expected an ambiguous reference, got {other:?}"),
+        }
+
+        let ack = client
+            .update_actor_by_ref("N1", "hello", "echo:2", None)
+            .await
+            .unwrap();
+        assert!(ack.accepted);
+        assert!(matches!(
+            client
+                .update_actor_by_ref("N1", "echo:2", "echo:3", None)
+                .await,
+            Err(Error::InvalidArgument { .. })
+        ));
+        // Only the resolvable reference leads to an update command
+        assert_eq!(host.received().iter().filter(|op| *op == "upd").count(), 1);
+    }
+
+    /// Note: This test requires a local NATS server
+    #[tokio::test]
+    #[ignore]
+    async fn test_stop_actor_instances_by_annotation_subset() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let instance = |max_concurrent, annotations: AnnotationMap| ActorInstance {
+            annotations: (!annotations.is_empty()).then_some(annotations),
+            max_concurrent,
+            ..Default::default()
+        };
+        let spread = |name: &str| {
+            let mut annotations = annotations::app("echo");
+            annotations.insert(annotations::SPREAD_NAME.to_string(), name.to_string());
+            annotations
+        };
+        let host = FakeHost::start(
+            nc.clone(),
+            "stopannotations",
+            "N1",
+            FakeHostConfig {
+                actors: vec![ActorDescription {
+                    id: "MECHO".to_string(),
+                    image_ref: Some("echo".to_string()),
+                    instances: vec![
+                        instance(2, spread("east")),
+                        instance(1, AnnotationMap::new()),
+                        instance(3, spread("west")),
+                        instance(1, spread("east")),
+                    ],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .await;
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("stopannotations")
+            .build();
+
+        let stops = client
+            .stop_actor_instances("N1", "echo", &Annotations::app("echo"))
+            .await
+            .unwrap();
+        assert_eq!(
+            stops
+                .iter()
+                .map(|stop| (&stop.annotations, stop.instance_count, stop.ack.accepted))
+                .collect::<Vec<_>>(),
+            [(&spread("east"), 3, true), (&spread("west"), 3, true)]
+        );
+        assert_eq!(host.received(), ["inv", "sa", "sa"]);
+
+        let stops = client
+            .stop_actor_instances("N1", "MECHO", &Annotations::managed_by("someone-else"))
+            .await
+            .unwrap();
+        assert!(stops.is_empty());
+        assert_eq!(host.received(), ["inv", "sa", "sa", "inv"]);
+    }
+
+    /// Note: This test requires a local NATS server
+    #[tokio::test]
+    #[ignore]
+    async fn test_error_on_nack_surfaces_rejections() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let _host = FakeHost::start(
+            nc.clone(),
+            "nacktest",
+            "N1",
+            FakeHostConfig {
+                reject: vec!["scale"],
+                ..Default::default()
+            },
+        )
+        .await;
+        let lenient = ClientBuilder::new(nc).lattice_prefix("nacktest").build();
+        let strict = ClientBuilder::new(lenient.nc.clone())
+            .lattice_prefix("nacktest")
+            .error_on_nack(true)
+            .build();
+
+        let ack = lenient
+            .scale_actor("N1", "echo", Some(2), None)
+            .await
+            .unwrap();
+        assert!(!ack.accepted);
+        assert!(ack.clone().into_result().is_err());
+
+        let Err(Error::NotAccepted(rejected)) =
+            strict.scale_actor("N1", "echo", Some(2), None).await
+        else {
+            panic!("a rejected command should be an error");
+        };
+        assert_eq!(rejected.error, ack.error);
+        assert_eq!(rejected.operation, Some("scale_actor"));
+        assert_eq!(rejected.host_id.as_deref(), Some("N1"));
+        let command = rejected.command.unwrap();
+        assert_eq!(command["actor_ref"], "echo");
+        assert_eq!(command["count"], 2);
+
+        // Accepted commands are unaffected
+        assert!(
+            strict
+                .stop_actor("N1", "echo", None)
+                .await
+                .unwrap()
+                .accepted
+        );
+    }
+
+    /// Note: This test requires a local NATS server
+    #[tokio::test]
+    #[ignore]
+    async fn test_detailed_responses_are_timed() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let _host = FakeHost::start(
+            nc.clone(),
+            "timingtest",
+            "N1",
+            FakeHostConfig {
+                delay: Duration::from_millis(100),
+                ..Default::default()
+            },
+        )
+        .await;
+        let client = ClientBuilder::new(nc).lattice_prefix("timingtest").build();
+
+        let (first, second) = tokio::join!(
+            client.scale_actor_detailed("N1", "echo", Some(1), None),
+            client.get_host_inventory_detailed("N1"),
+        );
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert!(first.data.accepted);
+        assert_eq!(first.subject, "wasmbus.ctl.timingtest.cmd.N1.scale");
+        assert!(first.elapsed >= Duration::from_millis(100));
+        assert_eq!(second.data.host_id, "N1");
+        assert_eq!(second.subject, "wasmbus.ctl.timingtest.get.N1.inv");
+        // The fake host answers one request at a time, so whichever was answered second waited
+        // for both
+        assert!(first.elapsed.max(second.elapsed) >= Duration::from_millis(200));
+
+        let hosts = client.get_hosts_detailed().await.unwrap();
+        assert_eq!(hosts.data.len(), 1);
+        assert_eq!(hosts.subject, "wasmbus.ctl.timingtest.ping.hosts");
+        assert!(hosts.elapsed >= Duration::from_millis(100));
+    }
+
+    /// Note: This test requires a local NATS server
+    #[tokio::test]
+    #[ignore]
+    async fn test_msgpack_codec() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let host = FakeHost::start(nc.clone(), "codectest", "N1", FakeHostConfig::default()).await;
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("codectest")
+            .codec(Codec::MsgPack)
+            .auction_timeout(Duration::from_millis(200))
+            .build();
+
+        let ack = client
+            .scale_actor("N1", "wasmcloud.azurecr.io/echo:0.3.4", Some(2), None)
+            .await
+            .unwrap();
+        assert!(ack.accepted);
+        let inventory = client.get_host_inventory("N1").await.unwrap();
+        assert_eq!(inventory.host_id, "N1");
+        let bids = client
+            .perform_actor_auction("wasmcloud.azurecr.io/echo:0.3.4", Constraints::new())
+            .await
+            .unwrap();
+        assert_eq!(bids.len(), 1);
+        assert_eq!(bids[0].actor_ref, "wasmcloud.azurecr.io/echo:0.3.4");
+        assert_eq!(host.received(), ["scale", "inv", "auction"]);
+    }
+
+    /// Note: This test requires a local NATS server with JetStream enabled
+    #[tokio::test]
+    #[ignore]
+    async fn test_create_bucket_and_migrate_links() {
+        use futures::StreamExt as _;
+
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let js = async_nats::jetstream::new(nc.clone());
+        let _ = js.delete_key_value(kv::bucket_name("migratetest")).await;
+        let link = |actor_id: &str| LinkDefinition {
+            actor_id: actor_id.to_string(),
+            provider_id: "VHTTP".to_string(),
+            contract_id: "wasmcloud:httpserver".to_string(),
+            link_name: "default".to_string(),
+            ..Default::default()
+        };
+        // Stands in for a host still answering link queries over the legacy topic
+        let mut queries = nc
+            .subscribe(broker::queries::link_definitions(&None, "migratetest"))
+            .await
+            .unwrap();
+        let responder_nc = nc.clone();
+        let legacy_links = vec![link("M1"), link("M2")];
+        let responder = tokio::spawn(async move {
+            while let Some(msg) = queries.next().await {
+                let list = LinkDefinitionList {
+                    links: legacy_links.clone(),
+                };
+                let _ = responder_nc
+                    .publish(
+                        msg.reply.unwrap(),
+                        serde_json::to_vec(&list).unwrap().into(),
+                    )
+                    .await;
+            }
+        });
+
+        let client = ClientBuilder::new(nc).lattice_prefix("migratetest").build();
+        assert!(matches!(
+            client.migrate_topic_links_to_kv().await,
+            Err(Error::RequiresKv(_))
+        ));
+        assert!(client
+            .ensure_lattice_metadata_bucket(BucketConfig::new())
+            .await
+            .unwrap());
+        assert!(!client
+            .ensure_lattice_metadata_bucket(BucketConfig::new().history(5))
+            .await
+            .unwrap());
+        assert!(client.lattice_generation().await.unwrap().is_some());
+
+        kv::put_link(&client.kv_store().await.unwrap(), &link("M1"))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.migrate_topic_links_to_kv().await.unwrap(),
+            LinkMigration {
+                copied: 1,
+                already_present: 1,
+            }
+        );
+        assert_eq!(
+            client.migrate_topic_links_to_kv().await.unwrap(),
+            LinkMigration {
+                copied: 0,
+                already_present: 2,
+            }
+        );
+        responder.abort();
+        js.delete_key_value(kv::bucket_name("migratetest"))
+            .await
+            .unwrap();
+    }
+
+    /// Note: This test requires a local NATS server with JetStream enabled
+    #[tokio::test]
+    #[ignore]
+    async fn test_put_link_if_absent_writes_once() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let js = async_nats::jetstream::new(nc.clone());
+        let _ = js.delete_key_value(kv::bucket_name("absenttest")).await;
+        let client = ClientBuilder::new(nc).lattice_prefix("absenttest").build();
+        client
+            .ensure_lattice_metadata_bucket(BucketConfig::new())
+            .await
+            .unwrap();
+        let store = client.kv_store().await.unwrap();
+        let link = LinkDefinition {
+            actor_id: "Mecho".to_string(),
+            provider_id: "Vhttp".to_string(),
+            contract_id: "wasmcloud:httpserver".to_string(),
+            link_name: "default".to_string(),
+            ..Default::default()
+        };
+
+        let written =
+            futures::future::join_all((0..5).map(|_| kv::put_link_if_absent(&store, &link))).await;
+        assert_eq!(
+            written
+                .into_iter()
+                .filter(|written| *written.as_ref().unwrap())
+                .count(),
+            1
+        );
+
+        // A deleted link can be written again
+        kv::delete_link(&store, &link.actor_id, &link.contract_id, &link.link_name)
+            .await
+            .unwrap();
+        assert!(kv::put_link_if_absent(&store, &link).await.unwrap());
+        assert!(!kv::put_link_if_absent(&store, &link).await.unwrap());
+        js.delete_key_value(kv::bucket_name("absenttest"))
+            .await
+            .unwrap();
+    }
+
+    /// Note: This test requires a local NATS server with JetStream enabled
+    #[tokio::test]
+    #[ignore]
+    async fn test_bulk_links_write_to_the_bucket() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let js = async_nats::jetstream::new(nc.clone());
+        let _ = js.delete_key_value(kv::bucket_name("bulkkvtest")).await;
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("bulkkvtest")
+            .require_kv_store(true)
+            .build();
+        client
+            .ensure_lattice_metadata_bucket(BucketConfig::new())
+            .await
+            .unwrap();
+        let links: Vec<_> = ["one", "two", "three"]
+            .into_iter()
+            .map(|name| LinkDefinition {
+                actor_id: "Mxxx".to_string(),
+                provider_id: "Vxxx".to_string(),
+                contract_id: "wasmcloud:httpserver".to_string(),
+                link_name: name.to_string(),
+                values: HashMap::from([("name".to_string(), name.to_string())]),
+            })
+            .collect();
+
+        let acks = client.advertise_links(links.clone()).await.unwrap();
+        assert_eq!(
+            acks,
+            vec![
+                CtlOperationAck {
+                    accepted: true,
+                    error: String::new(),
+                };
+                3
+            ]
+        );
+        let mut stored = client.query_links().await.unwrap();
+        stored.sort_by(|a, b| a.link_name.cmp(&b.link_name));
+        assert_eq!(
+            stored,
+            vec![links[0].clone(), links[2].clone(), links[1].clone()]
+        );
+
+        let removals = links[..2]
+            .iter()
+            .map(|ld| RemoveLinkDefinitionRequest {
+                actor_id: ld.actor_id.clone(),
+                contract_id: ld.contract_id.clone(),
+                link_name: ld.link_name.clone(),
+            })
+            .collect();
+        let acks = client.remove_links(removals).await.unwrap();
+        assert!(acks.iter().all(|ack| ack.accepted), "{acks:?}");
+        assert_eq!(client.query_links().await.unwrap(), vec![links[2].clone()]);
+        js.delete_key_value(kv::bucket_name("bulkkvtest"))
+            .await
+            .unwrap();
+    }
+
+    /// Note: This test requires a local NATS server with JetStream enabled
+    #[tokio::test]
+    #[ignore]
+    async fn test_purge_host_removes_stranded_metadata() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let js = async_nats::jetstream::new(nc.clone());
+        let _ = js.delete_key_value(kv::bucket_name("purgetest")).await;
+        let actor = |id: &str| ActorDescription {
+            id: id.to_string(),
+            ..Default::default()
+        };
+        let _live = FakeHost::start(
+            nc.clone(),
+            "purgetest",
+            "NLIVE",
+            FakeHostConfig {
+                actors: vec![actor("Mshared")],
+                ..Default::default()
+            },
+        )
+        .await;
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("purgetest")
+            .auction_timeout(Duration::from_millis(200))
+            .build();
+        let dead = HostInventory {
+            host_id: "NDEAD".to_string(),
+            actors: vec![actor("Mecho"), actor("Mshared")],
+            providers: vec![ProviderDescription {
+                id: "Vhttp".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert!(matches!(
+            client.purge_host_plan(&dead).await,
+            Err(Error::RequiresKv(_))
+        ));
+
+        client
+            .ensure_lattice_metadata_bucket(BucketConfig::new())
+            .await
+            .unwrap();
+        let store = client.kv_store().await.unwrap();
+        for subject in ["Mecho", "Mshared", "Vhttp"] {
+            let claims = HashMap::from([
+                ("sub".to_string(), subject.to_string()),
+                ("iss".to_string(), "Aissuer".to_string()),
+            ]);
+            kv::put_claims(&store, &claims).await.unwrap();
+        }
+        let link = LinkDefinition {
+            actor_id: "Mecho".to_string(),
+            provider_id: "Vhttp".to_string(),
+            contract_id: "wasmcloud:httpserver".to_string(),
+            link_name: "default".to_string(),
+            ..Default::default()
+        };
+        kv::put_link(&store, &link).await.unwrap();
+        // Defined ahead of a provider that hasn't started, and unrelated to the dead host
+        let pending = LinkDefinition {
+            actor_id: "Mother".to_string(),
+            provider_id: "Vnotstarted".to_string(),
+            contract_id: "wasmcloud:keyvalue".to_string(),
+            link_name: "default".to_string(),
+            ..Default::default()
+        };
+        kv::put_link(&store, &pending).await.unwrap();
+
+        let plan = client.purge_host_plan(&dead).await.unwrap();
+        assert_eq!(plan.claims, ["Mecho", "Vhttp"]);
+        assert_eq!(plan.links, [link]);
+        assert_eq!(plan.keys().len(), 3);
+        assert_eq!(client.get_claims().await.unwrap().len(), 3);
+
+        assert_eq!(client.purge_host(&dead).await.unwrap(), plan);
+        let remaining = client.get_claims().await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0]["sub"], "Mshared");
+        assert_eq!(
+            kv::get_links(&store, &LinkFilter::new()).await.unwrap(),
+            [pending]
+        );
+        assert!(client.purge_host_plan(&dead).await.unwrap().is_empty());
+
+        let live = HostInventory {
+            host_id: "NLIVE".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            client.purge_host(&live).await,
+            Err(Error::InvalidArgument {
+                argument: "inventory",
+                ..
+            })
+        ));
+        js.delete_key_value(kv::bucket_name("purgetest"))
+            .await
+            .unwrap();
+    }
+
+    /// Note: This test requires a local NATS server with JetStream enabled
+    #[tokio::test]
+    #[ignore]
+    async fn test_config_round_trip() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let js = async_nats::jetstream::new(nc.clone());
+        let bucket = kv::bucket_name("configtest");
+        let _ = js.delete_key_value(&bucket).await;
+
+        let client = ClientBuilder::new(nc).lattice_prefix("configtest").build();
+        let values = ConfigValues::from([("port".to_string(), "8080".to_string())]);
+        assert!(matches!(
+            client.put_config("http", values.clone()).await,
+            Err(Error::RequiresKv(_))
+        ));
+
+        js.create_key_value(async_nats::jetstream::kv::Config {
+            bucket: bucket.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(client.get_config("http").await.unwrap(), None);
+        client.put_config("http", values.clone()).await.unwrap();
+        client.put_config("kv", ConfigValues::new()).await.unwrap();
+        assert_eq!(
+            client.get_config("http").await.unwrap(),
+            Some(values.clone())
+        );
+        let mut names = client.list_config_names().await.unwrap();
+        names.sort();
+        assert_eq!(names, ["http", "kv"]);
+        assert_eq!(
+            client.list_configs().await.unwrap(),
+            [
+                NamedConfig {
+                    name: "http".to_string(),
+                    values: values.clone(),
+                },
+                NamedConfig {
+                    name: "kv".to_string(),
+                    values: ConfigValues::new(),
+                },
+            ]
+        );
+
+        client.delete_config("http").await.unwrap();
+        client.delete_config("http").await.unwrap();
+        assert_eq!(client.get_config("http").await.unwrap(), None);
+        assert_eq!(client.list_config_names().await.unwrap(), ["kv"]);
+        js.delete_key_value(&bucket).await.unwrap();
+    }
+
+    /// Note: This test requires a local NATS server with JetStream enabled
+    #[tokio::test]
+    #[ignore]
+    async fn test_claims_for_one_entity_and_changes() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let js = async_nats::jetstream::new(nc.clone());
+        let bucket = kv::bucket_name("claimswatchtest");
+        let _ = js.delete_key_value(&bucket).await;
+        js.create_key_value(async_nats::jetstream::kv::Config {
+            bucket: bucket.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("claimswatchtest")
+            .build();
+        let claims = |sub: &str| {
+            HashMap::from([
+                ("sub".to_string(), sub.to_string()),
+                ("iss".to_string(), "Axxx".to_string()),
+            ])
+        };
+        client.put_claims(claims("Mone")).await.unwrap();
+
+        let mut changes = client.claims_changes_receiver().await.unwrap();
+        client.put_claims(claims("Mtwo")).await.unwrap();
+        assert_eq!(
+            client.get_claims_for("Mtwo").await.unwrap(),
+            Some(claims("Mtwo"))
+        );
+        assert_eq!(client.get_claims_for("Mthree").await.unwrap(), None);
+        client.delete_claims("Mone").await.unwrap();
+
+        // Only changes made after the receiver was created are delivered
+        let mut seen = Vec::new();
+        while let Ok(Some(change)) =
+            tokio::time::timeout(Duration::from_secs(1), changes.recv()).await
+        {
+            seen.push(change);
+        }
+        assert_eq!(
+            seen,
+            vec![
+                ClaimsChange::Put(claims("Mtwo")),
+                ClaimsChange::Delete {
+                    subject: "Mone".to_string()
+                }
+            ]
+        );
+        drop(changes);
+        js.delete_key_value(&bucket).await.unwrap();
+    }
+
+    /// Note: This test requires a local NATS server with JetStream enabled
+    #[tokio::test]
+    #[ignore]
+    async fn test_link_changes_report_deleting_earlier_links() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let js = async_nats::jetstream::new(nc.clone());
+        let _ = js.delete_key_value(kv::bucket_name("linkwatchtest")).await;
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("linkwatchtest")
+            .build();
+        client
+            .ensure_lattice_metadata_bucket(BucketConfig::new())
+            .await
+            .unwrap();
+        let store = client.kv_store().await.unwrap();
+        let link = |name: &str| LinkDefinition {
+            actor_id: "Mecho".to_string(),
+            provider_id: "Vhttp".to_string(),
+            contract_id: "wasmcloud:httpserver".to_string(),
+            link_name: name.to_string(),
+            ..Default::default()
+        };
+        kv::put_link(&store, &link("old")).await.unwrap();
+
+        let mut changes = client.link_changes_receiver().await.unwrap();
+        kv::delete_link(&store, "Mecho", "wasmcloud:httpserver", "old")
+            .await
+            .unwrap();
+        kv::put_link(&store, &link("new")).await.unwrap();
+
+        // The link written before the watch isn't reported, but deleting it is
+        let mut seen = Vec::new();
+        while let Ok(Some(change)) =
+            tokio::time::timeout(Duration::from_secs(1), changes.recv()).await
+        {
+            seen.push(change);
+        }
+        assert_eq!(
+            seen,
+            vec![
+                LinkChange::Delete {
+                    actor_id: "Mecho".to_string(),
+                    contract_id: "wasmcloud:httpserver".to_string(),
+                    link_name: "old".to_string(),
+                },
+                LinkChange::Put(link("new")),
+            ]
+        );
+        drop(changes);
+        js.delete_key_value(kv::bucket_name("linkwatchtest"))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_send_batch_with_deadline_reports_unattempted() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let _host = FakeHost::start(
+            nc.clone(),
+            "batchtest",
+            "NSLOW",
+            FakeHostConfig {
+                delay: Duration::from_millis(300),
+                ..Default::default()
+            },
+        )
+        .await;
+        let client = ClientBuilder::new(nc).lattice_prefix("batchtest").build();
+        let batch = CommandBatch::new()
+            .push(stop("NSLOW", "one"))
+            .push(stop("NSLOW", "two"))
+            .push(stop("NSLOW", "three"))
+            .push(stop("NSLOW", "four"));
+        let report = client
+            .send_batch_with_deadline("NSLOW", batch, Duration::from_millis(700))
+            .await
+            .unwrap();
+        // Two commands fit in the budget, the third is cut short by the deadline and the fourth is
+        // never sent
+        assert_eq!(
+            report
+                .acks
+                .iter()
+                .map(|ack| ack.accepted)
+                .collect::<Vec<_>>(),
+            vec![true, true, false]
+        );
+        assert_eq!(report.not_attempted, vec![stop("NSLOW", "four")]);
+        assert!(report.deadline_exceeded);
+
+        // Cutting off the last command still counts as exceeding the deadline
+        let batch = CommandBatch::new()
+            .push(stop("NSLOW", "one"))
+            .push(stop("NSLOW", "two"))
+            .stop_on_failure(true);
+        let report = client
+            .send_batch_with_deadline("NSLOW", batch, Duration::from_millis(450))
+            .await
+            .unwrap();
+        assert_eq!(
+            report
+                .acks
+                .iter()
+                .map(|ack| ack.accepted)
+                .collect::<Vec<_>>(),
+            vec![true, false]
+        );
+        assert!(report.not_attempted.is_empty());
+        assert!(report.deadline_exceeded);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_send_batch_with_deadline_to_slow_batching_host() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let _host = FakeHost::start(
+            nc.clone(),
+            "batchtest",
+            "NSLOWBATCH",
+            FakeHostConfig {
+                supports_batch: true,
+                delay: Duration::from_millis(500),
+                ..Default::default()
+            },
+        )
+        .await;
+        let client = ClientBuilder::new(nc).lattice_prefix("batchtest").build();
+        let batch = CommandBatch::new().push(stop("NSLOWBATCH", "one"));
+        let report = client
+            .send_batch_with_deadline("NSLOWBATCH", batch, Duration::from_millis(200))
+            .await
+            .unwrap();
+        assert!(report.acks.is_empty());
+        assert!(report.deadline_exceeded);
+    }
+
+    fn scale(host_id: &str, actor_ref: &str) -> BatchCommand {
+        BatchCommand::ScaleActor(ScaleActorCommand {
+            actor_ref: actor_ref.to_string(),
+            host_id: host_id.to_string(),
+            max_concurrent: Some(1),
+            annotations: None,
+            command_id: None,
+        })
+    }
+
+    fn stop(host_id: &str, actor_ref: &str) -> BatchCommand {
+        BatchCommand::StopActor(StopActorCommand {
+            actor_ref: actor_ref.to_string(),
+            host_id: host_id.to_string(),
+            annotations: None,
+            command_id: None,
+        })
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_send_batch_to_batching_host() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let host = FakeHost::start(
+            nc.clone(),
+            "batchtest",
+            "NBATCHING",
+            FakeHostConfig {
+                supports_batch: true,
+                ..Default::default()
+            },
+        )
+        .await;
+        let client = ClientBuilder::new(nc).lattice_prefix("batchtest").build();
+        let batch = CommandBatch::new()
+            .push(stop("NBATCHING", "echo"))
+            .push(scale("NBATCHING", "echo:2"));
+        let acks = client.send_batch("NBATCHING", batch).await.unwrap();
+        assert_eq!(acks.len(), 2);
+        assert!(acks.iter().all(|ack| ack.accepted));
+        assert_eq!(host.received(), vec!["batch", "sa", "scale"]);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_send_batch_falls_back_to_sequential() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let host = FakeHost::start(
+            nc.clone(),
+            "batchtest",
+            "NSEQUENTIAL",
+            FakeHostConfig {
+                reject: vec!["scale"],
+                ..Default::default()
+            },
+        )
+        .await;
+        let client = ClientBuilder::new(nc).lattice_prefix("batchtest").build();
+
+        let batch = CommandBatch::new()
+            .push(stop("NSEQUENTIAL", "echo"))
+            .push(scale("NSEQUENTIAL", "echo:2"))
+            .push(stop("NSEQUENTIAL", "kvcounter"));
+        let acks = client
+            .send_batch("NSEQUENTIAL", batch.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            acks.iter().map(|ack| ack.accepted).collect::<Vec<_>>(),
+            vec![true, false, true]
+        );
+        assert_eq!(host.received(), vec!["sa", "scale", "sa"]);
+
+        let acks = client
+            .send_batch("NSEQUENTIAL", batch.stop_on_failure(true))
+            .await
+            .unwrap();
+        assert_eq!(
+            acks.iter().map(|ack| ack.accepted).collect::<Vec<_>>(),
+            vec![true, false]
+        );
+        assert_eq!(host.received(), vec!["sa", "scale", "sa", "sa", "scale"]);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_send_batch_keeps_acks_when_a_command_times_out() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let host = FakeHost::start(
+            nc.clone(),
+            "batchtest",
+            "NSILENT",
+            FakeHostConfig {
+                ignore: vec!["scale"],
+                ..Default::default()
+            },
+        )
+        .await;
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("batchtest")
+            .timeout(Duration::from_millis(500))
+            .build();
+
+        let batch = CommandBatch::new()
+            .push(stop("NSILENT", "echo"))
+            .push(scale("NSILENT", "echo:2"))
+            .push(stop("NSILENT", "kvcounter"))
+            .stop_on_failure(true);
+        let acks = client.send_batch("NSILENT", batch).await.unwrap();
+        // The unanswered command is recorded as a negative ack rather than losing the first one
+        assert_eq!(
+            acks.iter().map(|ack| ack.accepted).collect::<Vec<_>>(),
+            vec![true, false]
+        );
+        assert!(acks[1].error.contains("timed out"), "{}", acks[1].error);
+        assert_eq!(host.received(), vec!["sa", "scale"]);
+    }
+
+    /// Note: This test requires a local NATS server
+    #[tokio::test]
+    #[ignore]
+    async fn test_bulk_links_report_each_link() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        // No bucket and no hosts, so every link is sent as a request that nothing answers
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("bulklinktest")
+            .timeout(Duration::from_millis(500))
+            .build();
+        let links = ["one", "two", "three"]
+            .into_iter()
+            .map(|name| LinkDefinition {
+                actor_id: "Mxxx".to_string(),
+                provider_id: "Vxxx".to_string(),
+                contract_id: "wasmcloud:httpserver".to_string(),
+                link_name: name.to_string(),
+                values: HashMap::new(),
+            })
+            .collect();
+        let acks = client.advertise_links(links).await.unwrap();
+        assert_eq!(acks.len(), 3);
+        assert!(acks
+            .iter()
+            .all(|ack| !ack.accepted && ack.error.contains("no responders")));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_get_hosts_expecting_returns_early() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let _host = FakeHost::start(
+            nc.clone(),
+            "expecttest",
+            "NEXPECT1",
+            FakeHostConfig::default(),
+        )
+        .await;
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("expecttest")
+            .auction_timeout(Duration::from_secs(30))
+            .build();
+
+        let start = std::time::Instant::now();
+        let hosts = client.get_hosts_expecting(1).await.unwrap();
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].id, "NEXPECT1");
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_actor_auction_stream_yields_first_bid_early() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let _host = FakeHost::start(
+            nc.clone(),
+            "streamtest",
+            "NSTREAM1",
+            FakeHostConfig::default(),
+        )
+        .await;
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("streamtest")
+            .auction_timeout(Duration::from_secs(30))
+            .build();
+
+        let start = std::time::Instant::now();
+        let mut bids = client
+            .perform_actor_auction_stream("echo", HashMap::new(), &AuctionOptions::default())
+            .await
+            .unwrap();
+        let bid = bids.recv().await.unwrap();
+        assert_eq!(bid.host_id, "NSTREAM1");
+        assert!(start.elapsed() < Duration::from_secs(5));
+        drop(bids);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_spread_actor_reports_progress() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let mut hosts = Vec::new();
+        for host_id in ["NSPREAD1", "NSPREAD2"] {
+            hosts.push(
+                FakeHost::start(nc.clone(), "spreadtest", host_id, FakeHostConfig::default()).await,
+            );
+        }
+        let client = ClientBuilder::new(nc).lattice_prefix("spreadtest").build();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let acks = client
+            .spread_actor("echo", HashMap::new(), 2, Some(1), Some(tx))
+            .await
+            .unwrap();
+        assert_eq!(acks.len(), 2);
+        assert!(acks.iter().all(|ack| ack.accepted));
+
+        let mut events = Vec::new();
+        while let Some(event) = rx.recv().await {
+            events.push(event);
+        }
+        let sent = |host: &str| ProgressEvent::CommandSent {
+            host: host.to_string(),
+            op: "scale_actor".to_string(),
+        };
+        let acked = |host: &str| ProgressEvent::CommandAcked {
+            host: host.to_string(),
+            op: "scale_actor".to_string(),
+        };
+        assert_eq!(
+            events,
+            vec![
+                ProgressEvent::AuctionStarted {
+                    reference: "echo".to_string()
+                },
+                ProgressEvent::HostSelected {
+                    host: "NSPREAD1".to_string()
+                },
+                ProgressEvent::HostSelected {
+                    host: "NSPREAD2".to_string()
+                },
+                sent("NSPREAD1"),
+                acked("NSPREAD1"),
+                sent("NSPREAD2"),
+                acked("NSPREAD2"),
+            ]
+        );
+        for host in &hosts {
+            assert_eq!(host.received(), vec!["auction", "scale"]);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_serialized_host_commands_arrive_in_order() {
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let host = FakeHost::start(
+            nc.clone(),
+            "ordertest",
+            "NORDERED",
+            FakeHostConfig {
+                // Like a real host, stopping takes a while and commands are handled concurrently,
+                // so a scale sent while the stop is in progress is carried out first
+                op_delays: vec![("sa", Duration::from_millis(300))],
+                concurrent: true,
+                ..Default::default()
+            },
+        )
+        .await;
+        let unordered = ClientBuilder::new(nc.clone())
+            .lattice_prefix("ordertest")
+            .build();
+        let (stopped, scaled) = tokio::join!(
+            unordered.stop_actor("NORDERED", "echo:1", None),
+            unordered.scale_actor("NORDERED", "echo:2", Some(1), None),
+        );
+        assert!(stopped.unwrap().accepted && scaled.unwrap().accepted);
+        assert_eq!(host.received(), vec!["scale", "sa"]);
+
+        let client = ClientBuilder::new(nc)
+            .lattice_prefix("ordertest")
+            .serialize_host_commands(true)
+            .build();
+        let stop = client.stop_actor("NORDERED", "echo:1", None);
+        let scale = client.scale_actor("NORDERED", "echo:2", Some(1), None);
+        let depth = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            client.host_queue_depth("NORDERED")
+        };
+        let (stopped, scaled, depth) = tokio::join!(stop, scale, depth);
+        assert!(stopped.unwrap().accepted);
+        assert!(scaled.unwrap().accepted);
+        assert_eq!(depth, 2);
+        assert_eq!(host.received()[2..], ["sa", "scale"]);
+        assert_eq!(client.host_queue_depth("NORDERED"), 0);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_mirror_events_stores_each_event_once() {
+        use cloudevents::EventBuilder as _;
+
+        let nc = async_nats::connect("127.0.0.1:4222").await.unwrap();
+        let js = async_nats::jetstream::new(nc.clone());
+        let _ = js.delete_stream("MIRRORTEST").await;
+        let client = ClientBuilder::new(nc.clone())
+            .lattice_prefix("mirrortest")
+            .build();
+        let mut handle = client
+            .mirror_events_to_stream("MIRRORTEST", MirrorConfig::default())
+            .await
+            .unwrap();
+
+        let event = |id: &str| {
+            let event = cloudevents::EventBuilderV10::new()
+                .id(id)
+                .source("NMIRROR")
+                .ty("com.wasmcloud.lattice.actor_started")
+                .build()
+                .unwrap();
+            serde_json::to_vec(&event).unwrap()
+        };
+        let subject = broker::control_event("mirrortest");
+        for payload in [event("one"), event("two"), event("one"), b"nope".to_vec()] {
+            nc.publish(subject.clone(), payload.into()).await.unwrap();
+        }
+        nc.flush().await.unwrap();
+
+        let failure = handle.next_failure().await.unwrap();
+        assert_eq!(failure.event_id, None);
+        assert_eq!(
+            handle.shutdown().await,
+            MirrorStats {
+                mirrored: 2,
+                duplicates: 1,
+                failed: 1,
+            }
+        );
+        let mut stream = js.get_stream("MIRRORTEST").await.unwrap();
+        assert_eq!(stream.info().await.unwrap().state.messages, 2);
+        js.delete_stream("MIRRORTEST").await.unwrap();
     }
 }
@@ -0,0 +1,109 @@
+//! Selection of link definitions by what they connect
+
+use crate::LinkDefinition;
+
+/// Selects which links [`crate::Client::query_links_filtered`] returns. Each field that is set must
+/// match exactly, so setting several narrows the selection; [`LinkFilter::new`] on its own matches
+/// every link
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LinkFilter {
+    pub(crate) actor_id: Option<String>,
+    pub(crate) provider_id: Option<String>,
+    pub(crate) contract_id: Option<String>,
+    pub(crate) link_name: Option<String>,
+}
+
+impl LinkFilter {
+    /// Creates a filter that matches every link
+    pub fn new() -> LinkFilter {
+        LinkFilter::default()
+    }
+
+    /// Matches links from the actor with the given public key
+    pub fn actor_id(self, actor_id: impl Into<String>) -> LinkFilter {
+        LinkFilter {
+            actor_id: Some(actor_id.into()),
+            ..self
+        }
+    }
+
+    /// Matches links to the provider with the given public key
+    pub fn provider_id(self, provider_id: impl Into<String>) -> LinkFilter {
+        LinkFilter {
+            provider_id: Some(provider_id.into()),
+            ..self
+        }
+    }
+
+    /// Matches links for the given contract
+    pub fn contract_id(self, contract_id: impl Into<String>) -> LinkFilter {
+        LinkFilter {
+            contract_id: Some(contract_id.into()),
+            ..self
+        }
+    }
+
+    /// Matches links with the given link name
+    pub fn link_name(self, link_name: impl Into<String>) -> LinkFilter {
+        LinkFilter {
+            link_name: Some(link_name.into()),
+            ..self
+        }
+    }
+
+    /// Returns whether the link passes the filter
+    pub fn matches(&self, link: &LinkDefinition) -> bool {
+        fn field(wanted: &Option<String>, actual: &str) -> bool {
+            match wanted {
+                Some(wanted) => wanted == actual,
+                None => true,
+            }
+        }
+        field(&self.actor_id, &link.actor_id)
+            && field(&self.provider_id, &link.provider_id)
+            && field(&self.contract_id, &link.contract_id)
+            && field(&self.link_name, &link.link_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(actor_id: &str, provider_id: &str, link_name: &str) -> LinkDefinition {
+        LinkDefinition {
+            actor_id: actor_id.to_string(),
+            provider_id: provider_id.to_string(),
+            contract_id: "wasmcloud:httpserver".to_string(),
+            link_name: link_name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn empty_filter_matches_every_link() {
+        assert!(LinkFilter::new().matches(&link("M1", "V1", "default")));
+        assert!(LinkFilter::new().matches(&LinkDefinition::default()));
+    }
+
+    #[test]
+    fn fields_must_all_match() {
+        let filter = LinkFilter::new().provider_id("V1").link_name("default");
+        assert!(filter.matches(&link("M1", "V1", "default")));
+        assert!(filter.matches(&link("M2", "V1", "default")));
+        assert!(!filter.matches(&link("M1", "V1", "backup")));
+        assert!(!filter.matches(&link("M1", "V2", "default")));
+    }
+
+    #[test]
+    fn fields_match_exactly() {
+        let filter = LinkFilter::new()
+            .actor_id("M1")
+            .contract_id("wasmcloud:httpserver");
+        assert!(filter.matches(&link("M1", "V1", "default")));
+        assert!(!filter.matches(&link("M12", "V1", "default")));
+        assert!(!LinkFilter::new()
+            .contract_id("wasmcloud:http")
+            .matches(&link("M1", "V1", "default")));
+    }
+}
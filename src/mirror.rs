@@ -0,0 +1,191 @@
+//! Republishing of lattice control events into a JetStream stream for durable retention, for
+//! cases where the host configuration that would normally mirror them can't be changed
+
+use std::time::Duration;
+
+use async_nats::jetstream::{self, stream};
+use async_nats::HeaderMap;
+use cloudevents::{AttributesReader, Event};
+use futures::StreamExt;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::{json_deserialize, Error, Result};
+
+/// How many unread failures a [`MirrorHandle`] keeps before it starts discarding them. Discarded
+/// failures are still counted in [`MirrorStats::failed`]
+const FAILURE_BUFFER: usize = 64;
+
+/// Settings for [`crate::Client::mirror_events_to_stream`]. The retention settings are only used
+/// if the stream has to be created
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MirrorConfig {
+    /// The subject events are republished on. If the stream already exists it must capture this
+    /// subject. Defaults to `{stream_name}.{lattice_prefix}`
+    pub subject: Option<String>,
+    /// The longest time events are kept. Zero means no limit
+    pub max_age: Duration,
+    /// The most events kept. -1 means no limit
+    pub max_messages: i64,
+    /// The most bytes kept. -1 means no limit
+    pub max_bytes: i64,
+    /// How long JetStream remembers event IDs for deduplication
+    pub duplicate_window: Duration,
+    /// The number of stream replicas
+    pub replicas: usize,
+}
+
+impl Default for MirrorConfig {
+    fn default() -> MirrorConfig {
+        MirrorConfig {
+            subject: None,
+            max_age: Duration::ZERO,
+            max_messages: -1,
+            max_bytes: -1,
+            duplicate_window: Duration::from_secs(120),
+            replicas: 1,
+        }
+    }
+}
+
+/// Counts of what happened to the events seen by a mirror
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MirrorStats {
+    /// Events stored in the stream
+    pub mirrored: u64,
+    /// Events the stream had already stored with the same CloudEvent ID
+    pub duplicates: u64,
+    /// Events that could not be mirrored
+    pub failed: u64,
+}
+
+/// An event that could not be mirrored
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MirrorFailure {
+    /// The CloudEvent ID, if the event could be parsed
+    pub event_id: Option<String>,
+    /// What went wrong
+    pub error: String,
+}
+
+/// A running event mirror. Dropping the handle stops the mirror immediately; use
+/// [`MirrorHandle::shutdown`] to stop it after the event in progress has been stored
+pub struct MirrorHandle {
+    shutdown: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<MirrorStats>>,
+    failures: mpsc::Receiver<MirrorFailure>,
+}
+
+impl MirrorHandle {
+    /// Waits for the next event that could not be mirrored. Returns `None` once the mirror has
+    /// stopped and every failure has been read
+    pub async fn next_failure(&mut self) -> Option<MirrorFailure> {
+        self.failures.recv().await
+    }
+
+    /// Stops the mirror once the event in progress (if any) has been stored, returning counts of
+    /// everything it handled
+    pub async fn shutdown(mut self) -> MirrorStats {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        match self.task.take() {
+            Some(task) => task.await.unwrap_or_default(),
+            None => MirrorStats::default(),
+        }
+    }
+}
+
+impl Drop for MirrorHandle {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+/// Creates the stream if needed and starts republishing everything received on `events`
+pub(crate) async fn start(
+    context: jetstream::Context,
+    mut events: async_nats::Subscriber,
+    stream_name: &str,
+    subject: String,
+    config: MirrorConfig,
+) -> Result<MirrorHandle> {
+    context
+        .get_or_create_stream(stream::Config {
+            name: stream_name.to_string(),
+            subjects: vec![subject.clone()],
+            max_age: config.max_age,
+            max_messages: config.max_messages,
+            max_bytes: config.max_bytes,
+            duplicate_window: config.duplicate_window,
+            num_replicas: config.replicas,
+            ..Default::default()
+        })
+        .await
+        .map_err(Error::nats)?;
+
+    let (shutdown_tx, mut shutdown_rx) = oneshot::channel();
+    let (failure_tx, failures) = mpsc::channel(FAILURE_BUFFER);
+    let task = tokio::spawn(async move {
+        let mut stats = MirrorStats::default();
+        loop {
+            let msg = tokio::select! {
+                _ = &mut shutdown_rx => break,
+                msg = events.next() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+            };
+            match mirror_event(&context, &subject, msg.payload).await {
+                Ok(false) => stats.mirrored += 1,
+                Ok(true) => stats.duplicates += 1,
+                Err(failure) => {
+                    warn!(event_id = ?failure.event_id, error = %failure.error, "failed to mirror event");
+                    stats.failed += 1;
+                    let _ = failure_tx.try_send(failure);
+                }
+            }
+        }
+        let _ = events.unsubscribe().await;
+        debug!(?stats, "event mirror stopped");
+        stats
+    });
+
+    Ok(MirrorHandle {
+        shutdown: Some(shutdown_tx),
+        task: Some(task),
+        failures,
+    })
+}
+
+/// Publishes one event, using its CloudEvent ID as the JetStream message ID. Returns whether the
+/// stream reported the event as a duplicate
+async fn mirror_event(
+    context: &jetstream::Context,
+    subject: &str,
+    payload: bytes::Bytes,
+) -> std::result::Result<bool, MirrorFailure> {
+    let event_id = json_deserialize::<Event>(&payload)
+        .map_err(|e| MirrorFailure {
+            event_id: None,
+            error: format!("Object received on event stream was not a CloudEvent: {e}"),
+        })?
+        .id()
+        .to_string();
+    let failure = |error: String| MirrorFailure {
+        event_id: Some(event_id.clone()),
+        error,
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(async_nats::header::NATS_MESSAGE_ID, event_id.as_str());
+    let ack = context
+        .publish_with_headers(subject.to_string(), headers, payload)
+        .await
+        .map_err(|e| failure(format!("Failed to publish event: {e}")))?
+        .await
+        .map_err(|e| failure(format!("Event was not acknowledged by the stream: {e}")))?;
+    Ok(ack.duplicate)
+}
@@ -0,0 +1,174 @@
+//! Managing several lattices over a single NATS connection
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use futures::future;
+use tokio::sync::Mutex;
+
+use crate::{Client, Host, Result};
+
+/// Hands out a [`Client`] per lattice, all sharing one NATS connection and the settings of the
+/// client it was created from. Each lattice's client is created the first time it is needed and
+/// then kept, along with the metadata bucket it found, until [`MultiLatticeClient::refresh`] is
+/// called. A lattice with no bucket yet looks for it again on every operation that could use it,
+/// so a bucket created later is picked up without a refresh; refreshing is only needed when a
+/// bucket is deleted and recreated.
+///
+/// ```no_run
+/// # async fn run(nc: async_nats::Client) -> wasmcloud_control_interface::Result<()> {
+/// use wasmcloud_control_interface::{ClientBuilder, MultiLatticeClient};
+///
+/// let lattices = MultiLatticeClient::new(ClientBuilder::new(nc).build())
+///     .lattice("staging")
+///     .lattice_in_domain("edge", Some("leaf".to_string()));
+/// for (lattice, hosts) in lattices.get_hosts_all_lattices().await? {
+///     println!("{lattice}: {} hosts", hosts.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct MultiLatticeClient {
+    base: Client,
+    /// The JetStream domain of each lattice
+    lattices: BTreeMap<String, Option<String>>,
+    clients: Arc<Mutex<HashMap<String, Client>>>,
+}
+
+impl MultiLatticeClient {
+    /// Creates a multi-lattice client whose per-lattice clients copy the settings of `base`. The
+    /// lattice `base` was built for isn't included unless it is added
+    pub fn new(base: Client) -> MultiLatticeClient {
+        MultiLatticeClient {
+            base,
+            lattices: BTreeMap::new(),
+            clients: Arc::default(),
+        }
+    }
+
+    /// Adds a lattice whose metadata bucket is in the base client's JetStream domain
+    pub fn lattice(self, lattice_prefix: impl Into<String>) -> MultiLatticeClient {
+        let js_domain = self.base.js_domain.clone();
+        self.lattice_in_domain(lattice_prefix, js_domain)
+    }
+
+    /// Adds a lattice whose metadata bucket is in the given JetStream domain, or the default domain
+    /// if `None`
+    pub fn lattice_in_domain(
+        mut self,
+        lattice_prefix: impl Into<String>,
+        js_domain: Option<String>,
+    ) -> MultiLatticeClient {
+        self.lattices.insert(lattice_prefix.into(), js_domain);
+        self
+    }
+
+    /// Returns the prefixes of the lattices that were added, in order
+    pub fn lattices(&self) -> impl Iterator<Item = &str> {
+        self.lattices.keys().map(String::as_str)
+    }
+
+    /// Returns the client for a lattice, creating it with [`Client::for_lattice_in_domain`] the
+    /// first time. A lattice that wasn't added uses the base client's JetStream domain
+    pub async fn client(&self, lattice_prefix: &str) -> Result<Client> {
+        if let Some(client) = self.clients.lock().await.get(lattice_prefix) {
+            return Ok(client.clone());
+        }
+        let js_domain = match self.lattices.get(lattice_prefix) {
+            Some(js_domain) => js_domain.clone(),
+            None => self.base.js_domain.clone(),
+        };
+        // Looked up without holding the lock, so one slow lattice doesn't hold up the others. If
+        // two callers race, the first client stored wins
+        let client = self
+            .base
+            .for_lattice_in_domain(lattice_prefix, js_domain)
+            .await?;
+        Ok(self
+            .clients
+            .lock()
+            .await
+            .entry(lattice_prefix.to_string())
+            .or_insert(client)
+            .clone())
+    }
+
+    /// Forgets the client for a lattice, so that the next call creates it again and looks its
+    /// metadata bucket up afresh
+    pub async fn refresh(&self, lattice_prefix: &str) {
+        self.clients.lock().await.remove(lattice_prefix);
+    }
+
+    /// Forgets the clients for every lattice
+    pub async fn refresh_all(&self) {
+        self.clients.lock().await.clear();
+    }
+
+    /// Queries every lattice that was added for its responsive hosts, all at once, returning the
+    /// hosts keyed by lattice prefix
+    pub async fn get_hosts_all_lattices(&self) -> Result<HashMap<String, Vec<Host>>> {
+        future::try_join_all(self.lattices.keys().map(|lattice_prefix| async move {
+            let hosts = self.client(lattice_prefix).await?.get_hosts().await?;
+            Ok::<_, crate::Error>((lattice_prefix.clone(), hosts))
+        }))
+        .await
+        .map(|hosts| hosts.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::tests::offline_nats;
+    use crate::{ClientBuilder, Error};
+
+    async fn base() -> Client {
+        ClientBuilder::new(offline_nats().await)
+            .js_domain("hub")
+            .disable_kv_store()
+            .timeout(Duration::from_millis(7))
+            .auction_timeout(Duration::from_millis(20))
+            .build()
+    }
+
+    #[tokio::test]
+    async fn clients_are_created_once_per_lattice() {
+        let lattices = MultiLatticeClient::new(base().await)
+            .lattice("staging")
+            .lattice_in_domain("edge", None);
+        assert_eq!(lattices.lattices().collect::<Vec<_>>(), ["edge", "staging"]);
+
+        let staging = lattices.client("staging").await.unwrap();
+        assert_eq!(staging.lattice_prefix, "staging");
+        assert_eq!(staging.js_domain.as_deref(), Some("hub"));
+        assert_eq!(staging.timeout, Duration::from_millis(7));
+        assert!(Arc::ptr_eq(
+            &staging.kvstore,
+            &lattices.client("staging").await.unwrap().kvstore
+        ));
+        assert_eq!(lattices.client("edge").await.unwrap().js_domain, None);
+
+        lattices.refresh("staging").await;
+        assert!(!Arc::ptr_eq(
+            &staging.kvstore,
+            &lattices.client("staging").await.unwrap().kvstore
+        ));
+        assert!(matches!(
+            lattices.client("no.such").await,
+            Err(Error::InvalidArgument { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn hosts_are_gathered_from_every_lattice() {
+        let lattices = MultiLatticeClient::new(base().await)
+            .lattice("a")
+            .lattice("b");
+        let hosts = lattices.get_hosts_all_lattices().await.unwrap();
+        assert_eq!(hosts.len(), 2);
+        assert!(hosts["a"].is_empty() && hosts["b"].is_empty());
+    }
+}
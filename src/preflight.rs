@@ -0,0 +1,401 @@
+//! A battery of cheap checks that tools can run at startup to turn "nothing works" into a specific
+//! diagnosis: wrong NATS URL, wrong lattice prefix, missing permissions, or no hosts
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{broker, kv, AuctionOptions, Client, Error};
+
+/// Round trips slower than this are reported as a warning
+const SLOW_RTT: Duration = Duration::from_millis(500);
+
+/// The individual checks run by [`Client::preflight`], in the order they run
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreflightCheck {
+    /// The NATS server can be reached, and how long a round trip takes
+    NatsRtt,
+    /// Requests can be published on the control interface subjects
+    PublishPermission,
+    /// The lattice event subject can be subscribed to and subscriptions receive messages
+    SubscribePermission,
+    /// The lattice metadata bucket is visible
+    KvBucket,
+    /// At least one host in the lattice responds
+    HostResponsiveness,
+}
+
+impl PreflightCheck {
+    /// Every check, in the order they run
+    pub const ALL: [PreflightCheck; 5] = [
+        PreflightCheck::NatsRtt,
+        PreflightCheck::PublishPermission,
+        PreflightCheck::SubscribePermission,
+        PreflightCheck::KvBucket,
+        PreflightCheck::HostResponsiveness,
+    ];
+}
+
+/// The outcome of a single check
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    /// The check found something that limits what the client can do, but isn't fatal
+    Warn,
+    Fail,
+    /// The check was skipped with [`PreflightOptions::skip`]
+    Skipped,
+}
+
+/// The result of a single check
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CheckResult {
+    pub check: PreflightCheck,
+    pub status: CheckStatus,
+    /// What the check observed
+    pub detail: String,
+    /// What to try if the check didn't pass
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+}
+
+/// The results of every check, in the order they ran
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PreflightReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl PreflightReport {
+    /// Returns true if no check failed. Warnings and skipped checks don't count as failures
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|result| result.status != CheckStatus::Fail)
+    }
+
+    /// Returns the result of the given check
+    pub fn get(&self, check: PreflightCheck) -> Option<&CheckResult> {
+        self.checks.iter().find(|result| result.check == check)
+    }
+}
+
+/// Controls which checks [`Client::preflight_with_options`] runs
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PreflightOptions {
+    skipped: HashSet<PreflightCheck>,
+}
+
+impl PreflightOptions {
+    /// Creates options that run every check
+    pub fn new() -> PreflightOptions {
+        PreflightOptions::default()
+    }
+
+    /// Skips a check, e.g. one that is known to fail in a restricted environment. Skipped checks
+    /// are still listed in the report
+    pub fn skip(mut self, check: PreflightCheck) -> PreflightOptions {
+        self.skipped.insert(check);
+        self
+    }
+}
+
+/// Runs every check that isn't skipped
+pub(crate) async fn run(client: &Client, options: &PreflightOptions) -> PreflightReport {
+    let mut report = PreflightReport::default();
+    for check in PreflightCheck::ALL {
+        let result = if options.skipped.contains(&check) {
+            CheckResult {
+                check,
+                status: CheckStatus::Skipped,
+                detail: "skipped".to_string(),
+                remediation: None,
+            }
+        } else {
+            match check {
+                PreflightCheck::NatsRtt => nats_rtt(client).await,
+                PreflightCheck::PublishPermission => publish_permission(client).await,
+                PreflightCheck::SubscribePermission => subscribe_permission(client).await,
+                PreflightCheck::KvBucket => kv_bucket(client).await,
+                PreflightCheck::HostResponsiveness => host_responsiveness(client).await,
+            }
+        };
+        report.checks.push(result);
+    }
+    report
+}
+
+fn result(
+    check: PreflightCheck,
+    status: CheckStatus,
+    detail: impl Into<String>,
+    remediation: Option<&str>,
+) -> CheckResult {
+    CheckResult {
+        check,
+        status,
+        detail: detail.into(),
+        remediation: remediation.map(str::to_string),
+    }
+}
+
+async fn nats_rtt(client: &Client) -> CheckResult {
+    let check = PreflightCheck::NatsRtt;
+    let remediation = "Check the NATS URL and that the server is running and reachable";
+    let start = Instant::now();
+    match tokio::time::timeout(client.timeout, client.nc.flush()).await {
+        Ok(Ok(())) => {
+            let rtt = start.elapsed();
+            if rtt > SLOW_RTT {
+                result(
+                    check,
+                    CheckStatus::Warn,
+                    format!("round trip took {rtt:?}"),
+                    Some("The NATS server is slow to respond; check network latency to it"),
+                )
+            } else {
+                result(
+                    check,
+                    CheckStatus::Pass,
+                    format!("round trip took {rtt:?}"),
+                    None,
+                )
+            }
+        }
+        Ok(Err(e)) => result(
+            check,
+            CheckStatus::Fail,
+            format!("flush failed: {e}"),
+            Some(remediation),
+        ),
+        Err(_) => result(
+            check,
+            CheckStatus::Fail,
+            format!("no round trip within {:?}", client.timeout),
+            Some(remediation),
+        ),
+    }
+}
+
+async fn publish_permission(client: &Client) -> CheckResult {
+    let check = PreflightCheck::PublishPermission;
+    // The link definition query doesn't change anything and is answered by any host
+    let subject = broker::queries::link_definitions(&client.topic_prefix, &client.lattice_prefix);
+    match client
+        .request_timeout(subject.clone(), Vec::new(), client.timeout)
+        .await
+    {
+        Ok(_) => result(
+            check,
+            CheckStatus::Pass,
+            format!("query on {subject} was answered"),
+            None,
+        ),
+        Err(Error::NoResponders { .. }) => result(
+            check,
+            CheckStatus::Warn,
+            format!("query on {subject} was published but no host is listening"),
+            Some("Check the lattice prefix and that at least one host is running"),
+        ),
+        Err(e) => result(
+            check,
+            CheckStatus::Fail,
+            format!("query on {subject} failed: {e}"),
+            Some("Check that the NATS credentials allow publishing to the control interface subjects"),
+        ),
+    }
+}
+
+async fn subscribe_permission(client: &Client) -> CheckResult {
+    let check = PreflightCheck::SubscribePermission;
+    let remediation =
+        "Check that the NATS credentials allow subscribing to the lattice event subject and to reply inboxes";
+    let subject = broker::control_event(&client.lattice_prefix);
+    let mut events = match client.nc.subscribe(subject.clone()).await {
+        Ok(sub) => sub,
+        Err(e) => {
+            return result(
+                check,
+                CheckStatus::Fail,
+                format!("subscribing to {subject} failed: {e}"),
+                Some(remediation),
+            )
+        }
+    };
+    // The server reports a denied subscription asynchronously and a flush still succeeds, so only
+    // a message that actually comes back proves that subscriptions are delivered to. Nothing can
+    // be published on the event subject without confusing other consumers, so the probe goes to
+    // a reply inbox instead
+    let inbox = client.new_inbox();
+    let echoed = async {
+        let mut probe = client.nc.subscribe(inbox.clone()).await.ok()?;
+        client
+            .nc
+            .publish(inbox.clone(), "preflight".into())
+            .await
+            .ok()?;
+        let echoed = tokio::time::timeout(client.timeout, probe.next())
+            .await
+            .ok()
+            .flatten();
+        let _ = probe.unsubscribe().await;
+        echoed
+    }
+    .await;
+    let _ = events.unsubscribe().await;
+    match echoed {
+        Some(_) => result(
+            check,
+            CheckStatus::Pass,
+            format!("subscribed to {subject} and received a probe message on {inbox}"),
+            None,
+        ),
+        None => result(
+            check,
+            CheckStatus::Fail,
+            format!(
+                "a probe message published to {inbox} did not arrive within {:?}",
+                client.timeout
+            ),
+            Some(remediation),
+        ),
+    }
+}
+
+async fn kv_bucket(client: &Client) -> CheckResult {
+    let check = PreflightCheck::KvBucket;
+    let bucket = kv::bucket_name(&client.lattice_prefix);
+    match client.kv_store().await {
+        Some(_) => result(check, CheckStatus::Pass, format!("found bucket {bucket}"), None),
+        None => result(
+            check,
+            CheckStatus::Warn,
+            format!("bucket {bucket} was not found; the client will use topic-only mode"),
+            Some("Check the lattice prefix, the JetStream domain, and that the NATS credentials allow JetStream access"),
+        ),
+    }
+}
+
+async fn host_responsiveness(client: &Client) -> CheckResult {
+    let check = PreflightCheck::HostResponsiveness;
+    let remediation = "Check the lattice prefix and that at least one host is running";
+    let options = AuctionOptions::new().min_results(1);
+    match client.get_hosts_with_options(&options).await {
+        Ok(hosts) if !hosts.is_empty() => result(
+            check,
+            CheckStatus::Pass,
+            format!("host {} responded", hosts[0].id),
+            None,
+        ),
+        Ok(_) => result(
+            check,
+            CheckStatus::Fail,
+            format!("no hosts responded in lattice {}", client.lattice_prefix),
+            Some(remediation),
+        ),
+        Err(e) => result(
+            check,
+            CheckStatus::Fail,
+            format!("host query failed: {e}"),
+            Some(remediation),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::offline_nats;
+    use crate::ClientBuilder;
+
+    fn skip_all_but(check: PreflightCheck) -> PreflightOptions {
+        PreflightCheck::ALL
+            .into_iter()
+            .filter(|other| *other != check)
+            .fold(PreflightOptions::new(), PreflightOptions::skip)
+    }
+
+    async fn offline_client() -> Client {
+        ClientBuilder::new(offline_nats().await)
+            .timeout(Duration::from_millis(50))
+            .build()
+    }
+
+    #[tokio::test]
+    async fn skipped_checks_are_reported() {
+        let client = offline_client().await;
+        let options = PreflightCheck::ALL
+            .into_iter()
+            .fold(PreflightOptions::new(), PreflightOptions::skip);
+        let report = run(&client, &options).await;
+        assert_eq!(report.checks.len(), PreflightCheck::ALL.len());
+        assert!(report
+            .checks
+            .iter()
+            .all(|result| result.status == CheckStatus::Skipped));
+        assert!(report.passed());
+    }
+
+    #[tokio::test]
+    async fn unreachable_server_fails_rtt() {
+        let client = offline_client().await;
+        let report = run(&client, &skip_all_but(PreflightCheck::NatsRtt)).await;
+        let rtt = report.get(PreflightCheck::NatsRtt).unwrap();
+        assert_eq!(rtt.status, CheckStatus::Fail);
+        assert!(rtt.remediation.is_some());
+        assert!(!report.passed());
+    }
+
+    #[tokio::test]
+    async fn unreachable_server_fails_publish_probe() {
+        let client = offline_client().await;
+        let report = run(&client, &skip_all_but(PreflightCheck::PublishPermission)).await;
+        assert_eq!(
+            report
+                .get(PreflightCheck::PublishPermission)
+                .unwrap()
+                .status,
+            CheckStatus::Fail
+        );
+    }
+
+    #[tokio::test]
+    async fn unreachable_server_fails_subscribe_probe() {
+        let client = offline_client().await;
+        let report = run(&client, &skip_all_but(PreflightCheck::SubscribePermission)).await;
+        assert_eq!(
+            report
+                .get(PreflightCheck::SubscribePermission)
+                .unwrap()
+                .status,
+            CheckStatus::Fail
+        );
+    }
+
+    #[test]
+    fn report_serializes_statuses() {
+        let report = PreflightReport {
+            checks: vec![
+                result(PreflightCheck::NatsRtt, CheckStatus::Pass, "fast", None),
+                result(
+                    PreflightCheck::KvBucket,
+                    CheckStatus::Warn,
+                    "missing",
+                    Some("create it"),
+                ),
+            ],
+        };
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({
+                "checks": [
+                    {"check": "nats_rtt", "status": "pass", "detail": "fast"},
+                    {"check": "kv_bucket", "status": "warn", "detail": "missing", "remediation": "create it"}
+                ]
+            })
+        );
+    }
+}
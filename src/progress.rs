@@ -0,0 +1,47 @@
+//! Delivery of [`ProgressEvent`]s to callers of long-running helpers
+
+use tokio::sync::mpsc::Sender;
+
+use crate::ProgressEvent;
+
+/// The optional progress channel a caller passed to a helper
+pub(crate) struct Progress(Option<Sender<ProgressEvent>>);
+
+impl Progress {
+    pub fn new(sender: Option<Sender<ProgressEvent>>) -> Progress {
+        Progress(sender)
+    }
+
+    /// Sends an event if the caller asked for progress. A caller that stops listening does not
+    /// cause the operation itself to fail
+    pub async fn emit(&self, event: ProgressEvent) {
+        if let Some(sender) = &self.0 {
+            let _ = sender.send(event).await;
+        }
+    }
+
+    pub async fn command_sent(&self, host: &str, op: &str) {
+        self.emit(ProgressEvent::CommandSent {
+            host: host.to_string(),
+            op: op.to_string(),
+        })
+        .await
+    }
+
+    /// Reports the outcome of a command as either [`ProgressEvent::CommandAcked`] or
+    /// [`ProgressEvent::PhaseFailed`]
+    pub async fn command_result(&self, host: &str, op: &str, error: Option<String>) {
+        let event = match error {
+            None => ProgressEvent::CommandAcked {
+                host: host.to_string(),
+                op: op.to_string(),
+            },
+            Some(error) => ProgressEvent::PhaseFailed {
+                phase: op.to_string(),
+                host: Some(host.to_string()),
+                error,
+            },
+        };
+        self.emit(event).await
+    }
+}
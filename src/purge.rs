@@ -0,0 +1,149 @@
+//! Planning for [`crate::Client::purge_host`]: which lattice metadata a host that died without
+//! cleaning up after itself leaves behind
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{kv, HostInventory, LinkDefinition, PurgePlan};
+
+/// Works out what to remove from the lattice metadata bucket for the host whose last known
+/// inventory is `dead`, given the inventories of every other host and what the bucket holds.
+///
+/// The claims of an actor or provider are removed if it ran on the dead host and runs on none of
+/// the `others`. Links are removed if their provider is one of those, since nothing is left to
+/// serve them. Links to any other provider are kept, even one that isn't running anywhere, as
+/// links are often defined before their provider is started
+pub(crate) fn plan(
+    dead: &HostInventory,
+    others: &[HostInventory],
+    claims: &[HashMap<String, String>],
+    links: &[LinkDefinition],
+) -> PurgePlan {
+    let running_elsewhere: BTreeSet<&str> = others
+        .iter()
+        .flat_map(|inventory| {
+            inventory
+                .actors
+                .iter()
+                .map(|actor| actor.id.as_str())
+                .chain(
+                    inventory
+                        .providers
+                        .iter()
+                        .map(|provider| provider.id.as_str()),
+                )
+        })
+        .collect();
+    let stranded: BTreeSet<&str> = dead
+        .actors
+        .iter()
+        .map(|actor| actor.id.as_str())
+        .chain(dead.providers.iter().map(|provider| provider.id.as_str()))
+        .filter(|id| !running_elsewhere.contains(id))
+        .collect();
+    let purged: BTreeSet<&str> = claims
+        .iter()
+        .filter_map(|claims| claims.get(kv::CLAIM_SUBJECT))
+        .map(String::as_str)
+        .filter(|subject| stranded.contains(subject))
+        .collect();
+    let links = links
+        .iter()
+        .filter(|link| stranded.contains(link.provider_id.as_str()))
+        .cloned()
+        .collect();
+    PurgePlan {
+        host_id: dead.host_id.clone(),
+        claims: purged.into_iter().map(str::to_string).collect(),
+        links,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ActorDescription, ProviderDescription};
+
+    fn inventory(host_id: &str, actors: &[&str], providers: &[&str]) -> HostInventory {
+        HostInventory {
+            host_id: host_id.to_string(),
+            actors: actors
+                .iter()
+                .map(|id| ActorDescription {
+                    id: id.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            providers: providers
+                .iter()
+                .map(|id| ProviderDescription {
+                    id: id.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn claims(subjects: &[&str]) -> Vec<HashMap<String, String>> {
+        subjects
+            .iter()
+            .map(|subject| HashMap::from([("sub".to_string(), subject.to_string())]))
+            .collect()
+    }
+
+    fn link(actor_id: &str, provider_id: &str) -> LinkDefinition {
+        LinkDefinition {
+            actor_id: actor_id.to_string(),
+            provider_id: provider_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn only_what_ran_nowhere_else_is_purged() {
+        let dead = inventory("NDEAD", &["Mecho", "Mshared"], &["Vhttp", "Vredis"]);
+        let others = [inventory("NLIVE", &["Mshared"], &["Vredis"])];
+        let links = [
+            link("Mecho", "Vhttp"),
+            link("Mshared", "Vredis"),
+            link("Mecho", "Vnotstarted"),
+        ];
+        let plan = plan(
+            &dead,
+            &others,
+            &claims(&["Mecho", "Mshared", "Vhttp", "Vredis", "Mother"]),
+            &links,
+        );
+        assert_eq!(plan.host_id, "NDEAD");
+        assert_eq!(plan.claims, ["Mecho", "Vhttp"]);
+        assert_eq!(plan.links, [link("Mecho", "Vhttp")]);
+    }
+
+    #[test]
+    fn links_to_providers_without_claims_are_planned() {
+        // The provider's claims were never stored, so once the host is gone nothing serves the link
+        let dead = inventory("NDEAD", &["Mecho"], &["Vhttp"]);
+        let plan = plan(&dead, &[], &claims(&["Mecho"]), &[link("Mecho", "Vhttp")]);
+        assert_eq!(plan.claims, ["Mecho"]);
+        assert_eq!(plan.links, [link("Mecho", "Vhttp")]);
+    }
+
+    #[test]
+    fn links_to_unrelated_providers_that_never_started_are_kept() {
+        let dead = inventory("NDEAD", &["Mecho"], &["Vhttp"]);
+        let links = [link("Mecho", "Vhttp"), link("Mother", "Vnotstarted")];
+        let plan = plan(&dead, &[], &claims(&["Mecho", "Vhttp"]), &links);
+        assert_eq!(plan.links, [link("Mecho", "Vhttp")]);
+    }
+
+    #[test]
+    fn links_to_providers_that_are_still_served_are_kept() {
+        let dead = inventory("NDEAD", &["Mecho"], &["Vredis"]);
+        let others = [inventory("NLIVE", &[], &["Vredis"])];
+        let links = [link("Mecho", "Vhttp"), link("Mecho", "Vredis")];
+        // Vredis ran on the dead host but still runs elsewhere, and Vhttp never ran there
+        let plan = plan(&dead, &others, &claims(&["Mecho", "Vhttp"]), &links);
+        assert_eq!(plan.claims, ["Mecho"]);
+        assert!(plan.links.is_empty());
+    }
+}
@@ -0,0 +1,289 @@
+//! Choosing between the hosts that bid in an auction
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::{Host, HostInventory};
+
+/// What was auctioned, for an [`AuctionRanker`] to judge the bidders against
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AuctionSubject<'a> {
+    /// An actor, by the reference it was auctioned with
+    Actor { actor_ref: &'a str },
+    /// A provider, by the reference and link name it was auctioned with
+    Provider {
+        provider_ref: &'a str,
+        link_name: &'a str,
+    },
+}
+
+impl AuctionSubject<'_> {
+    /// Counts the instances of the subject a host runs, by public key or image reference. Actor
+    /// instances are counted by their `max_concurrent`, providers once for each link name
+    pub fn instances_on(&self, inventory: &HostInventory) -> u32 {
+        match self {
+            AuctionSubject::Actor { actor_ref } => inventory
+                .actors
+                .iter()
+                .filter(|actor| {
+                    actor.id == *actor_ref || actor.image_ref.as_deref() == Some(*actor_ref)
+                })
+                .flat_map(|actor| &actor.instances)
+                .map(|instance| u32::from(instance.max_concurrent))
+                .sum(),
+            AuctionSubject::Provider { provider_ref, .. } => inventory
+                .providers
+                .iter()
+                .filter(|provider| {
+                    provider.id == *provider_ref
+                        || provider.image_ref.as_deref() == Some(*provider_ref)
+                })
+                .count() as u32,
+        }
+    }
+}
+
+/// What is known about a host that bid in an auction
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bidder {
+    /// The ID of the host
+    pub host_id: String,
+    /// The host as it answered [`crate::Client::get_hosts`], or `None` if it didn't answer
+    pub host: Option<Host>,
+    /// The host's inventory, or `None` if it couldn't be retrieved
+    pub inventory: Option<HostInventory>,
+}
+
+impl Bidder {
+    /// The host's labels, from its inventory or else from its answer to the hosts query
+    pub fn labels(&self) -> Option<&HashMap<String, String>> {
+        self.inventory
+            .as_ref()
+            .map(|inventory| &inventory.labels)
+            .or_else(|| self.host.as_ref().and_then(|host| host.labels.as_ref()))
+    }
+}
+
+/// Decides which of the hosts that bid in an auction should be chosen, used by
+/// [`crate::Client::auction_and_pick_actor_host_with_ranker`] and the other ranking methods.
+/// Information about a host that couldn't be retrieved is `None` rather than an error, so a
+/// ranker should expect gaps
+pub trait AuctionRanker: Send + Sync {
+    /// Orders the bidders best first. A bidder left out of the result is never chosen
+    fn rank(&self, subject: &AuctionSubject<'_>, bidders: Vec<Bidder>) -> Vec<Bidder>;
+}
+
+/// The [`AuctionRanker`] used unless another is given. It prefers, in order:
+///
+/// 1. hosts whose inventory could be retrieved, as nothing is known about the load on the others
+/// 2. hosts running the fewest instances of what was auctioned, to spread it out
+/// 3. hosts with the greatest total weight of preferred labels, if any were given
+/// 4. hosts that have been up the longest, as the most likely to stay up
+///
+/// and then the lowest host ID, so that the same information always gives the same choice
+#[derive(Clone, Debug, Default)]
+pub struct LoadRanker {
+    preferred_labels: Vec<(String, String, u32)>,
+}
+
+impl LoadRanker {
+    /// Creates a ranker with no preferred labels
+    pub fn new() -> LoadRanker {
+        LoadRanker::default()
+    }
+
+    /// Prefers hosts with the given label, adding `weight` to their total when the label has the
+    /// given value. Hosts that are equally loaded are ordered by their total
+    pub fn prefer_label(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+        weight: u32,
+    ) -> LoadRanker {
+        self.preferred_labels
+            .push((key.into(), value.into(), weight));
+        self
+    }
+
+    fn label_weight(&self, bidder: &Bidder) -> u64 {
+        let Some(labels) = bidder.labels() else {
+            return 0;
+        };
+        self.preferred_labels
+            .iter()
+            .filter(|(key, value, _)| labels.get(key) == Some(value))
+            .map(|(_, _, weight)| u64::from(*weight))
+            .sum()
+    }
+}
+
+impl AuctionRanker for LoadRanker {
+    fn rank(&self, subject: &AuctionSubject<'_>, mut bidders: Vec<Bidder>) -> Vec<Bidder> {
+        bidders.sort_by_cached_key(|bidder| {
+            let instances = bidder
+                .inventory
+                .as_ref()
+                .map(|inventory| subject.instances_on(inventory));
+            let uptime = bidder.host.as_ref().map_or(0, |host| host.uptime_seconds);
+            (
+                instances.is_none(),
+                instances,
+                Reverse(self.label_weight(bidder)),
+                Reverse(uptime),
+                bidder.host_id.clone(),
+            )
+        });
+        bidders
+    }
+}
+
+/// Orders `bids` as the ranker ordered their hosts, leaving out bids from hosts it left out
+pub(crate) fn order_bids<B>(
+    bids: Vec<B>,
+    host_id: impl Fn(&B) -> &str,
+    ranked: &[Bidder],
+) -> Vec<B> {
+    let position: HashMap<&str, usize> = ranked
+        .iter()
+        .enumerate()
+        .map(|(position, bidder)| (bidder.host_id.as_str(), position))
+        .collect();
+    let mut bids: Vec<(usize, B)> = bids
+        .into_iter()
+        .filter_map(|bid| Some((*position.get(host_id(&bid))?, bid)))
+        .collect();
+    bids.sort_by_key(|(position, _)| *position);
+    bids.into_iter().map(|(_, bid)| bid).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ActorAuctionAck, ActorDescription, ActorInstance, ProviderDescription};
+
+    const ECHO: AuctionSubject = AuctionSubject::Actor { actor_ref: "echo" };
+
+    fn bidder(host_id: &str, echo_instances: Option<u16>, uptime_seconds: u64) -> Bidder {
+        Bidder {
+            host_id: host_id.to_string(),
+            host: Some(Host {
+                id: host_id.to_string(),
+                uptime_seconds,
+                ..Default::default()
+            }),
+            inventory: echo_instances.map(|max_concurrent| HostInventory {
+                host_id: host_id.to_string(),
+                actors: vec![ActorDescription {
+                    id: "MECHO".to_string(),
+                    image_ref: Some("echo".to_string()),
+                    instances: vec![ActorInstance {
+                        max_concurrent,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn ids(bidders: &[Bidder]) -> Vec<&str> {
+        bidders
+            .iter()
+            .map(|bidder| bidder.host_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn least_loaded_hosts_rank_first() {
+        let bidders = vec![
+            bidder("N1", Some(3), 100),
+            bidder("N2", Some(1), 10),
+            bidder("N3", Some(1), 50),
+            bidder("N4", None, 1000),
+            bidder("N5", Some(0), 0),
+        ];
+        let ranked = LoadRanker::new().rank(&ECHO, bidders);
+        assert_eq!(ids(&ranked), ["N5", "N3", "N2", "N1", "N4"]);
+    }
+
+    #[test]
+    fn preferred_labels_break_ties() {
+        let mut zoned = bidder("N2", Some(1), 10);
+        zoned.inventory.as_mut().unwrap().labels =
+            HashMap::from([("zone".to_string(), "east".to_string())]);
+        let bidders = vec![bidder("N1", Some(1), 10), zoned, bidder("N3", Some(0), 0)];
+        let ranked = LoadRanker::new()
+            .prefer_label("zone", "east", 5)
+            .rank(&ECHO, bidders);
+        assert_eq!(ids(&ranked), ["N3", "N2", "N1"]);
+    }
+
+    #[test]
+    fn providers_are_counted_by_reference() {
+        let inventory = HostInventory {
+            providers: vec![
+                ProviderDescription {
+                    id: "VHTTP".to_string(),
+                    link_name: "default".to_string(),
+                    ..Default::default()
+                },
+                ProviderDescription {
+                    id: "VHTTP".to_string(),
+                    link_name: "backup".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let subject = AuctionSubject::Provider {
+            provider_ref: "VHTTP",
+            link_name: "default",
+        };
+        assert_eq!(subject.instances_on(&inventory), 2);
+        assert_eq!(ECHO.instances_on(&inventory), 0);
+    }
+
+    /// Prefers hosts by a label value and refuses hosts without it
+    struct ByRack;
+
+    impl AuctionRanker for ByRack {
+        fn rank(&self, _subject: &AuctionSubject<'_>, bidders: Vec<Bidder>) -> Vec<Bidder> {
+            let mut racked: Vec<(String, Bidder)> = bidders
+                .into_iter()
+                .filter_map(|bidder| Some((bidder.labels()?.get("rack")?.clone(), bidder)))
+                .collect();
+            racked.sort_by(|(a, _), (b, _)| a.cmp(b));
+            racked.into_iter().map(|(_, bidder)| bidder).collect()
+        }
+    }
+
+    #[test]
+    fn custom_rankers_order_and_filter_bids() {
+        let racked = |host_id: &str, rack: Option<&str>| Bidder {
+            host_id: host_id.to_string(),
+            host: Some(Host {
+                id: host_id.to_string(),
+                labels: rack.map(|rack| HashMap::from([("rack".to_string(), rack.to_string())])),
+                ..Default::default()
+            }),
+            inventory: None,
+        };
+        let bidders = vec![
+            racked("N1", Some("r2")),
+            racked("N2", None),
+            racked("N3", Some("r1")),
+        ];
+        let ack = |host_id: &str| ActorAuctionAck {
+            actor_ref: "echo".to_string(),
+            host_id: host_id.to_string(),
+            ..Default::default()
+        };
+        let bids = vec![ack("N1"), ack("N2"), ack("N3"), ack("N9")];
+
+        let ranked = ByRack.rank(&ECHO, bidders);
+        let ordered = order_bids(bids, |bid| bid.host_id.as_str(), &ranked);
+        assert_eq!(ordered, [ack("N3"), ack("N1")]);
+        assert!(order_bids(Vec::<ActorAuctionAck>::new(), |bid| &bid.host_id, &ranked).is_empty());
+    }
+}
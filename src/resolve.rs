@@ -0,0 +1,118 @@
+//! Resolving for [`crate::Client::resolve_actor_id`]: which actor public keys a reference could
+//! mean, going by the lattice's claims and the inventories of its hosts
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{kv, HostInventory};
+
+/// The claim holding the alias an actor can be called by instead of its public key
+pub(crate) const CLAIM_CALL_ALIAS: &str = "call_alias";
+
+/// Collects the public keys of every actor that `actor_ref` could refer to: actors whose claims
+/// have it as their subject or call alias, and actors in the inventories that have it as their ID
+/// or as the image reference of the actor or of any of its instances
+pub(crate) fn candidates(
+    actor_ref: &str,
+    claims: &[HashMap<String, String>],
+    inventories: &[HostInventory],
+) -> BTreeSet<String> {
+    let claimed = claims.iter().filter_map(|claims| {
+        let subject = claims.get(kv::CLAIM_SUBJECT)?;
+        let matches = subject == actor_ref
+            || claims.get(CLAIM_CALL_ALIAS).map(String::as_str) == Some(actor_ref);
+        // Providers have claims too, but their subjects never start with `M`
+        (matches && subject.starts_with('M')).then(|| subject.clone())
+    });
+    let running = inventories
+        .iter()
+        .flat_map(|inventory| &inventory.actors)
+        .filter(|actor| {
+            actor.id == actor_ref
+                || actor.image_ref.as_deref() == Some(actor_ref)
+                || actor
+                    .instances
+                    .iter()
+                    .any(|instance| instance.image_ref.as_deref() == Some(actor_ref))
+        })
+        .map(|actor| actor.id.clone());
+    claimed.chain(running).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ActorDescription, ActorInstance};
+
+    fn claims(subject: &str, call_alias: Option<&str>) -> HashMap<String, String> {
+        let mut claims = HashMap::from([("sub".to_string(), subject.to_string())]);
+        if let Some(call_alias) = call_alias {
+            claims.insert("call_alias".to_string(), call_alias.to_string());
+        }
+        claims
+    }
+
+    fn running(actors: Vec<ActorDescription>) -> HostInventory {
+        HostInventory {
+            host_id: "N1".to_string(),
+            actors,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn references_resolve_through_claims_and_inventories() {
+        let claims = [
+            claims("MECHO", Some("echo")),
+            claims("MKV", None),
+            claims("VECHO", Some("echo")),
+        ];
+        let inventories = [running(vec![
+            ActorDescription {
+                id: "MECHO".to_string(),
+                image_ref: Some("registry/echo:0.1".to_string()),
+                ..Default::default()
+            },
+            ActorDescription {
+                id: "MKV".to_string(),
+                instances: vec![ActorInstance {
+                    image_ref: Some("registry/kv:0.2".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        ])];
+        let resolve = |actor_ref| {
+            candidates(actor_ref, &claims, &inventories)
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(resolve("echo"), ["MECHO"]);
+        assert_eq!(resolve("registry/echo:0.1"), ["MECHO"]);
+        assert_eq!(resolve("registry/kv:0.2"), ["MKV"]);
+        assert_eq!(resolve("MKV"), ["MKV"]);
+        assert!(resolve("registry/echo:0.2").is_empty());
+    }
+
+    #[test]
+    fn every_actor_a_reference_could_mean_is_a_candidate() {
+        let claims = [claims("MOLD", Some("registry/echo:0.1"))];
+        let inventories = [
+            running(vec![ActorDescription {
+                id: "MECHO".to_string(),
+                image_ref: Some("registry/echo:0.1".to_string()),
+                ..Default::default()
+            }]),
+            running(vec![ActorDescription {
+                id: "MECHO".to_string(),
+                image_ref: Some("registry/echo:0.1".to_string()),
+                ..Default::default()
+            }]),
+        ];
+        assert_eq!(
+            candidates("registry/echo:0.1", &claims, &inventories)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            ["MECHO", "MOLD"]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Retrying control requests that time out, e.g. during a NATS leader election or reconnect
+
+use std::future::Future;
+use std::time::Duration;
+
+use ring::rand::SecureRandom as _;
+
+use tracing::debug;
+
+use crate::{Error, Result};
+
+/// How a client retries requests that time out, set with [`crate::ClientBuilder::retry_policy`].
+/// Only timeouts are retried: a host that replies, even to refuse the request with a negative
+/// [`crate::CtlOperationAck`], has made its decision and is not asked again.
+///
+/// Queries and auctions are always safe to repeat, so they are retried whenever the policy allows.
+/// A hosts query or auction counts as timed out if nothing at all answers it within its window.
+/// Since an auction that nothing answers may just mean that no host qualifies, auction retries
+/// can be turned off with [`RetryPolicy::retry_auctions`].
+/// Commands are only retried with [`RetryPolicy::retry_commands`], since a command that timed out
+/// may still have reached the host and would then be carried out twice. A retried command keeps
+/// its [`crate::CommandId`], so a host that tracks command IDs can recognize it as a repeat
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    retries: u32,
+    backoff: Duration,
+    multiplier: u32,
+    max_backoff: Option<Duration>,
+    jitter: bool,
+    commands: bool,
+    auctions: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            retries: 0,
+            backoff: Duration::from_millis(100),
+            multiplier: 1,
+            max_backoff: None,
+            jitter: false,
+            commands: false,
+            auctions: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a policy that never retries
+    pub fn new() -> RetryPolicy {
+        RetryPolicy::default()
+    }
+
+    /// Sets how many times a request is retried after the first attempt times out. Defaults to 0
+    pub fn retries(self, retries: u32) -> RetryPolicy {
+        RetryPolicy { retries, ..self }
+    }
+
+    /// Sets the delay before the first retry. Defaults to 100ms
+    pub fn backoff(self, backoff: Duration) -> RetryPolicy {
+        RetryPolicy { backoff, ..self }
+    }
+
+    /// Multiplies the delay by `multiplier` after every retry, for exponential backoff. Defaults
+    /// to 1, i.e. the same delay before every retry
+    pub fn exponential(self, multiplier: u32) -> RetryPolicy {
+        RetryPolicy { multiplier, ..self }
+    }
+
+    /// Caps the delay before any one retry. Unbounded by default
+    pub fn max_backoff(self, max_backoff: Duration) -> RetryPolicy {
+        RetryPolicy {
+            max_backoff: Some(max_backoff),
+            ..self
+        }
+    }
+
+    /// Waits a random fraction of each delay instead of all of it, so that many clients retrying
+    /// at once don't all hit the server together. Defaults to `false`
+    pub fn jitter(self, jitter: bool) -> RetryPolicy {
+        RetryPolicy { jitter, ..self }
+    }
+
+    /// Also retries commands that time out. Defaults to `false`
+    pub fn retry_commands(self, commands: bool) -> RetryPolicy {
+        RetryPolicy { commands, ..self }
+    }
+
+    /// Whether an auction that nothing at all answers within its window is published again.
+    /// Defaults to `true`
+    pub fn retry_auctions(self, auctions: bool) -> RetryPolicy {
+        RetryPolicy { auctions, ..self }
+    }
+
+    /// Returns the delay before the given retry, counting from 0
+    pub fn delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .backoff
+            .saturating_mul(self.multiplier.saturating_pow(retry));
+        let delay = self.max_backoff.map_or(delay, |max| delay.min(max));
+        if self.jitter {
+            let mut bytes = [0u8; 8];
+            ring::rand::SystemRandom::new()
+                .fill(&mut bytes)
+                .expect("the system random number generator should be available");
+            delay.mul_f64(u64::from_le_bytes(bytes) as f64 / u64::MAX as f64)
+        } else {
+            delay
+        }
+    }
+
+    /// How many times a hosts query that nothing answered is published again
+    pub(crate) fn query_retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// How many times an auction that nothing answered is published again
+    pub(crate) fn auction_retries(&self) -> u32 {
+        if self.auctions {
+            self.retries
+        } else {
+            0
+        }
+    }
+
+    /// Makes attempts until one doesn't time out or the retries run out, returning the last
+    /// result. `idempotent` is false for commands, which are only retried if the policy says so
+    pub(crate) async fn run<T, F, Fut>(
+        &self,
+        subject: &str,
+        idempotent: bool,
+        mut attempt: F,
+    ) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let retries = if idempotent || self.commands {
+            self.retries
+        } else {
+            0
+        };
+        let mut retry = 0;
+        loop {
+            debug!(%subject, attempt = retry + 1, "control request");
+            match attempt().await {
+                Err(Error::Timeout { .. }) if retry < retries => {
+                    let delay = self.delay(retry);
+                    debug!(%subject, ?delay, "control request timed out, retrying");
+                    tokio::time::sleep(delay).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+    use crate::AckError;
+
+    fn timeout() -> Error {
+        Error::Timeout {
+            subject: "test".to_string(),
+            duration: Duration::from_secs(1),
+        }
+    }
+
+    /// Times out the first `failures` attempts, then succeeds, counting every attempt
+    async fn flaky(attempts: &AtomicU32, failures: u32) -> Result<u32> {
+        let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+        if attempt < failures {
+            Err(timeout())
+        } else {
+            Ok(attempt)
+        }
+    }
+
+    #[test]
+    fn delay_grows_exponentially_up_to_the_cap() {
+        let policy = RetryPolicy::new()
+            .backoff(Duration::from_millis(100))
+            .exponential(2)
+            .max_backoff(Duration::from_millis(500));
+        let delays: Vec<_> = (0..5).map(|retry| policy.delay(retry)).collect();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 500, 500]
+                .map(Duration::from_millis)
+                .to_vec()
+        );
+        assert_eq!(
+            RetryPolicy::new().delay(3),
+            RetryPolicy::new().backoff,
+            "backoff is constant by default"
+        );
+    }
+
+    #[test]
+    fn jitter_stays_within_the_delay() {
+        let policy = RetryPolicy::new()
+            .backoff(Duration::from_millis(100))
+            .jitter(true);
+        assert!((0..100).all(|_| policy.delay(0) <= Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn auction_retries_can_be_turned_off() {
+        let policy = RetryPolicy::new().retries(3);
+        assert_eq!(policy.auction_retries(), 3);
+        let policy = policy.retry_auctions(false);
+        assert_eq!(policy.auction_retries(), 0);
+        assert_eq!(policy.query_retries(), 3, "queries are still retried");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_timeouts_until_one_succeeds() {
+        let policy = RetryPolicy::new()
+            .retries(3)
+            .backoff(Duration::from_millis(250));
+        let attempts = AtomicU32::new(0);
+        let start = tokio::time::Instant::now();
+        let result = policy.run("test", true, || flaky(&attempts, 2)).await;
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+
+        let attempts = AtomicU32::new(0);
+        let result = policy.run("test", true, || flaky(&attempts, 10)).await;
+        assert!(matches!(result, Err(Error::Timeout { .. })));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn other_errors_and_commands_are_not_retried() {
+        let policy = RetryPolicy::new().retries(3);
+        let attempts = AtomicU32::new(0);
+        let result: Result<()> = policy
+            .run("test", true, || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(AckError {
+                    error: "no".to_string(),
+                    ..Default::default()
+                }
+                .into())
+            })
+            .await;
+        assert!(matches!(result, Err(Error::NotAccepted { .. })));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        let attempts = AtomicU32::new(0);
+        assert!(policy
+            .run("test", false, || flaky(&attempts, 1))
+            .await
+            .is_err());
+        let attempts = AtomicU32::new(0);
+        let policy = policy.retry_commands(true);
+        assert!(policy
+            .run("test", false, || flaky(&attempts, 1))
+            .await
+            .is_ok());
+    }
+}
@@ -0,0 +1,160 @@
+//! Planning for [`crate::Client::scale_actor_to`]: how many instances of an actor each host should
+//! run so that the lattice as a whole runs the desired number
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{ActorDescription, AnnotationMap};
+
+/// Counts the instances of the actor with the given public key in a host's inventory, only
+/// counting instances that carry every one of `annotations`. Instances are counted by their
+/// `max_concurrent`, which is what [`crate::Client::scale_actor`] sets
+pub(crate) fn instance_count(
+    actors: &[ActorDescription],
+    actor_id: &str,
+    annotations: Option<&AnnotationMap>,
+) -> u16 {
+    actors
+        .iter()
+        .filter(|actor| actor.id == actor_id)
+        .flat_map(|actor| &actor.instances)
+        .filter(|instance| match annotations {
+            Some(wanted) => crate::annotations::contains_all(instance.annotations.as_ref(), wanted),
+            None => true,
+        })
+        .fold(0u16, |count, instance| {
+            count.saturating_add(instance.max_concurrent)
+        })
+}
+
+/// A change to the number of instances on one host
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Change {
+    pub host_id: String,
+    pub from: u16,
+    pub to: u16,
+}
+
+/// Works out how to get from the `current` counts to `desired` instances in total. Instances are
+/// added one at a time to whichever of `candidates` has the fewest, and removed from whichever host
+/// has the most, with ties going to the lowest host ID so that the same inputs always produce the
+/// same plan. Hosts in `current` are always candidates for more instances. Returns the hosts whose
+/// count changes, in host ID order
+pub(crate) fn plan(
+    current: &BTreeMap<String, u16>,
+    candidates: &BTreeSet<String>,
+    desired: u32,
+) -> Vec<Change> {
+    let mut planned = current.clone();
+    for host_id in candidates {
+        planned.entry(host_id.clone()).or_insert(0);
+    }
+    let mut total: u32 = planned.values().map(|count| u32::from(*count)).sum();
+    while total < desired {
+        let Some(count) = planned
+            .values_mut()
+            .filter(|count| **count < u16::MAX)
+            .min_by_key(|count| **count)
+        else {
+            break;
+        };
+        *count += 1;
+        total += 1;
+    }
+    while total > desired {
+        // `max_by_key` keeps the last of several equal counts, so search in reverse to prefer the
+        // lowest host ID
+        let Some(count) = planned.values_mut().rev().max_by_key(|count| **count) else {
+            break;
+        };
+        *count -= 1;
+        total -= 1;
+    }
+    planned
+        .into_iter()
+        .filter_map(|(host_id, to)| {
+            let from = current.get(&host_id).copied().unwrap_or_default();
+            (from != to).then_some(Change { host_id, from, to })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ActorInstance;
+
+    fn counts(counts: &[(&str, u16)]) -> BTreeMap<String, u16> {
+        counts
+            .iter()
+            .map(|(host_id, count)| (host_id.to_string(), *count))
+            .collect()
+    }
+
+    fn hosts(host_ids: &[&str]) -> BTreeSet<String> {
+        host_ids.iter().map(|host_id| host_id.to_string()).collect()
+    }
+
+    fn change(host_id: &str, from: u16, to: u16) -> Change {
+        Change {
+            host_id: host_id.to_string(),
+            from,
+            to,
+        }
+    }
+
+    #[test]
+    fn scaling_up_fills_the_emptiest_hosts_first() {
+        let plan = plan(&counts(&[("N2", 2)]), &hosts(&["N1", "N3"]), 7);
+        assert_eq!(plan, [change("N1", 0, 3), change("N3", 0, 2)]);
+    }
+
+    #[test]
+    fn scaling_down_drains_the_fullest_hosts_first() {
+        let current = counts(&[("N1", 3), ("N2", 1), ("N3", 3)]);
+        let plan = plan(&current, &hosts(&["N4"]), 4);
+        assert_eq!(plan, [change("N1", 3, 1), change("N3", 3, 2)]);
+        assert_eq!(
+            super::plan(&current, &BTreeSet::new(), 0),
+            [change("N1", 3, 0), change("N2", 1, 0), change("N3", 3, 0)]
+        );
+    }
+
+    #[test]
+    fn nothing_changes_at_the_desired_count() {
+        assert!(plan(&counts(&[("N1", 2), ("N2", 2)]), &hosts(&["N3"]), 4).is_empty());
+        assert!(plan(&BTreeMap::new(), &BTreeSet::new(), 3).is_empty());
+    }
+
+    #[test]
+    fn only_instances_with_the_annotations_are_counted() {
+        let instance = |max_concurrent, annotations: &[(&str, &str)]| ActorInstance {
+            max_concurrent,
+            annotations: Some(
+                annotations
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+        let actors = [
+            ActorDescription {
+                id: "MECHO".to_string(),
+                instances: vec![
+                    instance(2, &[("team", "a"), ("env", "prod")]),
+                    instance(3, &[("team", "b")]),
+                ],
+                ..Default::default()
+            },
+            ActorDescription {
+                id: "MOTHER".to_string(),
+                instances: vec![instance(5, &[("team", "a")])],
+                ..Default::default()
+            },
+        ];
+        assert_eq!(instance_count(&actors, "MECHO", None), 5);
+        let team_a = AnnotationMap::from([("team".to_string(), "a".to_string())]);
+        assert_eq!(instance_count(&actors, "MECHO", Some(&team_a)), 2);
+        assert_eq!(instance_count(&actors, "MNONE", None), 0);
+    }
+}
@@ -1,24 +1,48 @@
-use crate::json_deserialize;
-use futures::StreamExt;
+use crate::codec::{self, Reply};
+use futures::{Stream, StreamExt};
 use serde::de::DeserializeOwned;
 use std::time::Duration;
+use tokio::sync::mpsc;
 use tracing::error;
 
-/// Collect results until timeout has elapsed
-pub async fn collect_timeout<T: DeserializeOwned>(
-    mut sub: async_nats::Subscriber,
-    timeout: Duration,
+/// How many results can be waiting to be read before gathering pauses
+pub const RESULT_BUFFER: usize = 64;
+
+/// Sends results to `sender` as they arrive, each decoded according to its content type, until the
+/// window has elapsed, `min_results` results have been sent, no result has arrived for `idle_gap`,
+/// or the receiver is dropped, whichever happens first. Returns how many results were sent
+pub async fn forward_timeout<T, S>(
+    mut payloads: S,
+    window: Duration,
+    min_results: Option<usize>,
+    idle_gap: Option<Duration>,
     reason: &str,
-) -> Vec<T> {
-    let mut items = Vec::new();
-    let sleep = tokio::time::sleep(timeout);
+    sender: mpsc::Sender<T>,
+) -> usize
+where
+    T: DeserializeOwned,
+    S: Stream + Unpin,
+    S::Item: Reply,
+{
+    let mut sent = 0;
+    let sleep = tokio::time::sleep(window);
     tokio::pin!(sleep);
     loop {
+        if min_results.is_some_and(|min| sent >= min) {
+            break;
+        }
+        // Recreated on every pass so the gap is measured from the most recent result
+        let idle = async {
+            match idle_gap {
+                Some(gap) => tokio::time::sleep(gap).await,
+                None => std::future::pending().await,
+            }
+        };
         tokio::select! {
-            maybe_msg = sub.next() => {
-                if let Some(msg) = maybe_msg {
-                    if msg.payload.is_empty() { break; }
-                    let item = match json_deserialize::<T>(&msg.payload) {
+            maybe_reply = payloads.next() => {
+                if let Some(reply) = maybe_reply {
+                    if reply.payload().is_empty() { break; }
+                    let item = match codec::decode::<T>(&reply) {
                         Ok(item) => item,
                         Err(error) => {
                             error!(%reason, %error,
@@ -27,11 +51,114 @@ pub async fn collect_timeout<T: DeserializeOwned>(
                             break;
                         }
                     };
-                    items.push(item);
+                    if sender.send(item).await.is_err() { break; }
+                    sent += 1;
                 } else { break; }
             },
             _ = &mut sleep => { /* timeout */ break; }
+            _ = idle => { /* idle gap */ break; }
+            _ = sender.closed() => { /* receiver dropped */ break; }
+        }
+    }
+    sent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc as channel;
+    use tokio::time::Instant;
+
+    /// Sends a result after each of the given delays, measured from the previous send
+    fn responses(delays: &[u64]) -> channel::UnboundedReceiver<Vec<u8>> {
+        let (tx, rx) = channel::unbounded();
+        let delays = delays.to_vec();
+        tokio::spawn(async move {
+            for (i, delay) in delays.into_iter().enumerate() {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                if tx.unbounded_send(i.to_string().into_bytes()).is_err() {
+                    return;
+                }
+            }
+        });
+        rx
+    }
+
+    /// Forwards results into a channel and gathers everything that was forwarded
+    async fn collect<S>(
+        payloads: S,
+        window: Duration,
+        min_results: Option<usize>,
+        idle_gap: Option<Duration>,
+    ) -> Vec<u32>
+    where
+        S: Stream + Unpin,
+        S::Item: Reply,
+    {
+        let (sender, mut receiver) = mpsc::channel(RESULT_BUFFER);
+        let sent = forward_timeout(payloads, window, min_results, idle_gap, "test", sender).await;
+        let mut items = Vec::new();
+        while let Some(item) = receiver.recv().await {
+            items.push(item);
         }
+        assert_eq!(sent, items.len());
+        items
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn window_bounds_gathering() {
+        let rx = responses(&[10, 100, 200]);
+        let start = Instant::now();
+        let items = collect(rx, Duration::from_millis(250), None, None).await;
+        assert_eq!(items, vec![0, 1]);
+        assert_eq!(start.elapsed(), Duration::from_millis(250));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn min_results_stops_early() {
+        let rx = responses(&[10, 10, 10, 10]);
+        let start = Instant::now();
+        let items = collect(rx, Duration::from_secs(5), Some(2), None).await;
+        assert_eq!(items, vec![0, 1]);
+        assert_eq!(start.elapsed(), Duration::from_millis(20));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_gap_stops_early() {
+        let rx = responses(&[10, 50, 500]);
+        let start = Instant::now();
+        let items = collect(
+            rx,
+            Duration::from_secs(5),
+            None,
+            Some(Duration::from_millis(100)),
+        )
+        .await;
+        assert_eq!(items, vec![0, 1]);
+        assert_eq!(start.elapsed(), Duration::from_millis(160));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn empty_payload_ends_gathering() {
+        let (tx, rx) = channel::unbounded::<Vec<u8>>();
+        tx.unbounded_send(b"1".to_vec()).unwrap();
+        tx.unbounded_send(Vec::new()).unwrap();
+        tx.unbounded_send(b"2".to_vec()).unwrap();
+        let items = collect(rx, Duration::from_secs(5), None, None).await;
+        assert_eq!(items, vec![1]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dropped_receiver_stops_forwarding() {
+        let rx = responses(&[10, 10, 1000]);
+        let (sender, mut receiver) = mpsc::channel::<u32>(RESULT_BUFFER);
+        let forward = tokio::spawn(async move {
+            forward_timeout(rx, Duration::from_secs(5), None, None, "test", sender).await
+        });
+        assert_eq!(receiver.recv().await, Some(0));
+        let start = Instant::now();
+        drop(receiver);
+        forward.await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(100));
     }
-    items
 }
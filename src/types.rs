@@ -1,7 +1,11 @@
 #![allow(deprecated)]
 use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
 
-use serde::{Deserialize, Serialize};
+use cloudevents::{AttributesReader, Data, Event};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::annotations;
 
 /// One of a potential list of responses to an actor auction
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
@@ -30,6 +34,148 @@ pub struct ActorAuctionRequest {
 
 pub type ConstraintMap = std::collections::HashMap<String, String>;
 
+/// The constraints a host's labels must satisfy for it to bid in an auction, built with helpers
+/// for the labels every host sets so that their keys can't be mistyped. Every auction method
+/// accepts these or a plain [`ConstraintMap`]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Constraints(ConstraintMap);
+
+impl Constraints {
+    /// The label every host sets to its operating system, e.g. `linux`
+    pub const OS: &'static str = "hostcore.os";
+    /// The label every host sets to its CPU architecture, e.g. `aarch64`
+    pub const ARCH: &'static str = "hostcore.arch";
+    /// The label every host sets to its operating system family, e.g. `unix`
+    pub const OS_FAMILY: &'static str = "hostcore.osfamily";
+
+    /// Creates constraints that every host satisfies
+    pub fn new() -> Constraints {
+        Constraints::default()
+    }
+
+    /// Requires the host to run the given operating system
+    pub fn os(self, os: impl Into<String>) -> Constraints {
+        self.label(Constraints::OS, os)
+    }
+
+    /// Requires the host to run on the given CPU architecture
+    pub fn arch(self, arch: impl Into<String>) -> Constraints {
+        self.label(Constraints::ARCH, arch)
+    }
+
+    /// Requires the host to run an operating system in the given family
+    pub fn os_family(self, family: impl Into<String>) -> Constraints {
+        self.label(Constraints::OS_FAMILY, family)
+    }
+
+    /// Requires the host to have the given label with the given value
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Constraints {
+        self.0.insert(key.into(), value.into());
+        self
+    }
+
+    /// Checks that every key could match a host label, i.e. is non-empty and contains no NATS
+    /// wildcard characters. The auction methods check this before publishing anything
+    pub fn validate(&self) -> crate::Result<()> {
+        self.0
+            .keys()
+            .try_for_each(|key| crate::validate::constraint_key(key))
+    }
+
+    /// Returns whether a host with the given labels satisfies every constraint, e.g. to filter
+    /// the hosts returned by [`crate::Client::get_hosts`] without holding an auction
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        self.0
+            .iter()
+            .all(|(key, value)| labels.get(key) == Some(value))
+    }
+
+    /// Returns the constraints as a map
+    pub fn as_map(&self) -> &ConstraintMap {
+        &self.0
+    }
+
+    /// Turns the constraints into a map
+    pub fn into_map(self) -> ConstraintMap {
+        self.0
+    }
+}
+
+impl From<ConstraintMap> for Constraints {
+    fn from(constraints: ConstraintMap) -> Constraints {
+        Constraints(constraints)
+    }
+}
+
+impl From<Constraints> for ConstraintMap {
+    fn from(constraints: Constraints) -> ConstraintMap {
+        constraints.0
+    }
+}
+
+/// A set of annotations to look for on actor and provider instances, such as the ones wadm puts
+/// on everything it manages. An instance matches if it carries every one of these annotations with
+/// the same value; any other annotations it has are ignored
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Annotations(AnnotationMap);
+
+impl Annotations {
+    /// Creates an empty set, which every instance matches
+    pub fn new() -> Annotations {
+        Annotations::default()
+    }
+
+    /// Matches instances managed by the given tool, e.g. `"wadm"`
+    pub fn managed_by(tool: impl Into<String>) -> Annotations {
+        Annotations::new().annotation(annotations::MANAGED_BY, tool)
+    }
+
+    /// Matches instances that wadm manages as part of the named application
+    pub fn app(name: &str) -> Annotations {
+        Annotations(annotations::app(name))
+    }
+
+    /// Also requires the given annotation with the given value
+    pub fn annotation(mut self, key: impl Into<String>, value: impl Into<String>) -> Annotations {
+        self.0.insert(key.into(), value.into());
+        self
+    }
+
+    /// Returns whether an instance with the given annotations carries every one of these
+    pub fn matches(&self, annotations: Option<&AnnotationMap>) -> bool {
+        annotations::contains_all(annotations, &self.0)
+    }
+
+    /// Returns true if there are no annotations to look for
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the annotations as a map
+    pub fn as_map(&self) -> &AnnotationMap {
+        &self.0
+    }
+
+    /// Turns the annotations into a map
+    pub fn into_map(self) -> AnnotationMap {
+        self.0
+    }
+}
+
+impl From<AnnotationMap> for Annotations {
+    fn from(annotations: AnnotationMap) -> Annotations {
+        Annotations(annotations)
+    }
+}
+
+impl From<Annotations> for AnnotationMap {
+    fn from(annotations: Annotations) -> AnnotationMap {
+        annotations.0
+    }
+}
+
 /// A summary description of an actor within a host inventory
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct ActorDescription {
@@ -66,8 +212,452 @@ pub struct ActorInstance {
     pub max_concurrent: u16,
 }
 
+/// The data of an `actor_started` lattice event
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ActorStarted {
+    /// The actor's public key
+    #[serde(default)]
+    pub public_key: String,
+    /// Image reference the actor was started from, if applicable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_ref: Option<String>,
+    /// The started instance's unique ID
+    #[serde(default)]
+    pub instance_id: String,
+    /// The annotations used in the start request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<AnnotationMap>,
+}
+
+/// The data of an `actor_stopped` lattice event
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ActorStopped {
+    /// The actor's public key
+    #[serde(default)]
+    pub public_key: String,
+    /// The stopped instance's unique ID
+    #[serde(default)]
+    pub instance_id: String,
+    /// The annotations the instance was started with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<AnnotationMap>,
+}
+
+/// The data of an `actor_start_failed` lattice event
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ActorStartFailed {
+    /// The reference the actor was to be started from
+    #[serde(default)]
+    pub actor_ref: String,
+    /// Why the actor failed to start
+    #[serde(default)]
+    pub error: String,
+}
+
+/// The data of an `actor_updated` lattice event
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ActorUpdated {
+    /// The actor's public key
+    #[serde(default)]
+    pub public_key: String,
+    /// The revision the actor was updated to
+    #[serde(default)]
+    pub revision: i32,
+    /// The updated instance's unique ID
+    #[serde(default)]
+    pub instance_id: String,
+}
+
+/// The data of an `actor_update_failed` lattice event
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ActorUpdateFailed {
+    /// The actor's public key
+    #[serde(default)]
+    pub public_key: String,
+    /// Why the update failed
+    #[serde(default)]
+    pub error: String,
+}
+
 pub type AnnotationMap = std::collections::HashMap<String, String>;
 
+/// Everything running in the lattice that belongs to a single wadm application, as identified by
+/// the [`annotations::APP_SPEC`] annotation
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AppFootprint {
+    /// The application name
+    pub name: String,
+    /// The application's actors, keyed by the ID of the host they run on. Each description only
+    /// lists the instances that belong to the application
+    #[serde(default)]
+    pub actors: HashMap<String, Vec<ActorDescription>>,
+    /// The application's providers, keyed by the ID of the host they run on
+    #[serde(default)]
+    pub providers: HashMap<String, Vec<ProviderDescription>>,
+    /// Links that carry the application annotation in their values or belong to one of the
+    /// application's actors
+    #[serde(default)]
+    pub links: Vec<LinkDefinition>,
+}
+
+impl AppFootprint {
+    /// Gathers the footprint of the named application from a set of host inventories and the
+    /// lattice's link definitions
+    pub fn from_inventories(
+        name: &str,
+        inventories: &[HostInventory],
+        links: &[LinkDefinition],
+    ) -> AppFootprint {
+        let mut footprint = AppFootprint {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        for inventory in inventories {
+            let actors = inventory.actors_for_app(name);
+            if !actors.is_empty() {
+                footprint.actors.insert(inventory.host_id.clone(), actors);
+            }
+            let providers = inventory.providers_for_app(name);
+            if !providers.is_empty() {
+                footprint
+                    .providers
+                    .insert(inventory.host_id.clone(), providers);
+            }
+        }
+        footprint.links = links
+            .iter()
+            .filter(|link| {
+                annotations::is_app(Some(&link.values), name)
+                    || footprint
+                        .actors
+                        .values()
+                        .flatten()
+                        .any(|actor| actor.id == link.actor_id)
+            })
+            .cloned()
+            .collect();
+        footprint
+    }
+
+    /// Returns true if nothing belonging to the application was found
+    pub fn is_empty(&self) -> bool {
+        self.actors.is_empty() && self.providers.is_empty() && self.links.is_empty()
+    }
+}
+
+/// Controls how responses are gathered for host queries and auctions. Any setting left as `None`
+/// falls back to the client's configuration, so a single set of options can be reused across
+/// calls that only need to override one knob
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AuctionOptions {
+    /// How long to wait for responses in total. Defaults to the client's auction timeout
+    pub window: Option<Duration>,
+    /// Stop gathering as soon as this many responses have arrived
+    pub min_results: Option<usize>,
+    /// Stop gathering if no response arrives within this long of the previous one (or of the
+    /// request, for the first response)
+    pub idle_gap: Option<Duration>,
+}
+
+impl AuctionOptions {
+    /// Creates options that use the client's defaults for everything
+    pub fn new() -> AuctionOptions {
+        AuctionOptions::default()
+    }
+
+    /// Sets how long to wait for responses in total
+    pub fn window(self, window: Duration) -> AuctionOptions {
+        AuctionOptions {
+            window: Some(window),
+            ..self
+        }
+    }
+
+    /// Sets the number of responses after which gathering stops early
+    pub fn min_results(self, min_results: usize) -> AuctionOptions {
+        AuctionOptions {
+            min_results: Some(min_results),
+            ..self
+        }
+    }
+
+    /// Sets the longest gap allowed between responses before gathering stops early
+    pub fn idle_gap(self, idle_gap: Duration) -> AuctionOptions {
+        AuctionOptions {
+            idle_gap: Some(idle_gap),
+            ..self
+        }
+    }
+}
+
+/// A single command contained within a [`CommandBatch`]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(tag = "type", content = "command", rename_all = "snake_case")]
+pub enum BatchCommand {
+    ScaleActor(ScaleActorCommand),
+    StopActor(StopActorCommand),
+    UpdateActor(UpdateActorCommand),
+    StartProvider(StartProviderCommand),
+    StopProvider(StopProviderCommand),
+}
+
+/// The outcome of a [`CommandBatch`] sent with an overall deadline
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BatchReport {
+    /// Acknowledgements for the commands that were sent, in batch order
+    #[serde(default)]
+    pub acks: Vec<CtlOperationAck>,
+    /// Commands that were never sent because the deadline had passed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub not_attempted: Vec<BatchCommand>,
+    /// True if the deadline ran out before every command was sent
+    #[serde(default)]
+    pub deadline_exceeded: bool,
+}
+
+/// The outcome of [`crate::Client::scale_actor_to`]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ScaleReport {
+    /// The number of matching instances running on matching hosts before scaling
+    #[serde(default)]
+    pub previous_count: u32,
+    /// The number of instances that was asked for
+    #[serde(default)]
+    pub desired_count: u32,
+    /// The planned change on every host whose instance count had to change, in host ID order
+    #[serde(default)]
+    pub changes: Vec<HostScaleChange>,
+    /// Hosts whose inventory couldn't be retrieved. They were neither counted nor changed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unreachable_hosts: Vec<String>,
+}
+
+impl ScaleReport {
+    /// Returns the number of instances there will be once every acknowledged change is carried
+    /// out
+    pub fn acknowledged_count(&self) -> u32 {
+        self.changes
+            .iter()
+            .filter(|change| change.ack.accepted)
+            .fold(self.previous_count, |count, change| {
+                (count + u32::from(change.to)).saturating_sub(u32::from(change.from))
+            })
+    }
+
+    /// Returns true if every planned change was acknowledged
+    pub fn is_complete(&self) -> bool {
+        self.changes.iter().all(|change| change.ack.accepted)
+    }
+}
+
+/// One of the `stop_actor` commands sent by [`crate::Client::stop_actor_instances`], covering the
+/// matching instances that carry exactly `annotations`
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct InstanceStop {
+    /// The full annotations of the instances this command stops, which were sent with it
+    #[serde(default)]
+    pub annotations: AnnotationMap,
+    /// The number of instances the command stops, counted by their `max_concurrent`
+    #[serde(default)]
+    pub instance_count: u16,
+    /// The host's acknowledgement. If the command couldn't be delivered, e.g. because the host
+    /// didn't answer, this is a negative acknowledgement describing the error
+    #[serde(default)]
+    pub ack: CtlOperationAck,
+}
+
+/// A planned change to the number of instances of an actor on one host, and how the host answered
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct HostScaleChange {
+    /// The ID of the host
+    #[serde(default)]
+    pub host_id: String,
+    /// The number of matching instances the host was running
+    #[serde(default)]
+    pub from: u16,
+    /// The number of matching instances the host was asked to run
+    #[serde(default)]
+    pub to: u16,
+    /// The host's acknowledgement. If the command couldn't be delivered, e.g. because the host
+    /// didn't answer, this is a negative acknowledgement describing the error
+    #[serde(default)]
+    pub ack: CtlOperationAck,
+}
+
+/// The outcome of [`crate::Client::migrate_topic_links_to_kv`]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct LinkMigration {
+    /// Links that were written to the lattice metadata bucket
+    pub copied: usize,
+    /// Links the bucket already had, which were left as they were
+    pub already_present: usize,
+}
+
+/// What [`crate::Client::purge_host`] removes from the lattice metadata bucket, or would remove
+/// when returned by [`crate::Client::purge_host_plan`]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PurgePlan {
+    /// The ID of the host being purged
+    #[serde(default)]
+    pub host_id: String,
+    /// The subjects of the claims to remove, for actors and providers that ran on no other host
+    #[serde(default)]
+    pub claims: Vec<String>,
+    /// The link definitions to remove, whose providers ran on the host and run on no other host
+    #[serde(default)]
+    pub links: Vec<LinkDefinition>,
+}
+
+impl PurgePlan {
+    /// Returns the bucket keys the plan removes, claims first
+    pub fn keys(&self) -> Vec<String> {
+        self.claims
+            .iter()
+            .map(|subject| crate::kv::claims_key(subject))
+            .chain(self.links.iter().map(|link| {
+                crate::kv::link_key(&link.actor_id, &link.contract_id, &link.link_name)
+            }))
+            .collect()
+    }
+
+    /// Returns true if there is nothing to remove
+    pub fn is_empty(&self) -> bool {
+        self.claims.is_empty() && self.links.is_empty()
+    }
+}
+
+/// A change to the claims in the lattice metadata bucket
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClaimsChange {
+    /// Claims were added or replaced
+    Put(HashMap<String, String>),
+    /// The claims for `subject` were removed
+    Delete { subject: String },
+}
+
+/// An ordered list of commands sent to a single host in one request. Hosts that support batching
+/// process the commands in order and reply with one acknowledgement per processed command
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CommandBatch {
+    /// The commands to run, in the order they should be processed
+    #[serde(default)]
+    pub commands: Vec<BatchCommand>,
+    /// If true, processing stops at the first command that is not accepted and no acknowledgements
+    /// are returned for the remaining commands
+    #[serde(default)]
+    pub stop_on_failure: bool,
+}
+
+impl CommandBatch {
+    /// Creates a new, empty batch that processes every command regardless of failures
+    pub fn new() -> CommandBatch {
+        CommandBatch::default()
+    }
+
+    /// Appends a command to the end of the batch
+    pub fn push(mut self, command: BatchCommand) -> CommandBatch {
+        self.commands.push(command);
+        self
+    }
+
+    /// Sets whether processing should stop at the first command that is not accepted
+    pub fn stop_on_failure(self, stop_on_failure: bool) -> CommandBatch {
+        CommandBatch {
+            stop_on_failure,
+            ..self
+        }
+    }
+
+    /// Gives each command that can carry a [`CommandId`] but has none a new one. Done once per
+    /// send, so the batch request, the sequential fallback and any retries all use the same IDs
+    pub(crate) fn assign_command_ids(&mut self) {
+        for command in &mut self.commands {
+            let command_id = match command {
+                BatchCommand::ScaleActor(cmd) => &mut cmd.command_id,
+                BatchCommand::StopActor(cmd) => &mut cmd.command_id,
+                BatchCommand::StartProvider(cmd) => &mut cmd.command_id,
+                BatchCommand::UpdateActor(_) | BatchCommand::StopProvider(_) => continue,
+            };
+            command_id.get_or_insert_with(CommandId::new);
+        }
+    }
+}
+
+/// The response from a host to a [`CommandBatch`]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CommandBatchResponse {
+    /// One acknowledgement per processed command, in the same order as the batch
+    #[serde(default)]
+    pub acks: Vec<CtlOperationAck>,
+}
+
+/// The result of a command that waited for the host to report that it was carried out
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandCompletion<T> {
+    /// The host's acknowledgement of the command
+    pub ack: CtlOperationAck,
+    /// The events the host published as it carried out the command, in the order received
+    pub events: Vec<TypedEvent<T>>,
+}
+
+/// Progress of a host shutting down, as reported by [`crate::Client::stop_host_and_monitor`]. The
+/// last event is always either [`HostDrainEvent::Stopped`] or [`HostDrainEvent::Unconfirmed`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum HostDrainEvent {
+    /// The host stopped an actor while draining
+    ActorStopped(TypedEvent<ActorStopped>),
+    /// The host stopped a provider while draining
+    ProviderStopped(TypedEvent<ProviderStopped>),
+    /// The host confirmed that it has stopped
+    Stopped(TypedEvent<HostStopped>),
+    /// The deadline passed, or the event subscription ended, without the host confirming that it
+    /// stopped. It may still be shutting down, or it may have exited without saying so
+    Unconfirmed,
+}
+
+/// A change in whether a host is alive, as reported by [`crate::Client::host_status_receiver`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[allow(clippy::large_enum_variant)] // Updates are rare, so boxing the host isn't worth it
+pub enum HostStatusUpdate {
+    /// The host was heard from for the first time, or again after going stale. Described from its
+    /// heartbeat, or from its host started event if that came first
+    Up(Host),
+    /// The host published a host stopped event
+    Down(String),
+    /// Nothing has been heard from the host within the staleness window
+    Stale(String),
+}
+
+/// A response along with the subject its request was sent on and how long the round trip took,
+/// returned by the `_detailed` variants of client calls such as
+/// [`crate::Client::get_host_inventory_detailed`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CtlResponse<T> {
+    /// The response itself
+    pub data: T,
+    /// The subject the request was sent on, or the bucket's name for a response read from the
+    /// lattice metadata bucket
+    pub subject: String,
+    /// The time from just before the request was serialized until its reply was decoded,
+    /// including any retries and any time spent queued behind earlier commands to the same host
+    pub elapsed: Duration,
+}
+
+impl<T> CtlResponse<T> {
+    pub(crate) fn new(data: T, subject: String, start: std::time::Instant) -> CtlResponse<T> {
+        CtlResponse {
+            data,
+            subject,
+            elapsed: start.elapsed(),
+        }
+    }
+
+    /// Discards the metadata, returning just the response
+    pub fn into_data(self) -> T {
+        self.data
+    }
+}
+
 /// Standard response for control interface operations
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct CtlOperationAck {
@@ -77,6 +667,34 @@ pub struct CtlOperationAck {
     pub error: String,
 }
 
+impl CtlOperationAck {
+    /// Turns a negative acknowledgement into an error carrying the host's reason, so that it can't
+    /// be mistaken for success
+    pub fn into_result(self) -> Result<(), crate::AckError> {
+        if self.accepted {
+            Ok(())
+        } else {
+            Err(crate::AckError {
+                error: self.error,
+                ..Default::default()
+            })
+        }
+    }
+}
+
+/// Notification that the lattice metadata bucket was recreated, so any state derived from the
+/// previous bucket (cached links, claims, etc.) is stale
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct GenerationChanged {
+    /// The generation marker that was previously observed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<String>,
+    /// The generation marker of the current bucket, `None` if the bucket is gone or has not been
+    /// written to yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current: Option<String>,
+}
+
 /// A response containing the full list of known claims within the lattice
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct GetClaimsResponse {
@@ -143,14 +761,309 @@ pub struct HostInventory {
 }
 
 pub type KeyValueMap = std::collections::HashMap<String, String>;
+
+impl HostInventory {
+    /// Returns the actors on this host that belong to the named application. Each description is
+    /// narrowed to the instances carrying the application annotation, and actors with no such
+    /// instances are left out
+    pub fn actors_for_app(&self, app_name: &str) -> Vec<ActorDescription> {
+        self.actors_where(|annotations| annotations::is_app(annotations, app_name))
+    }
+
+    /// Returns the providers on this host that belong to the named application
+    pub fn providers_for_app(&self, app_name: &str) -> Vec<ProviderDescription> {
+        self.providers_where(|annotations| annotations::is_app(annotations, app_name))
+    }
+
+    /// Returns the actors on this host with instances carrying all of the given annotations. Each
+    /// description is narrowed to those instances, and actors with none are left out
+    pub fn actors_with_annotations(&self, annotations: &Annotations) -> Vec<ActorDescription> {
+        self.actors_where(|actual| annotations.matches(actual))
+    }
+
+    /// Returns the providers on this host carrying all of the given annotations
+    pub fn providers_with_annotations(
+        &self,
+        annotations: &Annotations,
+    ) -> Vec<ProviderDescription> {
+        self.providers_where(|actual| annotations.matches(actual))
+    }
+
+    fn actors_where(
+        &self,
+        matches: impl Fn(Option<&AnnotationMap>) -> bool,
+    ) -> Vec<ActorDescription> {
+        self.actors
+            .iter()
+            .filter_map(|actor| {
+                let instances: Vec<ActorInstance> = actor
+                    .instances
+                    .iter()
+                    .filter(|instance| matches(instance.annotations.as_ref()))
+                    .cloned()
+                    .collect();
+                (!instances.is_empty()).then(|| ActorDescription {
+                    instances,
+                    ..actor.clone()
+                })
+            })
+            .collect()
+    }
+
+    fn providers_where(
+        &self,
+        matches: impl Fn(Option<&AnnotationMap>) -> bool,
+    ) -> Vec<ProviderDescription> {
+        self.providers
+            .iter()
+            .filter(|provider| matches(provider.annotations.as_ref()))
+            .cloned()
+            .collect()
+    }
+}
+
+/// The data of a `host_heartbeat` lattice event
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct HostHeartbeat {
+    /// The number of instances of each actor running on the host, keyed by actor public key
+    #[serde(default)]
+    pub actors: HashMap<String, usize>,
+    /// The providers running on the host
+    #[serde(default)]
+    pub providers: Vec<HostHeartbeatProvider>,
+    /// The host's human-readable friendly name
+    #[serde(default)]
+    pub friendly_name: String,
+    /// The host's labels
+    #[serde(default)]
+    pub labels: LabelsMap,
+    /// How long the host has been running
+    #[serde(default)]
+    pub uptime_seconds: u64,
+    /// The host's software version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// A provider listed in a [`HostHeartbeat`]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct HostHeartbeatProvider {
+    /// The provider's public key
+    #[serde(default)]
+    pub public_key: String,
+    #[serde(default)]
+    pub link_name: String,
+    #[serde(default)]
+    pub contract_id: String,
+}
+
+/// The data of a `host_started` lattice event
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct HostStarted {
+    /// The host's human-readable friendly name
+    #[serde(default)]
+    pub friendly_name: String,
+    /// The host's labels
+    #[serde(default)]
+    pub labels: LabelsMap,
+}
+
+/// The data of a `host_stopped` lattice event
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct HostStopped {
+    /// The host's labels
+    #[serde(default)]
+    pub labels: LabelsMap,
+}
+
 pub type LabelsMap = std::collections::HashMap<String, String>;
 
+/// A lattice control event with its data parsed according to the event type. Events of unknown
+/// types, and events whose data doesn't match their type, are passed through as
+/// [`LatticeEvent::Other`] so that nothing is dropped
+#[derive(Clone, Debug, PartialEq)]
+pub enum LatticeEvent {
+    ActorStarted(TypedEvent<ActorStarted>),
+    ActorStopped(TypedEvent<ActorStopped>),
+    ActorStartFailed(TypedEvent<ActorStartFailed>),
+    ActorUpdated(TypedEvent<ActorUpdated>),
+    ActorUpdateFailed(TypedEvent<ActorUpdateFailed>),
+    ProviderStarted(TypedEvent<ProviderStarted>),
+    ProviderStartFailed(TypedEvent<ProviderStartFailed>),
+    ProviderStopped(TypedEvent<ProviderStopped>),
+    LinkDefSet(TypedEvent<LinkDefinition>),
+    LinkDefDeleted(TypedEvent<LinkDefinition>),
+    HostHeartbeat(TypedEvent<HostHeartbeat>),
+    HostStarted(TypedEvent<HostStarted>),
+    HostStopped(TypedEvent<HostStopped>),
+    Other(Event),
+}
+
+impl LatticeEvent {
+    pub const ACTOR_STARTED: &'static str = "com.wasmcloud.lattice.actor_started";
+    pub const ACTOR_STOPPED: &'static str = "com.wasmcloud.lattice.actor_stopped";
+    pub const ACTOR_START_FAILED: &'static str = "com.wasmcloud.lattice.actor_start_failed";
+    pub const ACTOR_UPDATED: &'static str = "com.wasmcloud.lattice.actor_updated";
+    pub const ACTOR_UPDATE_FAILED: &'static str = "com.wasmcloud.lattice.actor_update_failed";
+    pub const PROVIDER_STARTED: &'static str = "com.wasmcloud.lattice.provider_started";
+    pub const PROVIDER_START_FAILED: &'static str = "com.wasmcloud.lattice.provider_start_failed";
+    pub const PROVIDER_STOPPED: &'static str = "com.wasmcloud.lattice.provider_stopped";
+    pub const LINKDEF_SET: &'static str = "com.wasmcloud.lattice.linkdef_set";
+    pub const LINKDEF_DELETED: &'static str = "com.wasmcloud.lattice.linkdef_deleted";
+    pub const HOST_HEARTBEAT: &'static str = "com.wasmcloud.lattice.host_heartbeat";
+    pub const HOST_STARTED: &'static str = "com.wasmcloud.lattice.host_started";
+    pub const HOST_STOPPED: &'static str = "com.wasmcloud.lattice.host_stopped";
+}
+
+impl From<Event> for LatticeEvent {
+    fn from(event: Event) -> LatticeEvent {
+        fn typed<T: DeserializeOwned>(
+            event: &Event,
+            variant: fn(TypedEvent<T>) -> LatticeEvent,
+        ) -> Option<LatticeEvent> {
+            let data = match event.data()? {
+                Data::Json(value) => T::deserialize(value).ok()?,
+                Data::String(s) => serde_json::from_str(s).ok()?,
+                Data::Binary(bytes) => serde_json::from_slice(bytes).ok()?,
+            };
+            Some(variant(TypedEvent {
+                id: event.id().to_string(),
+                host_id: event.source().to_string(),
+                timestamp: event.time().map(|time| SystemTime::from(*time)),
+                data,
+            }))
+        }
+
+        let parsed = match event.ty() {
+            LatticeEvent::ACTOR_STARTED => typed(&event, LatticeEvent::ActorStarted),
+            LatticeEvent::ACTOR_STOPPED => typed(&event, LatticeEvent::ActorStopped),
+            LatticeEvent::ACTOR_START_FAILED => typed(&event, LatticeEvent::ActorStartFailed),
+            LatticeEvent::ACTOR_UPDATED => typed(&event, LatticeEvent::ActorUpdated),
+            LatticeEvent::ACTOR_UPDATE_FAILED => typed(&event, LatticeEvent::ActorUpdateFailed),
+            LatticeEvent::PROVIDER_STARTED => typed(&event, LatticeEvent::ProviderStarted),
+            LatticeEvent::PROVIDER_START_FAILED => typed(&event, LatticeEvent::ProviderStartFailed),
+            LatticeEvent::PROVIDER_STOPPED => typed(&event, LatticeEvent::ProviderStopped),
+            LatticeEvent::LINKDEF_SET => typed(&event, LatticeEvent::LinkDefSet),
+            LatticeEvent::LINKDEF_DELETED => typed(&event, LatticeEvent::LinkDefDeleted),
+            LatticeEvent::HOST_HEARTBEAT => typed(&event, LatticeEvent::HostHeartbeat),
+            LatticeEvent::HOST_STARTED => typed(&event, LatticeEvent::HostStarted),
+            LatticeEvent::HOST_STOPPED => typed(&event, LatticeEvent::HostStopped),
+            _ => None,
+        };
+        parsed.unwrap_or(LatticeEvent::Other(event))
+    }
+}
+
+/// A change to the link definitions in the lattice metadata bucket
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkChange {
+    /// A link was added or its values changed
+    Put(LinkDefinition),
+    /// A link was removed
+    Delete {
+        actor_id: String,
+        contract_id: String,
+        link_name: String,
+    },
+}
+
 /// A list of link definitions
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct LinkDefinitionList {
     pub links: Vec<LinkDefinition>,
 }
 
+/// Everything [`crate::Client::get_lattice_snapshot`] found in the lattice at one point in time.
+/// Each part is gathered separately, and a part that couldn't be retrieved is left out with the
+/// reason in its `_error` field rather than failing the whole snapshot
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct LatticeSnapshot {
+    /// When the snapshot was started, in milliseconds since the Unix epoch
+    #[serde(default)]
+    pub captured_at_ms: u64,
+    /// The hosts that answered the hosts query
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hosts: Option<Vec<Host>>,
+    /// Why the hosts query failed, if it did. `hosts` is then `None` and `inventories` is empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hosts_error: Option<String>,
+    /// The inventory of each host that answered, in the same order as `hosts`
+    #[serde(default)]
+    pub inventories: Vec<InventorySnapshot>,
+    /// The lattice's link definitions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<LinkDefinitionList>,
+    /// Why the link definitions couldn't be retrieved, if they couldn't. `links` is then `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links_error: Option<String>,
+    /// The lattice's claims
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claims: Option<GetClaimsResponse>,
+    /// Why the claims couldn't be retrieved, if they couldn't. `claims` is then `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claims_error: Option<String>,
+}
+
+impl LatticeSnapshot {
+    /// Whether every part of the snapshot, including every host's inventory, was retrieved
+    pub fn is_complete(&self) -> bool {
+        self.hosts_error.is_none()
+            && self.links_error.is_none()
+            && self.claims_error.is_none()
+            && self
+                .inventories
+                .iter()
+                .all(|inventory| inventory.error.is_none())
+    }
+
+    /// The IDs of the hosts that answered the hosts query but whose inventory couldn't be
+    /// retrieved
+    pub fn unreachable_hosts(&self) -> Vec<&str> {
+        self.inventories
+            .iter()
+            .filter(|inventory| inventory.error.is_some())
+            .map(|inventory| inventory.host_id.as_str())
+            .collect()
+    }
+}
+
+/// A host's inventory in a [`LatticeSnapshot`], or why it couldn't be retrieved
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct InventorySnapshot {
+    /// The ID of the host the inventory was requested from
+    #[serde(default)]
+    pub host_id: String,
+    /// The host's inventory, or `None` if it couldn't be retrieved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inventory: Option<HostInventory>,
+    /// Why the inventory couldn't be retrieved, e.g. the host didn't answer in time. Exactly one
+    /// of `inventory` and `error` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// An update emitted by a long-running helper as it makes progress, so that callers can show
+/// live feedback rather than waiting for the final result
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    /// An auction for the given actor or provider reference has been published
+    AuctionStarted { reference: String },
+    /// A host has been chosen to run part of the operation
+    HostSelected { host: String },
+    /// A command has been sent to a host
+    CommandSent { host: String, op: String },
+    /// A host has accepted a command
+    CommandAcked { host: String, op: String },
+    /// A phase of the operation failed. `host` is set if the failure is specific to one host
+    PhaseFailed {
+        phase: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        host: Option<String>,
+        error: String,
+    },
+}
+
 /// One of a potential list of responses to a provider auction
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct ProviderAuctionAck {
@@ -213,7 +1126,59 @@ pub struct ProviderDescription {
 
 pub type ProviderDescriptions = Vec<ProviderDescription>;
 
+/// The data of a `provider_started` lattice event
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ProviderStarted {
+    /// The provider's public key
+    #[serde(default)]
+    pub public_key: String,
+    /// Image reference the provider was started from, if applicable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_ref: Option<String>,
+    #[serde(default)]
+    pub link_name: String,
+    #[serde(default)]
+    pub contract_id: String,
+    /// The started instance's unique ID
+    #[serde(default)]
+    pub instance_id: String,
+    /// The annotations used in the start request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<AnnotationMap>,
+}
+
+/// The data of a `provider_start_failed` lattice event
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ProviderStartFailed {
+    /// The reference the provider was to be started from
+    #[serde(default)]
+    pub provider_ref: String,
+    #[serde(default)]
+    pub link_name: String,
+    /// Why the provider failed to start
+    #[serde(default)]
+    pub error: String,
+}
+
+/// The data of a `provider_stopped` lattice event
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ProviderStopped {
+    /// The provider's public key
+    #[serde(default)]
+    pub public_key: String,
+    #[serde(default)]
+    pub link_name: String,
+    #[serde(default)]
+    pub contract_id: String,
+    /// The stopped instance's unique ID
+    #[serde(default)]
+    pub instance_id: String,
+    /// Why the provider stopped
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Clone, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct RegistryCredential {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub password: Option<String>,
@@ -228,9 +1193,47 @@ pub struct RegistryCredential {
     pub registry_type: String,
 }
 
+/// Secrets are redacted so that credentials never end up in logs
+impl std::fmt::Debug for RegistryCredential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let redacted = |secret: &Option<String>| secret.as_ref().map(|_| "<redacted>");
+        f.debug_struct("RegistryCredential")
+            .field("password", &redacted(&self.password))
+            .field("token", &redacted(&self.token))
+            .field("username", &self.username)
+            .field("registry_type", &self.registry_type)
+            .finish()
+    }
+}
+
+/// The metadata of a lattice control event along with its parsed data
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypedEvent<T> {
+    /// The CloudEvent ID, unique to each event
+    pub id: String,
+    /// The ID of the host that published the event
+    pub host_id: String,
+    /// When the event was published, if the host recorded it
+    pub timestamp: Option<SystemTime>,
+    pub data: T,
+}
+
 /// A set of credentials to be used for fetching from specific registries
 pub type RegistryCredentialMap = std::collections::HashMap<String, RegistryCredential>;
 
+/// The values of a named config stored in the lattice metadata bucket, which providers read
+/// instead of (or as well as) link values
+pub type ConfigValues = std::collections::HashMap<String, String>;
+
+/// A named config and its values, as listed by [`crate::Client::list_configs`]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct NamedConfig {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub values: ConfigValues,
+}
+
 /// A request to remove a link definition and detach the relevant actor
 /// from the given provider
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
@@ -246,6 +1249,65 @@ pub struct RemoveLinkDefinitionRequest {
     pub link_name: String,
 }
 
+/// Identifies one logical command, so that a host can recognize a command it has already carried
+/// out when it is sent again after a timeout. The client gives every command it sends a new ID,
+/// which is kept for any retries; callers doing their own retries can reuse one with
+/// [`crate::Client::scale_actor_with_id`]. Hosts that don't know the field ignore it
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct CommandId(String);
+
+impl CommandId {
+    /// Generates a random (version 4) UUID to use as a command ID
+    pub fn new() -> CommandId {
+        use ring::rand::SecureRandom as _;
+        let mut bytes = [0u8; 16];
+        ring::rand::SystemRandom::new()
+            .fill(&mut bytes)
+            .expect("the system random number generator should be available");
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let hex = data_encoding::HEXLOWER.encode(&bytes);
+        CommandId(format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        ))
+    }
+
+    /// The ID as it is sent to hosts
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for CommandId {
+    fn default() -> CommandId {
+        CommandId::new()
+    }
+}
+
+impl std::fmt::Display for CommandId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<String> for CommandId {
+    fn from(id: String) -> CommandId {
+        CommandId(id)
+    }
+}
+
+impl From<&str> for CommandId {
+    fn from(id: &str) -> CommandId {
+        CommandId(id.to_string())
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct ScaleActorCommand {
     /// Image reference for the actor.
@@ -255,6 +1317,9 @@ pub struct ScaleActorCommand {
     /// example, autonomous agents may wish to "tag" scale requests as part of a given deployment
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub annotations: Option<AnnotationMap>,
+    /// Identifies this command across retries, see [`CommandId`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command_id: Option<CommandId>,
     /// The maximum number of concurrent executing instances of this actor. If set to `None` there
     /// there is no maximum, while setting to `0` will stop the actor.
     // NOTE: renaming to `count` lets us remain backwards compatible for a few minor versions
@@ -280,6 +1345,9 @@ pub struct StartActorCommand {
     /// example, autonomous agents may wish to "tag" start requests as part of a given deployment
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub annotations: Option<AnnotationMap>,
+    /// Identifies this command across retries, see [`CommandId`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command_id: Option<CommandId>,
     /// The number of actors to start
     /// A zero value will be interpreted as 1.
     #[serde(default)]
@@ -297,6 +1365,9 @@ pub struct StartProviderCommand {
     /// example, autonomous agents may wish to "tag" start requests as part of a given deployment
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub annotations: Option<AnnotationMap>,
+    /// Identifies this command across retries, see [`CommandId`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command_id: Option<CommandId>,
     /// Optional provider configuration in the form of an opaque string. Many
     /// providers prefer base64-encoded JSON here, though that data should never
     /// exceed 500KB
@@ -326,6 +1397,9 @@ pub struct StopActorCommand {
     /// annotations will be stopped
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub annotations: Option<AnnotationMap>,
+    /// Identifies this command across retries, see [`CommandId`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command_id: Option<CommandId>,
     /// The ID of the target host
     #[serde(default)]
     pub host_id: String,
@@ -342,6 +1416,18 @@ pub struct StopHostCommand {
     pub timeout: Option<u64>,
 }
 
+/// A command that sets a label on a host, or removes it. Labels are matched against auction
+/// constraints, so changing them changes which auctions the host bids in
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct HostLabel {
+    /// The label's key
+    #[serde(default)]
+    pub key: String,
+    /// The label's value. Ignored when removing a label
+    #[serde(default)]
+    pub value: String,
+}
+
 /// A request to stop the given provider on the indicated host
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct StopProviderCommand {
@@ -409,3 +1495,410 @@ pub struct LinkDefinition {
     pub contract_id: String,
     pub values: LinkSettings,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constraints_match_hosts_with_every_label() {
+        let constraints = Constraints::new().os("linux").label("zone", "us-east-1a");
+        let labels = HashMap::from([
+            (Constraints::OS.to_string(), "linux".to_string()),
+            (Constraints::ARCH.to_string(), "x86_64".to_string()),
+            ("zone".to_string(), "us-east-1a".to_string()),
+        ]);
+        assert!(constraints.matches(&labels));
+        assert!(Constraints::new().matches(&HashMap::new()));
+        assert!(!constraints.clone().arch("aarch64").matches(&labels));
+        assert!(!constraints.matches(&HashMap::from([(
+            Constraints::OS.to_string(),
+            "linux".to_string()
+        )])));
+    }
+
+    #[test]
+    fn constraints_keys_are_validated() {
+        assert!(Constraints::new().os_family("unix").validate().is_ok());
+        for key in ["", "zone.*", ">"] {
+            assert!(matches!(
+                Constraints::new().label(key, "x").validate(),
+                Err(crate::Error::InvalidArgument {
+                    argument: "constraint key",
+                    ..
+                })
+            ));
+        }
+        let map = ConstraintMap::from([("zone".to_string(), "a".to_string())]);
+        assert_eq!(
+            serde_json::to_value(Constraints::from(map.clone())).unwrap(),
+            serde_json::json!({"zone": "a"})
+        );
+        assert_eq!(Constraints::from(map.clone()).into_map(), map);
+    }
+
+    #[test]
+    fn acks_convert_into_results() {
+        let accepted = CtlOperationAck {
+            accepted: true,
+            error: String::new(),
+        };
+        assert_eq!(accepted.into_result(), Ok(()));
+        let rejected: CtlOperationAck =
+            serde_json::from_str(r#"{"accepted": false, "error": "no such actor"}"#).unwrap();
+        let err = rejected.into_result().unwrap_err();
+        assert_eq!(err.error, "no such actor");
+        assert_eq!(err.to_string(), "request was not accepted: no such actor");
+    }
+
+    #[test]
+    fn command_ids_round_trip_and_are_omitted_when_unset() {
+        let id = CommandId::new();
+        assert_eq!(id.as_str().len(), 36);
+        assert_eq!(id.as_str().as_bytes()[14], b'4');
+        assert_ne!(id, CommandId::new());
+
+        let command = StopActorCommand {
+            actor_ref: "echo".to_string(),
+            host_id: "Nxxx".to_string(),
+            command_id: Some(id.clone()),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&command).unwrap();
+        assert_eq!(json["command_id"], id.as_str());
+        assert_eq!(
+            serde_json::from_value::<StopActorCommand>(json).unwrap(),
+            command
+        );
+        let without = serde_json::to_value(StopActorCommand::default()).unwrap();
+        assert!(without.get("command_id").is_none());
+    }
+
+    #[test]
+    fn snapshots_flag_what_is_missing() {
+        let mut snapshot = LatticeSnapshot {
+            hosts: Some(vec![Host::default(), Host::default()]),
+            inventories: vec![
+                InventorySnapshot {
+                    host_id: "N1".to_string(),
+                    inventory: Some(HostInventory::default()),
+                    error: None,
+                },
+                InventorySnapshot {
+                    host_id: "N2".to_string(),
+                    inventory: None,
+                    error: Some("timed out".to_string()),
+                },
+            ],
+            links: Some(LinkDefinitionList::default()),
+            claims: Some(GetClaimsResponse::default()),
+            ..Default::default()
+        };
+        assert_eq!(snapshot.unreachable_hosts(), ["N2"]);
+        assert!(!snapshot.is_complete());
+
+        snapshot.inventories.pop();
+        assert!(snapshot.is_complete());
+        snapshot.claims = None;
+        snapshot.claims_error = Some("no responders".to_string());
+        assert!(!snapshot.is_complete());
+    }
+
+    #[test]
+    fn command_batch_wire_format() {
+        let batch = CommandBatch::new()
+            .push(BatchCommand::ScaleActor(ScaleActorCommand {
+                actor_ref: "wasmcloud.azurecr.io/echo:0.3.8".to_string(),
+                max_concurrent: Some(5),
+                host_id: "Nxxx".to_string(),
+                annotations: None,
+                command_id: None,
+            }))
+            .push(BatchCommand::StopProvider(StopProviderCommand {
+                contract_id: "wasmcloud:httpserver".to_string(),
+                host_id: "Nxxx".to_string(),
+                link_name: "default".to_string(),
+                provider_ref: "Vxxx".to_string(),
+                annotations: None,
+            }))
+            .stop_on_failure(true);
+
+        let json = serde_json::to_value(&batch).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "commands": [
+                    {
+                        "type": "scale_actor",
+                        "command": {
+                            "actor_ref": "wasmcloud.azurecr.io/echo:0.3.8",
+                            "count": 5,
+                            "host_id": "Nxxx"
+                        }
+                    },
+                    {
+                        "type": "stop_provider",
+                        "command": {
+                            "contract_id": "wasmcloud:httpserver",
+                            "host_id": "Nxxx",
+                            "link_name": "default",
+                            "provider_ref": "Vxxx"
+                        }
+                    }
+                ],
+                "stop_on_failure": true
+            })
+        );
+
+        let parsed: CommandBatch = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, batch);
+    }
+
+    #[test]
+    fn command_batch_defaults() {
+        let parsed: CommandBatch = serde_json::from_str("{}").unwrap();
+        assert!(parsed.commands.is_empty());
+        assert!(!parsed.stop_on_failure);
+
+        let resp: CommandBatchResponse =
+            serde_json::from_str(r#"{"acks":[{"accepted":true},{"error":"nope"}]}"#).unwrap();
+        assert_eq!(
+            resp.acks,
+            vec![
+                CtlOperationAck {
+                    accepted: true,
+                    error: String::new()
+                },
+                CtlOperationAck {
+                    accepted: false,
+                    error: "nope".to_string()
+                }
+            ]
+        );
+    }
+
+    fn instance(app: Option<&str>) -> ActorInstance {
+        ActorInstance {
+            annotations: app.map(annotations::app),
+            instance_id: format!("{}-instance", app.unwrap_or("unmanaged")),
+            ..Default::default()
+        }
+    }
+
+    fn provider(id: &str, app: Option<&str>) -> ProviderDescription {
+        ProviderDescription {
+            annotations: app.map(annotations::app),
+            id: id.to_string(),
+            contract_id: "wasmcloud:httpserver".to_string(),
+            link_name: "default".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn link(actor_id: &str, values: LinkSettings) -> LinkDefinition {
+        LinkDefinition {
+            actor_id: actor_id.to_string(),
+            provider_id: "Vhttp".to_string(),
+            link_name: "default".to_string(),
+            contract_id: "wasmcloud:httpserver".to_string(),
+            values,
+        }
+    }
+
+    /// Two hosts running a mix of workloads managed by wadm for two applications and workloads
+    /// started by hand
+    fn inventories() -> Vec<HostInventory> {
+        vec![
+            HostInventory {
+                host_id: "Nhost1".to_string(),
+                actors: vec![
+                    ActorDescription {
+                        id: "Mecho".to_string(),
+                        instances: vec![instance(Some("echo")), instance(None)],
+                        ..Default::default()
+                    },
+                    ActorDescription {
+                        id: "Mkvcounter".to_string(),
+                        instances: vec![instance(Some("kvcounter"))],
+                        ..Default::default()
+                    },
+                ],
+                providers: vec![provider("Vhttp", Some("echo")), provider("Vredis", None)],
+                ..Default::default()
+            },
+            HostInventory {
+                host_id: "Nhost2".to_string(),
+                actors: vec![
+                    ActorDescription {
+                        id: "Mecho".to_string(),
+                        instances: vec![instance(Some("echo"))],
+                        ..Default::default()
+                    },
+                    ActorDescription {
+                        id: "Mloose".to_string(),
+                        instances: vec![instance(None)],
+                        ..Default::default()
+                    },
+                ],
+                providers: vec![provider("Vhttp", Some("kvcounter"))],
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn actors_for_app_narrows_instances() {
+        let inventories = inventories();
+        let actors = inventories[0].actors_for_app("echo");
+        assert_eq!(actors.len(), 1);
+        assert_eq!(actors[0].id, "Mecho");
+        assert_eq!(actors[0].instances, vec![instance(Some("echo"))]);
+
+        assert!(inventories[1].actors_for_app("kvcounter").is_empty());
+        assert!(inventories[0].actors_for_app("missing").is_empty());
+    }
+
+    #[test]
+    fn annotations_match_as_a_subset() {
+        let managed = Annotations::managed_by("wadm");
+        let app = annotations::app("echo");
+        assert!(managed.matches(Some(&app)));
+        assert!(Annotations::app("echo").matches(Some(&app)));
+        assert!(Annotations::new().matches(None));
+        assert!(Annotations::new().matches(Some(&app)));
+
+        assert!(!managed.matches(None));
+        assert!(!Annotations::app("kvcounter").matches(Some(&app)));
+        assert!(!managed
+            .clone()
+            .annotation("extra", "key")
+            .matches(Some(&app)));
+        assert!(!Annotations::managed_by("someone-else").matches(Some(&app)));
+        assert_eq!(
+            serde_json::to_value(&managed).unwrap(),
+            serde_json::json!({annotations::MANAGED_BY: "wadm"})
+        );
+    }
+
+    #[test]
+    fn inventories_filter_by_annotations() {
+        let inventories = inventories();
+        let managed = Annotations::managed_by(annotations::MANAGED_BY_WADM);
+        let actors = inventories[0].actors_with_annotations(&managed);
+        assert_eq!(
+            actors
+                .iter()
+                .map(|actor| actor.id.as_str())
+                .collect::<Vec<_>>(),
+            ["Mecho", "Mkvcounter"]
+        );
+        assert_eq!(actors[0].instances, vec![instance(Some("echo"))]);
+        assert_eq!(
+            inventories[1].actors_with_annotations(&Annotations::new()),
+            inventories[1].actors
+        );
+
+        let providers = inventories[1].providers_with_annotations(&Annotations::app("kvcounter"));
+        assert_eq!(providers, vec![provider("Vhttp", Some("kvcounter"))]);
+        assert!(inventories[1]
+            .providers_with_annotations(&managed.annotation("wasmcloud.dev/scaler", "x"))
+            .is_empty());
+    }
+
+    #[test]
+    fn app_footprint_aggregates_across_hosts() {
+        let links = vec![
+            link("Mecho", LinkSettings::new()),
+            link("Mloose", annotations::app("echo")),
+            link("Mkvcounter", LinkSettings::new()),
+            link("Mloose", LinkSettings::new()),
+        ];
+        let footprint = AppFootprint::from_inventories("echo", &inventories(), &links);
+
+        assert_eq!(footprint.name, "echo");
+        assert_eq!(footprint.actors.len(), 2);
+        for host in ["Nhost1", "Nhost2"] {
+            let actors = &footprint.actors[host];
+            assert_eq!(actors.len(), 1);
+            assert_eq!(actors[0].instances, vec![instance(Some("echo"))]);
+        }
+        assert_eq!(
+            footprint.providers,
+            HashMap::from([("Nhost1".to_string(), vec![provider("Vhttp", Some("echo"))])])
+        );
+        assert_eq!(footprint.links, links[..2].to_vec());
+
+        assert!(AppFootprint::from_inventories("missing", &inventories(), &links).is_empty());
+    }
+
+    fn event(ty: &str, data: serde_json::Value) -> Event {
+        use cloudevents::EventBuilder as _;
+        cloudevents::EventBuilderV10::new()
+            .id("1")
+            .source("NCPGH5CVAP4XGOBOCXNUMXAXSFXL2DFZG2KXDGD2RLNJBMVGGVD4VQ5P")
+            .ty(ty)
+            .time("2023-05-01T12:00:00Z")
+            .data("application/json", data)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn lattice_event_from_known_type() {
+        let raw = event(
+            LatticeEvent::ACTOR_STARTED,
+            serde_json::json!({"public_key": "Mxxx", "instance_id": "abc", "revision": 2}),
+        );
+        let LatticeEvent::ActorStarted(started) = LatticeEvent::from(raw) else {
+            panic!("actor_started was not parsed");
+        };
+        assert_eq!(started.id, "1");
+        assert_eq!(
+            started.host_id,
+            "NCPGH5CVAP4XGOBOCXNUMXAXSFXL2DFZG2KXDGD2RLNJBMVGGVD4VQ5P"
+        );
+        assert_eq!(
+            started.timestamp,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_682_942_400))
+        );
+        assert_eq!(started.data.public_key, "Mxxx");
+        assert_eq!(started.data.instance_id, "abc");
+        assert_eq!(started.data.image_ref, None);
+    }
+
+    #[test]
+    fn lattice_event_falls_back_to_other() {
+        let unknown = event("com.wasmcloud.lattice.something_new", serde_json::json!({}));
+        assert_eq!(
+            LatticeEvent::from(unknown.clone()),
+            LatticeEvent::Other(unknown)
+        );
+
+        let malformed = event(
+            LatticeEvent::HOST_HEARTBEAT,
+            serde_json::json!({"uptime_seconds": "a while"}),
+        );
+        assert_eq!(
+            LatticeEvent::from(malformed.clone()),
+            LatticeEvent::Other(malformed)
+        );
+    }
+
+    #[test]
+    fn registry_credential_debug_redacts_secrets() {
+        let registries = RegistryCredentialMap::from([(
+            "ghcr.io".to_string(),
+            RegistryCredential {
+                password: Some("hunter2".to_string()),
+                token: Some("s3cr3t-token".to_string()),
+                username: Some("admin".to_string()),
+                registry_type: "oci".to_string(),
+            },
+        )]);
+        let debug = format!("{registries:?}");
+        assert!(!debug.contains("hunter2"));
+        assert!(!debug.contains("s3cr3t-token"));
+        assert!(debug.contains("admin"));
+        assert!(debug.contains("<redacted>"));
+    }
+}
@@ -0,0 +1,282 @@
+//! Checks on caller-supplied identifiers, so that a bad one fails up front with a clear error
+//! instead of producing a request that hosts ignore
+
+use crate::{Error, Result};
+
+/// Characters that NATS treats as wildcards when they appear in a subject
+const WILDCARDS: [char; 2] = ['*', '>'];
+
+fn invalid(argument: &'static str, reason: &str) -> Error {
+    Error::InvalidArgument {
+        argument,
+        reason: reason.to_string(),
+    }
+}
+
+/// Checks a host label key: it must not be empty or contain NATS wildcard characters
+pub(crate) fn label_key(key: &str) -> Result<()> {
+    key_of("label key", key)
+}
+
+/// Checks an auction constraint key, which is matched against host label keys and so has to be a
+/// valid label key
+pub(crate) fn constraint_key(key: &str) -> Result<()> {
+    key_of("constraint key", key)
+}
+
+fn key_of(argument: &'static str, key: &str) -> Result<()> {
+    if key.is_empty() {
+        Err(invalid(argument, "must not be empty"))
+    } else if key.contains(WILDCARDS) {
+        Err(invalid(
+            argument,
+            "must not contain the NATS wildcard characters `*` or `>`",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks a config name, which becomes part of a key in the lattice metadata bucket: it must not be
+/// empty, and may only contain the characters a bucket key allows other than `.`, which would split
+/// the key into several subject tokens
+pub(crate) fn config_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        Err(invalid("config name", "must not be empty"))
+    } else if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '/' | '_' | '='))
+    {
+        Err(invalid(
+            "config name",
+            "may only contain ASCII letters, digits, `-`, `/`, `_` and `=`",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks a lattice prefix, which is a single token of every control subject
+pub(crate) fn lattice_prefix(prefix: &str) -> Result<()> {
+    if prefix.is_empty() {
+        return Err(invalid("lattice prefix", "must not be empty"));
+    }
+    subject_token("lattice prefix", prefix)
+}
+
+/// Checks a topic prefix, which may span several subject tokens but can't have an empty one
+pub(crate) fn topic_prefix(prefix: &str) -> Result<()> {
+    multi_token_prefix("topic prefix", prefix)
+}
+
+/// Checks a reply inbox prefix, which follows the same rules as a topic prefix
+pub(crate) fn inbox_prefix(prefix: &str) -> Result<()> {
+    multi_token_prefix("inbox prefix", prefix)
+}
+
+fn multi_token_prefix(argument: &'static str, prefix: &str) -> Result<()> {
+    if prefix.split('.').any(str::is_empty) {
+        return Err(invalid(
+            argument,
+            "must not be empty or start, end or contain two `.` in a row",
+        ));
+    }
+    prefix
+        .split('.')
+        .try_for_each(|token| subject_token(argument, token))
+}
+
+/// Checks a queue group name, which NATS requires to be non-empty and free of whitespace
+pub(crate) fn queue_group(group: &str) -> Result<()> {
+    if group.is_empty() {
+        Err(invalid("queue group", "must not be empty"))
+    } else if group.contains(char::is_whitespace) {
+        Err(invalid("queue group", "must not contain whitespace"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks the name of a JetStream stream or consumer, which becomes a single token of the
+/// JetStream API subjects
+pub(crate) fn jetstream_name(argument: &'static str, name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(invalid(argument, "must not be empty"));
+    }
+    subject_token(argument, name)
+}
+
+/// Checks the ID of the host a command or query is addressed to
+pub(crate) fn host_id(host_id: &str) -> Result<()> {
+    if host_id.is_empty() {
+        return Err(invalid("host ID", "must not be empty"));
+    }
+    subject_token("host ID", host_id)
+}
+
+/// Checks the subject of a set of claims (an actor or provider public key), which becomes a single
+/// token of its key in the lattice metadata bucket
+pub(crate) fn claims_subject(subject: &str) -> Result<()> {
+    if subject.is_empty() {
+        return Err(invalid("claims subject", "must not be empty"));
+    }
+    subject_token("claims subject", subject)
+}
+
+/// Checks an identifier that becomes a single subject token. An empty one is allowed here, since
+/// some callers have their own rules about that
+pub(crate) fn subject_token(argument: &'static str, token: &str) -> Result<()> {
+    if token.contains(char::is_whitespace) || token.contains('.') || token.contains(WILDCARDS) {
+        Err(invalid(
+            argument,
+            "contains characters not permitted in NATS subjects (whitespace, `.`, `*` or `>`)",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_keys_must_be_non_empty_without_wildcards() {
+        assert!(label_key("hostcore.os").is_ok());
+        assert!(label_key("zone").is_ok());
+        for key in ["", "*", "zone.>", "a*b"] {
+            assert!(
+                matches!(
+                    label_key(key),
+                    Err(Error::InvalidArgument {
+                        argument: "label key",
+                        ..
+                    })
+                ),
+                "{key:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn inbox_prefixes_and_queue_groups() {
+        assert!(inbox_prefix("_INBOX_tenant.ops").is_ok());
+        for prefix in ["", "_INBOX.", "a..b", "_INBOX.*", "my inbox"] {
+            assert!(
+                matches!(
+                    inbox_prefix(prefix),
+                    Err(Error::InvalidArgument {
+                        argument: "inbox prefix",
+                        ..
+                    })
+                ),
+                "{prefix:?} should be rejected"
+            );
+        }
+        assert!(queue_group("monitors.v2").is_ok());
+        assert!(queue_group("").is_err());
+        assert!(queue_group("my monitors").is_err());
+    }
+
+    #[test]
+    fn claims_subjects_are_single_tokens() {
+        assert!(claims_subject("MBCFOPM6JW2APJLXJD3Z5O4CN7CPYJ2B4FTKLJUR5YR5MITIU7HD3WD5").is_ok());
+        for subject in ["", "M.x", "M*", ">", "M x"] {
+            assert!(
+                matches!(
+                    claims_subject(subject),
+                    Err(Error::InvalidArgument {
+                        argument: "claims subject",
+                        ..
+                    })
+                ),
+                "{subject:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn config_names_must_be_usable_as_key_fragments() {
+        assert!(config_name("http-server_defaults").is_ok());
+        assert!(config_name("team/http=v2").is_ok());
+        for name in [
+            "",
+            "a.b",
+            "a*",
+            ">",
+            "my config",
+            "tab\there",
+            "a:b",
+            "a@b",
+            "café",
+        ] {
+            assert!(
+                matches!(
+                    config_name(name),
+                    Err(Error::InvalidArgument {
+                        argument: "config name",
+                        ..
+                    })
+                ),
+                "{name:?} should be rejected"
+            );
+        }
+    }
+
+    fn assert_rejected(result: Result<()>, argument: &str, value: &str) {
+        assert!(
+            matches!(result, Err(Error::InvalidArgument { argument: a, .. }) if a == argument),
+            "{value:?} should be rejected as a {argument}"
+        );
+    }
+
+    /// One value per class of character that can't appear in a subject token
+    const BAD_TOKENS: [&str; 6] = ["my lattice", "tab\there", "new\nline", "a.b", "a*", ">"];
+
+    #[test]
+    fn lattice_prefixes_must_be_single_tokens() {
+        assert!(lattice_prefix("default").is_ok());
+        assert!(lattice_prefix("prod-east_1").is_ok());
+        assert_rejected(lattice_prefix(""), "lattice prefix", "");
+        for prefix in BAD_TOKENS {
+            assert_rejected(lattice_prefix(prefix), "lattice prefix", prefix);
+        }
+    }
+
+    #[test]
+    fn jetstream_names_must_be_single_tokens() {
+        assert!(jetstream_name("stream name", "wasmbus_events").is_ok());
+        assert_rejected(jetstream_name("consumer name", ""), "consumer name", "");
+        for name in BAD_TOKENS {
+            assert_rejected(jetstream_name("consumer name", name), "consumer name", name);
+        }
+    }
+
+    #[test]
+    fn topic_prefixes_may_span_tokens() {
+        assert!(topic_prefix("wasmbus.ctl").is_ok());
+        assert!(topic_prefix("custom").is_ok());
+        for prefix in [
+            "",
+            ".ctl",
+            "wasmbus.",
+            "wasmbus..ctl",
+            "wasm bus.ctl",
+            "wasmbus.*",
+            ">",
+        ] {
+            assert_rejected(topic_prefix(prefix), "topic prefix", prefix);
+        }
+    }
+
+    #[test]
+    fn host_ids_must_be_non_empty_tokens() {
+        assert!(host_id("NCPGH5CUTGPCFGA5PU5HTEHZYSVUCKBBDYIVCN7TT6VBSOPTHJMJJGWP").is_ok());
+        assert_rejected(host_id(""), "host ID", "");
+        for id in BAD_TOKENS {
+            assert_rejected(host_id(id), "host ID", id);
+        }
+        assert!(subject_token("host ID", "").is_ok());
+        assert_rejected(subject_token("host ID", "N 1"), "host ID", "N 1");
+    }
+}
@@ -0,0 +1,148 @@
+//! Correlation of control commands with the lattice events that report their outcome, for the
+//! `*_and_wait` client methods
+
+use std::time::Duration;
+
+use cloudevents::{AttributesReader, Event};
+use futures::{Stream, StreamExt};
+
+use crate::{json_deserialize, Error, LatticeEvent, Result, TypedEvent};
+
+/// What a matcher made of an event from the target host
+pub(crate) enum Outcome<T> {
+    /// The event reports the command (or one of its instances) completing
+    Done(TypedEvent<T>),
+    /// The event reports the command failing, with the host's reason
+    Failed(String),
+}
+
+/// Waits up to `timeout` for `expected` events from `host_id` that `matcher` reports as done,
+/// failing as soon as it reports a failure. Events from other hosts are never passed to
+/// `matcher`, so concurrent commands against different hosts can't observe each other's events.
+/// `subject` is only used to describe a timeout
+pub(crate) async fn for_events<T, S>(
+    mut payloads: S,
+    subject: &str,
+    host_id: &str,
+    expected: usize,
+    timeout: Duration,
+    mut matcher: impl FnMut(LatticeEvent) -> Option<Outcome<T>>,
+) -> Result<Vec<TypedEvent<T>>>
+where
+    S: Stream + Unpin,
+    S::Item: AsRef<[u8]>,
+{
+    let mut events = Vec::with_capacity(expected);
+    let sleep = tokio::time::sleep(timeout);
+    tokio::pin!(sleep);
+    while events.len() < expected {
+        let payload = tokio::select! {
+            payload = payloads.next() => match payload {
+                Some(payload) => payload,
+                None => return Err(Error::nats("lattice event subscription closed")),
+            },
+            _ = &mut sleep => {
+                return Err(Error::Timeout {
+                    subject: subject.to_string(),
+                    duration: timeout,
+                })
+            }
+        };
+        let Ok(event) = json_deserialize::<Event>(payload.as_ref()) else {
+            continue;
+        };
+        if event.source().as_str() != host_id {
+            continue;
+        }
+        match matcher(LatticeEvent::from(event)) {
+            Some(Outcome::Done(event)) => events.push(event),
+            Some(Outcome::Failed(reason)) => {
+                return Err(Error::CommandFailed {
+                    host_id: host_id.to_string(),
+                    reason,
+                })
+            }
+            None => {}
+        }
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use cloudevents::{EventBuilder as _, EventBuilderV10};
+    use futures::channel::mpsc;
+
+    use super::*;
+    use crate::ActorStarted;
+
+    fn payload(ty: &str, host_id: &str, data: serde_json::Value) -> Vec<u8> {
+        let event = EventBuilderV10::new()
+            .id("1")
+            .source(host_id)
+            .ty(ty)
+            .data("application/json", data)
+            .build()
+            .unwrap();
+        serde_json::to_vec(&event).unwrap()
+    }
+
+    fn actor_started(event: LatticeEvent) -> Option<Outcome<ActorStarted>> {
+        match event {
+            LatticeEvent::ActorStarted(started) if started.data.public_key == "Mxxx" => {
+                Some(Outcome::Done(started))
+            }
+            LatticeEvent::ActorStartFailed(failed) if failed.data.actor_ref == "Mxxx" => {
+                Some(Outcome::Failed(failed.data.error))
+            }
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn ignores_events_from_other_hosts() {
+        let (tx, rx) = mpsc::unbounded();
+        let started = serde_json::json!({"public_key": "Mxxx", "instance_id": "a"});
+        tx.unbounded_send(payload(LatticeEvent::ACTOR_STARTED, "N2", started.clone()))
+            .unwrap();
+        tx.unbounded_send(b"not an event".to_vec()).unwrap();
+        tx.unbounded_send(payload(LatticeEvent::ACTOR_STARTED, "N1", started))
+            .unwrap();
+        let events = for_events(rx, "events", "N1", 1, Duration::from_secs(1), actor_started)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].host_id, "N1");
+    }
+
+    #[tokio::test]
+    async fn failure_event_ends_the_wait() {
+        let (tx, rx) = mpsc::unbounded();
+        tx.unbounded_send(payload(
+            LatticeEvent::ACTOR_START_FAILED,
+            "N1",
+            serde_json::json!({"actor_ref": "Mxxx", "error": "bad signature"}),
+        ))
+        .unwrap();
+        let err = for_events(rx, "events", "N1", 1, Duration::from_secs(1), actor_started)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::CommandFailed { reason, .. } if reason == "bad signature"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn times_out_waiting_for_every_instance() {
+        let (tx, rx) = mpsc::unbounded();
+        tx.unbounded_send(payload(
+            LatticeEvent::ACTOR_STARTED,
+            "N1",
+            serde_json::json!({"public_key": "Mxxx", "instance_id": "a"}),
+        ))
+        .unwrap();
+        let err = for_events(rx, "events", "N1", 2, Duration::from_secs(1), actor_started)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Timeout { .. }));
+        drop(tx);
+    }
+}